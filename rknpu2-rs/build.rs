@@ -0,0 +1,33 @@
+use std::env;
+use std::process;
+
+/// Stop the build with a message instead of a panic and its backtrace.
+fn fail(message: &str) -> ! {
+    println!("cargo:warning={message}");
+    process::exit(1);
+}
+
+fn main() {
+    println!("cargo:rustc-check-cfg=cfg(rknn_v1_6)");
+    println!("cargo:rustc-check-cfg=cfg(rknn_v2)");
+
+    // Set by the build script of rknpu2-sys from the selected SDK
+    let version = env::var("DEP_RKNNRT_VERSION")
+        .unwrap_or_else(|_| fail("rknpu2-sys did not report its SDK version"));
+    let mut numbers = version.split('.').map(|n| n.parse::<u32>().unwrap_or(0));
+    let release = (numbers.next().unwrap_or(0), numbers.next().unwrap_or(0));
+
+    if release >= (1, 6) {
+        println!("cargo:rustc-cfg=rknn_v1_6");
+    }
+    if release >= (2, 0) {
+        println!("cargo:rustc-cfg=rknn_v2");
+    }
+    println!("cargo:rustc-env=RKNPU2_SDK_VERSION={version}");
+
+    if env::var_os("CARGO_FEATURE_CUSTOM_OP").is_some() && release < (1, 6) {
+        fail(&format!(
+            "The 'custom-op' feature needs SDK v1.6.0 or later, but rknpu2-sys is built for v{version}"
+        ));
+    }
+}
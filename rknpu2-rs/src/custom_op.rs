@@ -0,0 +1,463 @@
+//! CPU custom operators implemented in Rust, enabled by the `custom-op`
+//! feature. Needs a v1.6.0 or later runtime.
+//!
+//! A [`CustomOpDef`] names the op type as found in the model and creates a
+//! fresh [`CustomOp`] for every node of that type. The runtime calls back
+//! through `extern "C"` trampolines which catch panics, so a panicking op
+//! fails the run with `RKNN_ERR_FAIL` instead of unwinding into C.
+
+use std::ffi::{c_char, c_int, c_void, CString};
+use std::mem;
+use std::panic::{self, AssertUnwindSafe};
+use std::slice;
+use std::sync::{Arc, Mutex};
+
+use crate::{RKNNContext, RKNNTensorAttr};
+
+type RawOpContext = rknpu2_sys::rknn_custom_op_context;
+type RawTensor = rknpu2_sys::rknn_custom_op_tensor;
+
+/// Element types a tensor can be viewed as.
+pub trait TensorElement: Copy {
+    /// Tensor types stored as `Self`.
+    const TYPES: &'static [rknpu2_sys::rknn_tensor_type];
+}
+
+macro_rules! tensor_element {
+    ($t:ty, $($tensor_type:ident),+) => {
+        impl TensorElement for $t {
+            const TYPES: &'static [rknpu2_sys::rknn_tensor_type] =
+                &[$(rknpu2_sys::$tensor_type),+];
+        }
+    };
+}
+
+tensor_element!(f32, _rknn_tensor_type_RKNN_TENSOR_FLOAT32);
+// fp16 is viewed as its bits
+tensor_element!(
+    u16,
+    _rknn_tensor_type_RKNN_TENSOR_FLOAT16,
+    _rknn_tensor_type_RKNN_TENSOR_UINT16
+);
+tensor_element!(i8, _rknn_tensor_type_RKNN_TENSOR_INT8);
+tensor_element!(
+    u8,
+    _rknn_tensor_type_RKNN_TENSOR_UINT8,
+    _rknn_tensor_type_RKNN_TENSOR_BOOL
+);
+tensor_element!(i16, _rknn_tensor_type_RKNN_TENSOR_INT16);
+tensor_element!(i32, _rknn_tensor_type_RKNN_TENSOR_INT32);
+tensor_element!(u32, _rknn_tensor_type_RKNN_TENSOR_UINT32);
+tensor_element!(i64, _rknn_tensor_type_RKNN_TENSOR_INT64);
+
+/// An input or output of a custom op node.
+#[derive(Debug)]
+pub struct CustomOpTensor<'a> {
+    raw: &'a mut RawTensor,
+}
+
+impl CustomOpTensor<'_> {
+    pub fn attr(&self) -> &RKNNTensorAttr {
+        &self.raw.attr
+    }
+
+    pub fn dims(&self) -> &[u32] {
+        &self.raw.attr.dims[..self.raw.attr.n_dims as usize]
+    }
+
+    fn data(&self) -> *mut u8 {
+        unsafe { (self.raw.mem.virt_addr as *mut u8).add(self.raw.mem.offset as usize) }
+    }
+
+    fn len_of<T: TensorElement>(&self) -> Option<usize> {
+        let data = self.data();
+        let fits = T::TYPES.contains(&self.raw.attr.type_)
+            && !data.is_null()
+            && data.cast::<T>().is_aligned();
+        let len = self.raw.attr.n_elems as usize;
+        // the buffer must hold every element
+        (fits && len * mem::size_of::<T>() <= self.raw.mem.size as usize).then_some(len)
+    }
+
+    /// The elements, `None` if the tensor does not hold `T`.
+    pub fn as_slice<T: TensorElement>(&self) -> Option<&[T]> {
+        let len = self.len_of::<T>()?;
+        Some(unsafe { slice::from_raw_parts(self.data() as *const T, len) })
+    }
+
+    pub fn as_mut_slice<T: TensorElement>(&mut self) -> Option<&mut [T]> {
+        let len = self.len_of::<T>()?;
+        Some(unsafe { slice::from_raw_parts_mut(self.data() as *mut T, len) })
+    }
+}
+
+/// An attribute of the node, as set in the source model.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OpAttr {
+    pub dtype: rknpu2_sys::rknn_tensor_type,
+    pub n_elems: u32,
+    pub data: Vec<u8>,
+}
+
+impl OpAttr {
+    fn values<T: TensorElement, const N: usize>(&self, from: fn([u8; N]) -> T) -> Option<Vec<T>> {
+        T::TYPES.contains(&self.dtype).then(|| {
+            self.data
+                .chunks_exact(N)
+                .map(|b| from(b.try_into().unwrap()))
+                .collect()
+        })
+    }
+
+    pub fn as_f32s(&self) -> Option<Vec<f32>> {
+        self.values(f32::from_ne_bytes)
+    }
+
+    pub fn as_i64s(&self) -> Option<Vec<i64>> {
+        self.values(i64::from_ne_bytes)
+    }
+
+    pub fn as_i32s(&self) -> Option<Vec<i32>> {
+        self.values(i32::from_ne_bytes)
+    }
+
+    /// String attributes are stored as bytes.
+    pub fn as_str(&self) -> Option<&str> {
+        let bytes = self.data.split(|&b| b == 0).next()?;
+        std::str::from_utf8(bytes).ok()
+    }
+}
+
+/// The node a [`CustomOp`] runs for.
+#[derive(Debug)]
+pub struct OpContext<'a> {
+    raw: &'a mut RawOpContext,
+}
+
+impl OpContext<'_> {
+    pub fn attr(&mut self, name: &str) -> Option<OpAttr> {
+        let name = CString::new(name).ok()?;
+        let mut attr: rknpu2_sys::rknn_custom_op_attr = unsafe { mem::zeroed() };
+        unsafe {
+            rknpu2_sys::rknn_custom_op_get_op_attr(self.raw, name.as_ptr(), &mut attr);
+        }
+        if attr.data.is_null() {
+            return None;
+        }
+        let size = attr.n_elems as usize * type_size(attr.dtype);
+        let data = unsafe { slice::from_raw_parts(attr.data as *const u8, size) }.to_vec();
+
+        Some(OpAttr {
+            dtype: attr.dtype,
+            n_elems: attr.n_elems,
+            data,
+        })
+    }
+}
+
+fn type_size(dtype: rknpu2_sys::rknn_tensor_type) -> usize {
+    match dtype {
+        rknpu2_sys::_rknn_tensor_type_RKNN_TENSOR_FLOAT32
+        | rknpu2_sys::_rknn_tensor_type_RKNN_TENSOR_INT32
+        | rknpu2_sys::_rknn_tensor_type_RKNN_TENSOR_UINT32 => 4,
+        rknpu2_sys::_rknn_tensor_type_RKNN_TENSOR_FLOAT16
+        | rknpu2_sys::_rknn_tensor_type_RKNN_TENSOR_INT16
+        | rknpu2_sys::_rknn_tensor_type_RKNN_TENSOR_UINT16 => 2,
+        rknpu2_sys::_rknn_tensor_type_RKNN_TENSOR_INT64 => 8,
+        _ => 1,
+    }
+}
+
+/// The kernel of one custom op node. Errors are RKNN error codes.
+pub trait CustomOp: Send {
+    /// Called once before the first run.
+    fn init(
+        &mut self,
+        ctx: &mut OpContext,
+        inputs: &mut [CustomOpTensor],
+        outputs: &mut [CustomOpTensor],
+    ) -> Result<(), i32> {
+        let _ = (ctx, inputs, outputs);
+        Ok(())
+    }
+
+    fn compute(
+        &mut self,
+        inputs: &mut [CustomOpTensor],
+        outputs: &mut [CustomOpTensor],
+    ) -> Result<(), i32>;
+}
+
+type Factory = dyn Fn() -> Box<dyn CustomOp> + Send + Sync;
+
+#[derive(Clone)]
+pub struct CustomOpDef {
+    /// Op type of the nodes in the model.
+    pub op_type: String,
+    pub factory: Arc<Factory>,
+}
+
+impl CustomOpDef {
+    pub fn new<F>(op_type: &str, factory: F) -> Self
+    where
+        F: Fn() -> Box<dyn CustomOp> + Send + Sync + 'static,
+    {
+        CustomOpDef {
+            op_type: op_type.to_string(),
+            factory: Arc::new(factory),
+        }
+    }
+}
+
+impl std::fmt::Debug for CustomOpDef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CustomOpDef")
+            .field("op_type", &self.op_type)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Most distinct op types per process. The init callback gets no user data,
+/// so every op type is bound to its own trampoline.
+pub const MAX_CUSTOM_OP_TYPES: usize = 16;
+
+/// Factories by trampoline slot. A slot keeps its op type and factory for
+/// the life of the process.
+static SLOTS: Mutex<Vec<(String, Arc<Factory>)>> = Mutex::new(Vec::new());
+
+fn slot_for(def: &CustomOpDef) -> Result<usize, i32> {
+    let mut slots = SLOTS.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(slot) = slots.iter().position(|(t, _)| *t == def.op_type) {
+        // another factory would also be picked up by nodes of contexts
+        // registered earlier
+        if !Arc::ptr_eq(&slots[slot].1, &def.factory) {
+            return Err(rknpu2_sys::RKNN_ERR_PARAM_INVALID);
+        }
+        return Ok(slot);
+    }
+    if slots.len() == MAX_CUSTOM_OP_TYPES {
+        return Err(rknpu2_sys::RKNN_ERR_MALLOC_FAIL);
+    }
+    slots.push((def.op_type.clone(), def.factory.clone()));
+
+    Ok(slots.len() - 1)
+}
+
+/// Run `f` on the tensors of a callback, turning panics into errors.
+unsafe fn call(
+    inputs: *mut RawTensor,
+    n_inputs: u32,
+    outputs: *mut RawTensor,
+    n_outputs: u32,
+    f: impl FnOnce(&mut [CustomOpTensor], &mut [CustomOpTensor]) -> Result<(), i32>,
+) -> c_int {
+    let wrap = |ptr: *mut RawTensor, n: u32| -> Vec<CustomOpTensor> {
+        if ptr.is_null() {
+            return Vec::new();
+        }
+        slice::from_raw_parts_mut(ptr, n as usize)
+            .iter_mut()
+            .map(|raw| CustomOpTensor { raw })
+            .collect()
+    };
+    let mut inputs = wrap(inputs, n_inputs);
+    let mut outputs = wrap(outputs, n_outputs);
+
+    match panic::catch_unwind(AssertUnwindSafe(|| f(&mut inputs, &mut outputs))) {
+        Ok(Ok(())) => 0,
+        Ok(Err(e)) => e,
+        Err(_) => rknpu2_sys::RKNN_ERR_FAIL,
+    }
+}
+
+unsafe extern "C" fn init<const SLOT: usize>(
+    op_ctx: *mut RawOpContext,
+    inputs: *mut RawTensor,
+    n_inputs: u32,
+    outputs: *mut RawTensor,
+    n_outputs: u32,
+) -> c_int {
+    let Some(op_ctx) = op_ctx.as_mut() else {
+        return rknpu2_sys::RKNN_ERR_PARAM_INVALID;
+    };
+    let factory = {
+        let slots = SLOTS.lock().unwrap_or_else(|e| e.into_inner());
+        slots[SLOT].1.clone()
+    };
+
+    call(inputs, n_inputs, outputs, n_outputs, |inputs, outputs| {
+        let mut op = factory();
+        op.init(&mut OpContext { raw: op_ctx }, inputs, outputs)?;
+        // freed in destroy
+        op_ctx.priv_data = Box::into_raw(Box::new(op)) as *mut c_void;
+        Ok(())
+    })
+}
+
+unsafe extern "C" fn prepare(
+    _op_ctx: *mut RawOpContext,
+    _inputs: *mut RawTensor,
+    _n_inputs: u32,
+    _outputs: *mut RawTensor,
+    _n_outputs: u32,
+) -> c_int {
+    0
+}
+
+unsafe extern "C" fn compute(
+    op_ctx: *mut RawOpContext,
+    inputs: *mut RawTensor,
+    n_inputs: u32,
+    outputs: *mut RawTensor,
+    n_outputs: u32,
+) -> c_int {
+    let op = match op_ctx.as_ref() {
+        Some(ctx) if !ctx.priv_data.is_null() => &mut *(ctx.priv_data as *mut Box<dyn CustomOp>),
+        _ => return rknpu2_sys::RKNN_ERR_CTX_INVALID,
+    };
+
+    call(inputs, n_inputs, outputs, n_outputs, |inputs, outputs| {
+        op.compute(inputs, outputs)
+    })
+}
+
+unsafe extern "C" fn destroy(op_ctx: *mut RawOpContext) -> c_int {
+    if let Some(ctx) = op_ctx.as_mut() {
+        if !ctx.priv_data.is_null() {
+            let op = Box::from_raw(ctx.priv_data as *mut Box<dyn CustomOp>);
+            ctx.priv_data = std::ptr::null_mut();
+            // a panicking drop must not unwind into the runtime either
+            if panic::catch_unwind(AssertUnwindSafe(move || drop(op))).is_err() {
+                return rknpu2_sys::RKNN_ERR_FAIL;
+            }
+        }
+    }
+    0
+}
+
+type InitFn =
+    unsafe extern "C" fn(*mut RawOpContext, *mut RawTensor, u32, *mut RawTensor, u32) -> c_int;
+
+const INITS: [InitFn; MAX_CUSTOM_OP_TYPES] = [
+    init::<0>, init::<1>, init::<2>, init::<3>, init::<4>, init::<5>, init::<6>, init::<7>,
+    init::<8>, init::<9>, init::<10>, init::<11>, init::<12>, init::<13>, init::<14>, init::<15>,
+];
+
+/// Register CPU custom ops with a context, before the first run.
+///
+/// Op types are bound to their factory for the whole process: registering a
+/// type again, e.g. with another context, needs a clone of the same
+/// [`CustomOpDef`] and fails with `RKNN_ERR_PARAM_INVALID` for a different
+/// factory. At most [`MAX_CUSTOM_OP_TYPES`] types can be registered.
+pub fn register_custom_ops(ctx: RKNNContext, defs: &[CustomOpDef]) -> Result<(), i32> {
+    let mut ops = Vec::with_capacity(defs.len());
+    for def in defs {
+        let op_type = def.op_type.as_bytes();
+        let mut op: rknpu2_sys::rknn_custom_op = unsafe { mem::zeroed() };
+        // keep the terminating NUL
+        if op_type.len() >= op.op_type.len() || op_type.contains(&0) {
+            return Err(rknpu2_sys::RKNN_ERR_PARAM_INVALID);
+        }
+        for (d, &s) in op.op_type.iter_mut().zip(op_type) {
+            *d = s as c_char;
+        }
+        op.version = rknpu2_sys::RKNN_CUSTOM_OP_VERSION;
+        op.target = rknpu2_sys::_rknn_target_type_RKNN_TARGET_TYPE_CPU;
+        op.init = Some(INITS[slot_for(def)?]);
+        op.prepare = Some(prepare);
+        op.compute = Some(compute);
+        op.destroy = Some(destroy);
+        ops.push(op);
+    }
+
+    let ret =
+        unsafe { rknpu2_sys::rknn_register_custom_ops(ctx, ops.as_mut_ptr(), ops.len() as u32) };
+    if ret == 0 {
+        Ok(())
+    } else {
+        Err(ret)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    /// Adds a constant to every element, panics once `fail` is set.
+    struct AddOp {
+        value: f32,
+        fail: Arc<AtomicBool>,
+    }
+
+    impl CustomOp for AddOp {
+        fn compute(
+            &mut self,
+            inputs: &mut [CustomOpTensor],
+            outputs: &mut [CustomOpTensor],
+        ) -> Result<(), i32> {
+            let src = inputs[0].as_slice::<f32>().ok_or(-5)?.to_vec();
+            let dst = outputs[0].as_mut_slice::<f32>().ok_or(-5)?;
+            for (d, s) in dst.iter_mut().zip(src) {
+                *d = s + self.value;
+            }
+            assert!(!self.fail.load(Ordering::Relaxed), "failing op");
+            Ok(())
+        }
+    }
+
+    fn tensor(data: &mut [f32]) -> RawTensor {
+        let mut raw: RawTensor = unsafe { mem::zeroed() };
+        raw.attr.type_ = rknpu2_sys::_rknn_tensor_type_RKNN_TENSOR_FLOAT32;
+        raw.attr.n_dims = 1;
+        raw.attr.dims[0] = data.len() as u32;
+        raw.attr.n_elems = data.len() as u32;
+        raw.mem.virt_addr = data.as_mut_ptr() as *mut c_void;
+        raw.mem.size = mem::size_of_val(data) as u32;
+        raw
+    }
+
+    #[test]
+    fn test_callbacks() {
+        let fail = Arc::new(AtomicBool::new(false));
+        let op_fail = fail.clone();
+        let def = CustomOpDef::new("cstAddTest", move || {
+            Box::new(AddOp {
+                value: 1.0,
+                fail: op_fail.clone(),
+            })
+        });
+        let slot = slot_for(&def).unwrap();
+        assert_eq!(slot_for(&def.clone()), Ok(slot));
+        let other = CustomOpDef::new("cstAddTest", || {
+            Box::new(AddOp {
+                value: 2.0,
+                fail: Arc::default(),
+            })
+        });
+        assert_eq!(slot_for(&other), Err(rknpu2_sys::RKNN_ERR_PARAM_INVALID));
+
+        let (mut a, mut b) = ([1.0f32, 2.0], [0.0f32; 2]);
+        let (mut input, mut output) = (tensor(&mut a), tensor(&mut b));
+        let mut op_ctx: RawOpContext = unsafe { mem::zeroed() };
+
+        unsafe {
+            assert_eq!(INITS[slot](&mut op_ctx, &mut input, 1, &mut output, 1), 0);
+            assert!(!op_ctx.priv_data.is_null());
+            assert_eq!(compute(&mut op_ctx, &mut input, 1, &mut output, 1), 0);
+            assert_eq!(b, [2.0, 3.0]);
+
+            // a panic turns into an error
+            fail.store(true, Ordering::Relaxed);
+            let ret = compute(&mut op_ctx, &mut input, 1, &mut output, 1);
+            assert_eq!(ret, rknpu2_sys::RKNN_ERR_FAIL);
+
+            assert_eq!(destroy(&mut op_ctx), 0);
+            assert!(op_ctx.priv_data.is_null());
+        }
+
+        // an int8 view of a float tensor is refused
+        let raw = &mut tensor(&mut a);
+        assert!(CustomOpTensor { raw }.as_slice::<i8>().is_none());
+    }
+}
@@ -0,0 +1,542 @@
+//! Drawing results onto `image` crate buffers, enabled by the `draw` feature.
+//!
+//! Everything is rasterized with integer arithmetic and a built-in 5x7 bitmap
+//! font, so the same inputs always give the same pixels on every platform.
+
+use image::{Rgb, RgbImage};
+use ndarray::prelude::*;
+
+use crate::postprocess::labels::Labels;
+use crate::postprocess::pose::{Pose, Skeleton};
+use crate::postprocess::yolo_seg::{InstanceMask, Segmentation};
+use crate::postprocess::{BBox, Detection};
+use crate::track::Track;
+
+/// The 20 color palette of Ultralytics, cycled by class or track id.
+const PALETTE: [[u8; 3]; 20] = [
+    [0xff, 0x38, 0x38],
+    [0xff, 0x9d, 0x97],
+    [0xff, 0x70, 0x1f],
+    [0xff, 0xb2, 0x1d],
+    [0xcf, 0xd2, 0x31],
+    [0x48, 0xf9, 0x0a],
+    [0x92, 0xcc, 0x17],
+    [0x3d, 0xdb, 0x86],
+    [0x1a, 0x93, 0x34],
+    [0x00, 0xd4, 0xbb],
+    [0x2c, 0x99, 0xa8],
+    [0x00, 0xc2, 0xff],
+    [0x34, 0x45, 0x93],
+    [0x64, 0x73, 0xff],
+    [0x00, 0x18, 0xec],
+    [0x84, 0x38, 0xff],
+    [0x52, 0x00, 0x85],
+    [0xcb, 0x38, 0xff],
+    [0xff, 0x95, 0xc8],
+    [0xff, 0x37, 0xc7],
+];
+
+const GLYPH_WIDTH: u32 = 5;
+const GLYPH_HEIGHT: u32 = 7;
+
+/// Printable ASCII from `' '` to `'~'`, one byte per column, bit 0 on top.
+const FONT: [[u8; 5]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x00, 0x00, 0x5f, 0x00, 0x00], // !
+    [0x00, 0x07, 0x00, 0x07, 0x00], // "
+    [0x14, 0x7f, 0x14, 0x7f, 0x14], // #
+    [0x24, 0x2a, 0x7f, 0x2a, 0x12], // $
+    [0x23, 0x13, 0x08, 0x64, 0x62], // %
+    [0x36, 0x49, 0x56, 0x20, 0x50], // &
+    [0x00, 0x05, 0x03, 0x00, 0x00], // '
+    [0x00, 0x1c, 0x22, 0x41, 0x00], // (
+    [0x00, 0x41, 0x22, 0x1c, 0x00], // )
+    [0x2a, 0x1c, 0x7f, 0x1c, 0x2a], // *
+    [0x08, 0x08, 0x3e, 0x08, 0x08], // +
+    [0x00, 0x50, 0x30, 0x00, 0x00], // ,
+    [0x08, 0x08, 0x08, 0x08, 0x08], // -
+    [0x00, 0x60, 0x60, 0x00, 0x00], // .
+    [0x20, 0x10, 0x08, 0x04, 0x02], // /
+    [0x3e, 0x51, 0x49, 0x45, 0x3e], // 0
+    [0x00, 0x42, 0x7f, 0x40, 0x00], // 1
+    [0x42, 0x61, 0x51, 0x49, 0x46], // 2
+    [0x21, 0x41, 0x45, 0x4b, 0x31], // 3
+    [0x18, 0x14, 0x12, 0x7f, 0x10], // 4
+    [0x27, 0x45, 0x45, 0x45, 0x39], // 5
+    [0x3c, 0x4a, 0x49, 0x49, 0x30], // 6
+    [0x01, 0x71, 0x09, 0x05, 0x03], // 7
+    [0x36, 0x49, 0x49, 0x49, 0x36], // 8
+    [0x06, 0x49, 0x49, 0x29, 0x1e], // 9
+    [0x00, 0x36, 0x36, 0x00, 0x00], // :
+    [0x00, 0x56, 0x36, 0x00, 0x00], // ;
+    [0x08, 0x14, 0x22, 0x41, 0x00], // <
+    [0x14, 0x14, 0x14, 0x14, 0x14], // =
+    [0x00, 0x41, 0x22, 0x14, 0x08], // >
+    [0x02, 0x01, 0x51, 0x09, 0x06], // ?
+    [0x32, 0x49, 0x79, 0x41, 0x3e], // @
+    [0x7e, 0x11, 0x11, 0x11, 0x7e], // A
+    [0x7f, 0x49, 0x49, 0x49, 0x36], // B
+    [0x3e, 0x41, 0x41, 0x41, 0x22], // C
+    [0x7f, 0x41, 0x41, 0x22, 0x1c], // D
+    [0x7f, 0x49, 0x49, 0x49, 0x41], // E
+    [0x7f, 0x09, 0x09, 0x09, 0x01], // F
+    [0x3e, 0x41, 0x49, 0x49, 0x7a], // G
+    [0x7f, 0x08, 0x08, 0x08, 0x7f], // H
+    [0x00, 0x41, 0x7f, 0x41, 0x00], // I
+    [0x20, 0x40, 0x41, 0x3f, 0x01], // J
+    [0x7f, 0x08, 0x14, 0x22, 0x41], // K
+    [0x7f, 0x40, 0x40, 0x40, 0x40], // L
+    [0x7f, 0x02, 0x0c, 0x02, 0x7f], // M
+    [0x7f, 0x04, 0x08, 0x10, 0x7f], // N
+    [0x3e, 0x41, 0x41, 0x41, 0x3e], // O
+    [0x7f, 0x09, 0x09, 0x09, 0x06], // P
+    [0x3e, 0x41, 0x51, 0x21, 0x5e], // Q
+    [0x7f, 0x09, 0x19, 0x29, 0x46], // R
+    [0x46, 0x49, 0x49, 0x49, 0x31], // S
+    [0x01, 0x01, 0x7f, 0x01, 0x01], // T
+    [0x3f, 0x40, 0x40, 0x40, 0x3f], // U
+    [0x1f, 0x20, 0x40, 0x20, 0x1f], // V
+    [0x3f, 0x40, 0x38, 0x40, 0x3f], // W
+    [0x63, 0x14, 0x08, 0x14, 0x63], // X
+    [0x07, 0x08, 0x70, 0x08, 0x07], // Y
+    [0x61, 0x51, 0x49, 0x45, 0x43], // Z
+    [0x00, 0x7f, 0x41, 0x41, 0x00], // [
+    [0x02, 0x04, 0x08, 0x10, 0x20], // \
+    [0x00, 0x41, 0x41, 0x7f, 0x00], // ]
+    [0x04, 0x02, 0x01, 0x02, 0x04], // ^
+    [0x40, 0x40, 0x40, 0x40, 0x40], // _
+    [0x00, 0x01, 0x02, 0x04, 0x00], // `
+    [0x20, 0x54, 0x54, 0x54, 0x78], // a
+    [0x7f, 0x48, 0x44, 0x44, 0x38], // b
+    [0x38, 0x44, 0x44, 0x44, 0x20], // c
+    [0x38, 0x44, 0x44, 0x48, 0x7f], // d
+    [0x38, 0x54, 0x54, 0x54, 0x18], // e
+    [0x08, 0x7e, 0x09, 0x01, 0x02], // f
+    [0x0c, 0x52, 0x52, 0x52, 0x3e], // g
+    [0x7f, 0x08, 0x04, 0x04, 0x78], // h
+    [0x00, 0x44, 0x7d, 0x40, 0x00], // i
+    [0x20, 0x40, 0x44, 0x3d, 0x00], // j
+    [0x7f, 0x10, 0x28, 0x44, 0x00], // k
+    [0x00, 0x41, 0x7f, 0x40, 0x00], // l
+    [0x7c, 0x04, 0x18, 0x04, 0x78], // m
+    [0x7c, 0x08, 0x04, 0x04, 0x78], // n
+    [0x38, 0x44, 0x44, 0x44, 0x38], // o
+    [0x7c, 0x14, 0x14, 0x14, 0x08], // p
+    [0x08, 0x14, 0x14, 0x18, 0x7c], // q
+    [0x7c, 0x08, 0x04, 0x04, 0x08], // r
+    [0x48, 0x54, 0x54, 0x54, 0x20], // s
+    [0x04, 0x3f, 0x44, 0x40, 0x20], // t
+    [0x3c, 0x40, 0x40, 0x20, 0x7c], // u
+    [0x1c, 0x20, 0x40, 0x20, 0x1c], // v
+    [0x3c, 0x40, 0x30, 0x40, 0x3c], // w
+    [0x44, 0x28, 0x10, 0x28, 0x44], // x
+    [0x0c, 0x50, 0x50, 0x50, 0x3c], // y
+    [0x44, 0x64, 0x54, 0x4c, 0x44], // z
+    [0x00, 0x08, 0x36, 0x41, 0x00], // {
+    [0x00, 0x00, 0x7f, 0x00, 0x00], // |
+    [0x00, 0x41, 0x36, 0x08, 0x00], // }
+    [0x08, 0x04, 0x08, 0x10, 0x08], // ~
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DrawStyle {
+    /// Line width in pixels.
+    pub thickness: u32,
+    /// Integer scale of the 5x7 font.
+    pub font_scale: u32,
+    /// Append the score to labels.
+    pub show_score: bool,
+    /// Opacity of mask overlays.
+    pub mask_alpha: f32,
+    pub keypoint_radius: u32,
+    /// Keypoints and limbs scoring below this are not drawn.
+    pub keypoint_thresh: f32,
+}
+
+impl Default for DrawStyle {
+    fn default() -> Self {
+        DrawStyle {
+            thickness: 2,
+            font_scale: 2,
+            show_score: true,
+            mask_alpha: 0.5,
+            keypoint_radius: 3,
+            keypoint_thresh: 0.5,
+        }
+    }
+}
+
+/// Color of a class or track id.
+pub fn color(id: usize) -> Rgb<u8> {
+    Rgb(PALETTE[id % PALETTE.len()])
+}
+
+fn put(image: &mut RgbImage, x: i32, y: i32, color: Rgb<u8>) {
+    if x >= 0 && y >= 0 && (x as u32) < image.width() && (y as u32) < image.height() {
+        image.put_pixel(x as u32, y as u32, color);
+    }
+}
+
+/// Blend `color` over a pixel with an opacity in 1/256 steps.
+fn blend(image: &mut RgbImage, x: i32, y: i32, color: Rgb<u8>, alpha: u32) {
+    if x < 0 || y < 0 || x as u32 >= image.width() || y as u32 >= image.height() {
+        return;
+    }
+    let pixel = image.get_pixel_mut(x as u32, y as u32);
+    for (p, &c) in pixel.0.iter_mut().zip(color.0.iter()) {
+        *p = ((*p as u32 * (256 - alpha) + c as u32 * alpha + 128) >> 8) as u8;
+    }
+}
+
+fn alpha_steps(alpha: f32) -> u32 {
+    (alpha.clamp(0.0, 1.0) * 256.0).round() as u32
+}
+
+/// Fill the pixels `x..x + width`, `y..y + height`, clipped to the image.
+pub fn fill_rect(image: &mut RgbImage, x: i32, y: i32, width: u32, height: u32, color: Rgb<u8>) {
+    let x0 = x.max(0);
+    let y0 = y.max(0);
+    let x1 = (x + width as i32).min(image.width() as i32);
+    let y1 = (y + height as i32).min(image.height() as i32);
+    for py in y0..y1 {
+        for px in x0..x1 {
+            image.put_pixel(px as u32, py as u32, color);
+        }
+    }
+}
+
+/// Outline a box, the lines growing inwards.
+pub fn draw_rect(image: &mut RgbImage, bbox: &BBox, color: Rgb<u8>, thickness: u32) {
+    let (x1, y1) = (bbox.x1.round() as i32, bbox.y1.round() as i32);
+    let (x2, y2) = (bbox.x2.round() as i32, bbox.y2.round() as i32);
+    let (w, h) = ((x2 - x1).max(1) as u32, (y2 - y1).max(1) as u32);
+    let t = thickness.max(1);
+
+    fill_rect(image, x1, y1, w, t.min(h), color);
+    fill_rect(image, x1, y2 - t as i32, w, t.min(h), color);
+    fill_rect(image, x1, y1, t.min(w), h, color);
+    fill_rect(image, x2 - t as i32, y1, t.min(w), h, color);
+}
+
+/// A Bresenham line drawn with a square pen of `thickness` pixels.
+pub fn draw_line(
+    image: &mut RgbImage,
+    from: (f32, f32),
+    to: (f32, f32),
+    color: Rgb<u8>,
+    thickness: u32,
+) {
+    let (mut x, mut y) = (from.0.round() as i32, from.1.round() as i32);
+    let (x1, y1) = (to.0.round() as i32, to.1.round() as i32);
+    let (dx, dy) = ((x1 - x).abs(), -(y1 - y).abs());
+    let (sx, sy) = ((x1 - x).signum(), (y1 - y).signum());
+    let t = thickness.max(1);
+    let offset = (t as i32 - 1) / 2;
+
+    let mut err = dx + dy;
+    loop {
+        fill_rect(image, x - offset, y - offset, t, t, color);
+        if x == x1 && y == y1 {
+            break;
+        }
+        let e2 = 2 * err;
+        if e2 >= dy {
+            err += dy;
+            x += sx;
+        }
+        if e2 <= dx {
+            err += dx;
+            y += sy;
+        }
+    }
+}
+
+pub fn fill_circle(image: &mut RgbImage, center: (f32, f32), radius: u32, color: Rgb<u8>) {
+    let (cx, cy) = (center.0.round() as i32, center.1.round() as i32);
+    let r = radius as i32;
+    for dy in -r..=r {
+        for dx in -r..=r {
+            if dx * dx + dy * dy <= r * r {
+                put(image, cx + dx, cy + dy, color);
+            }
+        }
+    }
+}
+
+/// `(width, height)` of a single line of text drawn at `scale`.
+pub fn text_size(text: &str, scale: u32) -> (u32, u32) {
+    let chars = text.chars().count() as u32;
+    let width = (chars * (GLYPH_WIDTH + 1)).saturating_sub(1);
+
+    (width * scale, GLYPH_HEIGHT * scale)
+}
+
+/// Draw a single line of text with its top left corner at `(x, y)`.
+/// Characters outside printable ASCII are drawn as `?`.
+pub fn draw_text(image: &mut RgbImage, x: i32, y: i32, text: &str, color: Rgb<u8>, scale: u32) {
+    let scale = scale.max(1);
+    for (i, ch) in text.chars().enumerate() {
+        let code = ch as u32;
+        let glyph = if (0x20..0x7f).contains(&code) {
+            &FONT[(code - 0x20) as usize]
+        } else {
+            &FONT[(b'?' - 0x20) as usize]
+        };
+        let gx = x + (i as u32 * (GLYPH_WIDTH + 1) * scale) as i32;
+        for (col, bits) in glyph.iter().enumerate() {
+            for row in 0..GLYPH_HEIGHT {
+                if bits >> row & 1 == 1 {
+                    let px = gx + (col as u32 * scale) as i32;
+                    let py = y + (row * scale) as i32;
+                    fill_rect(image, px, py, scale, scale, color);
+                }
+            }
+        }
+    }
+}
+
+/// Text on a filled background, above `(x, y)` when there is room and below
+/// it otherwise. The text is black or white, whichever reads better.
+pub fn draw_label(
+    image: &mut RgbImage,
+    x: i32,
+    y: i32,
+    text: &str,
+    background: Rgb<u8>,
+    scale: u32,
+) {
+    let scale = scale.max(1);
+    let (w, h) = text_size(text, scale);
+    let (w, h) = (w + 2 * scale, h + 2 * scale);
+    let top = if y >= h as i32 { y - h as i32 } else { y };
+
+    let [r, g, b] = background.0;
+    let luma = 299 * r as u32 + 587 * g as u32 + 114 * b as u32;
+    let foreground = if luma > 128_000 {
+        Rgb([0, 0, 0])
+    } else {
+        Rgb([255, 255, 255])
+    };
+
+    fill_rect(image, x, top, w, h, background);
+    draw_text(
+        image,
+        x + scale as i32,
+        top + scale as i32,
+        text,
+        foreground,
+        scale,
+    );
+}
+
+/// `"name score"`, the class id standing in for a missing name.
+fn label_text(class_id: usize, score: f32, labels: Option<&Labels>, style: &DrawStyle) -> String {
+    let name = labels
+        .and_then(|l| l.get(class_id))
+        .map_or_else(|| class_id.to_string(), str::to_string);
+    if style.show_score {
+        format!("{name} {score:.2}")
+    } else {
+        name
+    }
+}
+
+/// Boxes and labels colored by class. Detections must be in image
+/// coordinates.
+pub fn draw_detections(
+    image: &mut RgbImage,
+    detections: &[Detection],
+    labels: Option<&Labels>,
+    style: &DrawStyle,
+) {
+    for det in detections {
+        let c = color(det.class_id);
+        draw_rect(image, &det.bbox, c, style.thickness);
+        let text = label_text(det.class_id, det.score, labels, style);
+        let (x, y) = (det.bbox.x1.round() as i32, det.bbox.y1.round() as i32);
+        draw_label(image, x, y, &text, c, style.font_scale);
+    }
+}
+
+/// Blend a mask over the image.
+pub fn draw_mask(image: &mut RgbImage, mask: &InstanceMask, color: Rgb<u8>, alpha: f32) {
+    let alpha = alpha_steps(alpha);
+    for ((y, x), &inside) in mask.data.indexed_iter() {
+        if inside {
+            let px = mask.x as i32 + x as i32;
+            let py = mask.y as i32 + y as i32;
+            blend(image, px, py, color, alpha);
+        }
+    }
+}
+
+/// Masks, boxes and labels of instance segmentation results.
+pub fn draw_segmentations(
+    image: &mut RgbImage,
+    segmentations: &[Segmentation],
+    labels: Option<&Labels>,
+    style: &DrawStyle,
+) {
+    for seg in segmentations {
+        let c = color(seg.detection.class_id);
+        draw_mask(image, &seg.mask, c, style.mask_alpha);
+    }
+    let detections: Vec<Detection> = segmentations.iter().map(|s| s.detection).collect();
+    draw_detections(image, &detections, labels, style);
+}
+
+/// Blend a semantic label map of the image size with `palette`. Class 0 is
+/// taken as background and left untouched.
+pub fn draw_label_map(
+    image: &mut RgbImage,
+    label_map: ArrayView2<u8>,
+    palette: &[[u8; 3]],
+    alpha: f32,
+) {
+    let alpha = alpha_steps(alpha);
+    for ((y, x), &label) in label_map.indexed_iter() {
+        if let Some(&c) = palette.get(label as usize).filter(|_| label != 0) {
+            blend(image, x as i32, y as i32, Rgb(c), alpha);
+        }
+    }
+}
+
+/// Skeletons with keypoints colored by index, boxes and labels.
+pub fn draw_poses(
+    image: &mut RgbImage,
+    poses: &[Pose],
+    skeleton: &Skeleton,
+    labels: Option<&Labels>,
+    style: &DrawStyle,
+) {
+    for pose in poses {
+        let c = color(pose.detection.class_id);
+        for (a, b) in pose.limbs(skeleton, style.keypoint_thresh) {
+            draw_line(image, (a.x, a.y), (b.x, b.y), c, style.thickness);
+        }
+        for (i, k) in pose.keypoints.iter().enumerate() {
+            if k.score >= style.keypoint_thresh {
+                fill_circle(image, (k.x, k.y), style.keypoint_radius, color(i));
+            }
+        }
+    }
+    let detections: Vec<Detection> = poses.iter().map(|p| p.detection).collect();
+    draw_detections(image, &detections, labels, style);
+}
+
+/// Boxes labeled `"#id name"` and colored by track id, so an object keeps
+/// its color across frames.
+pub fn draw_tracks(
+    image: &mut RgbImage,
+    tracks: &[Track],
+    labels: Option<&Labels>,
+    style: &DrawStyle,
+) {
+    for track in tracks {
+        let c = color(track.id as usize);
+        draw_rect(image, &track.bbox, c, style.thickness);
+        let text = format!(
+            "#{} {}",
+            track.id,
+            label_text(track.class_id, track.score, labels, style)
+        );
+        let (x, y) = (track.bbox.x1.round() as i32, track.bbox.y1.round() as i32);
+        draw_label(image, x, y, &text, c, style.font_scale);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::postprocess::pose::Keypoint;
+    use crate::track::ByteTracker;
+
+    const GOLDEN: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/golden/draw.png");
+
+    fn detection(class_id: usize, score: f32, bbox: BBox) -> Detection {
+        Detection {
+            class_id,
+            score,
+            bbox,
+        }
+    }
+
+    #[test]
+    fn test_primitives() {
+        let mut image = RgbImage::new(16, 16);
+        let red = Rgb([255, 0, 0]);
+        draw_rect(&mut image, &BBox::new(2.0, 2.0, 10.0, 8.0), red, 1);
+        assert_eq!(*image.get_pixel(2, 2), red);
+        assert_eq!(*image.get_pixel(9, 7), red);
+        assert_eq!(*image.get_pixel(5, 5), Rgb([0, 0, 0]));
+
+        // clipped at the border
+        draw_line(&mut image, (-4.0, 15.0), (20.0, 15.0), red, 1);
+        assert!((0..16).all(|x| *image.get_pixel(x, 15) == red));
+
+        // "1" has its stem in the middle column
+        let mut image = RgbImage::new(8, 8);
+        draw_text(&mut image, 0, 0, "1", red, 1);
+        assert!((0..7).all(|y| *image.get_pixel(2, y) == red));
+        assert_eq!(*image.get_pixel(0, 0), Rgb([0, 0, 0]));
+        assert_eq!(text_size("ab", 2), (22, 14));
+
+        let mut image = RgbImage::from_pixel(1, 1, Rgb([0, 0, 200]));
+        blend(&mut image, 0, 0, Rgb([200, 0, 0]), alpha_steps(0.5));
+        assert_eq!(*image.get_pixel(0, 0), Rgb([100, 0, 100]));
+    }
+
+    /// Render every kind of result into one image and compare it with the
+    /// checked in golden image. Run with `RKNPU2_UPDATE_GOLDEN=1` to
+    /// regenerate it after an intended change.
+    #[test]
+    fn test_golden() {
+        let mut image = RgbImage::from_fn(128, 96, |x, y| Rgb([(x * 2) as u8, (y * 2) as u8, 64]));
+        let labels = Labels::parse("person\ncar\n");
+        let style = DrawStyle {
+            font_scale: 1,
+            ..Default::default()
+        };
+
+        let car = detection(1, 0.87, BBox::new(4.0, 12.0, 60.0, 40.0));
+        draw_detections(&mut image, &[car], Some(&labels), &style);
+
+        let mut mask = Array2::from_elem((16, 20), false);
+        mask.slice_mut(s![4..12, 2..18]).fill(true);
+        let seg = Segmentation {
+            detection: detection(2, 0.5, BBox::new(70.0, 10.0, 90.0, 26.0)),
+            mask: InstanceMask {
+                x: 70,
+                y: 10,
+                data: mask,
+            },
+        };
+        draw_segmentations(&mut image, &[seg], Some(&labels), &style);
+
+        let keypoints = [(100.0, 50.0), (96.0, 60.0), (104.0, 60.0), (100.0, 70.0)]
+            .into_iter()
+            .map(|(x, y)| Keypoint { x, y, score: 1.0 })
+            .collect();
+        let pose = Pose {
+            detection: detection(0, 0.9, BBox::new(90.0, 44.0, 110.0, 76.0)),
+            keypoints,
+        };
+        let skeleton = Skeleton {
+            keypoints: &["a", "b", "c", "d"],
+            edges: &[(0, 1), (0, 2), (1, 3), (2, 3)],
+        };
+        draw_poses(&mut image, &[pose], &skeleton, Some(&labels), &style);
+
+        let mut tracker = ByteTracker::default();
+        let person = detection(0, 0.9, BBox::new(10.0, 56.0, 40.0, 90.0));
+        tracker.update(&[person]);
+        let update = tracker.update(&[person]);
+        draw_tracks(&mut image, &update.tracks, Some(&labels), &style);
+
+        if std::env::var_os("RKNPU2_UPDATE_GOLDEN").is_some() {
+            image.save(GOLDEN).unwrap();
+        }
+        let golden = image::open(GOLDEN).unwrap().to_rgb8();
+        assert!(golden == image, "drawing differs from {GOLDEN}");
+    }
+}
@@ -0,0 +1,303 @@
+//! Top-k cosine similarity search over a gallery of embeddings, e.g. face or
+//! product features.
+//!
+//! The normalized gallery is the B matrix of an int8 [`QuantizedMatmul`],
+//! one column and one scale per embedding, and a batch of normalized queries
+//! is A, so every product is a cosine similarity. Large galleries are split
+//! into shards of at most `shard_size` embeddings, one matmul each.
+//! [`EmbeddingIndex::search_cpu`] computes the exact float scores without an
+//! NPU.
+
+use ndarray::prelude::*;
+
+use crate::matmul::layout::Chip;
+use crate::matmul::quant::{QuantGranularity, QuantizedB, QuantizedMatmul};
+use crate::matmul::Int8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EmbeddingConfig {
+    /// Queries per matmul run, smaller batches are zero padded.
+    pub batch: usize,
+    /// Most embeddings per matmul.
+    pub shard_size: usize,
+    /// Keep the gallery in the native layout of this chip.
+    pub chip: Option<Chip>,
+}
+
+impl Default for EmbeddingConfig {
+    fn default() -> Self {
+        EmbeddingConfig {
+            batch: 8,
+            shard_size: 4096,
+            chip: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SearchHit {
+    pub id: u64,
+    /// Cosine similarity, in `-1.0..=1.0`.
+    pub score: f32,
+}
+
+#[derive(Debug)]
+pub struct EmbeddingIndex {
+    pub config: EmbeddingConfig,
+    dim: usize,
+    ids: Vec<u64>,
+    /// `(len, dim)` normalized embeddings.
+    gallery: Array2<f32>,
+    /// Matmuls over the current gallery, rebuilt after a change.
+    shards: Option<Vec<QuantizedMatmul<Int8>>>,
+}
+
+/// Scale to unit length, zero vectors stay zero.
+fn normalize(mut v: ArrayViewMut1<f32>) {
+    let norm = v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        v.mapv_inplace(|x| x / norm);
+    }
+}
+
+/// The `k` best hits, best first, ties broken by id.
+fn top_k(hits: impl Iterator<Item = SearchHit>, k: usize) -> Vec<SearchHit> {
+    let order =
+        |a: &SearchHit, b: &SearchHit| b.score.total_cmp(&a.score).then_with(|| a.id.cmp(&b.id));
+    let mut hits: Vec<SearchHit> = hits.collect();
+    if k == 0 {
+        return Vec::new();
+    }
+    if hits.len() > k {
+        hits.select_nth_unstable_by(k - 1, order);
+        hits.truncate(k);
+    }
+    hits.sort_by(order);
+
+    hits
+}
+
+impl EmbeddingIndex {
+    pub fn new(dim: usize, config: EmbeddingConfig) -> Self {
+        EmbeddingIndex {
+            config,
+            dim,
+            ids: Vec::new(),
+            gallery: Array2::zeros((0, dim)),
+            shards: None,
+        }
+    }
+
+    pub fn dim(&self) -> usize {
+        self.dim
+    }
+
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    pub fn ids(&self) -> &[u64] {
+        &self.ids
+    }
+
+    /// Add `(len, dim)` embeddings under `ids`.
+    pub fn add(&mut self, ids: &[u64], embeddings: ArrayView2<f32>) -> Result<(), i32> {
+        if embeddings.dim() != (ids.len(), self.dim) {
+            return Err(rknpu2_sys::RKNN_ERR_PARAM_INVALID);
+        }
+        let mut embeddings = embeddings.to_owned();
+        for row in embeddings.rows_mut() {
+            normalize(row);
+        }
+        self.gallery
+            .append(Axis(0), embeddings.view())
+            .map_err(|_| rknpu2_sys::RKNN_ERR_PARAM_INVALID)?;
+        self.ids.extend_from_slice(ids);
+        self.shards = None;
+
+        Ok(())
+    }
+
+    /// Remove every embedding stored under `id`, returning how many there were.
+    pub fn remove(&mut self, id: u64) -> usize {
+        let keep: Vec<usize> = (0..self.ids.len()).filter(|&i| self.ids[i] != id).collect();
+        let removed = self.ids.len() - keep.len();
+        if removed > 0 {
+            self.gallery = self.gallery.select(Axis(0), &keep);
+            self.ids = keep.iter().map(|&i| self.ids[i]).collect();
+            self.shards = None;
+        }
+
+        removed
+    }
+
+    /// The quantized B matrix of the shard starting at embedding `start`.
+    fn shard_b(&self, start: usize) -> Result<QuantizedB<Int8>, i32> {
+        let end = (start + self.config.shard_size.max(1)).min(self.len());
+        let b = self.gallery.slice(s![start..end, ..]).reversed_axes();
+
+        QuantizedB::quantize(b, QuantGranularity::PerChannel)
+    }
+
+    /// Quantize the gallery and load it to the NPU. [`search`](Self::search)
+    /// does this on demand after the gallery changed.
+    pub fn build(&mut self) -> Result<(), i32> {
+        let mut shards = Vec::new();
+        for start in (0..self.len()).step_by(self.config.shard_size.max(1)) {
+            let b = self.shard_b(start)?;
+            shards.push(QuantizedMatmul::new(
+                self.config.batch.max(1),
+                b,
+                self.config.chip,
+            )?);
+        }
+        self.shards = Some(shards);
+
+        Ok(())
+    }
+
+    /// The `k` most similar embeddings for each of the `(q, dim)` queries,
+    /// scored on the NPU.
+    pub fn search(
+        &mut self,
+        queries: ArrayView2<f32>,
+        k: usize,
+    ) -> Result<Vec<Vec<SearchHit>>, i32> {
+        if queries.ncols() != self.dim {
+            return Err(rknpu2_sys::RKNN_ERR_PARAM_INVALID);
+        }
+        if self.shards.is_none() {
+            self.build()?;
+        }
+        let batch = self.config.batch.max(1);
+        let shards = self.shards.as_mut().unwrap();
+
+        let mut results = Vec::with_capacity(queries.nrows());
+        for chunk in queries.axis_chunks_iter(Axis(0), batch) {
+            let mut a = Array2::zeros((batch, self.dim));
+            a.slice_mut(s![..chunk.nrows(), ..]).assign(&chunk);
+            for row in a.rows_mut() {
+                normalize(row);
+            }
+
+            let mut scores = Array2::zeros((chunk.nrows(), 0));
+            for shard in shards.iter_mut() {
+                let c = shard.run(a.view())?;
+                scores
+                    .append(Axis(1), c.slice(s![..chunk.nrows(), ..]))
+                    .unwrap();
+            }
+            for row in scores.rows() {
+                let hits = row.iter().enumerate().map(|(i, &score)| SearchHit {
+                    id: self.ids[i],
+                    score,
+                });
+                results.push(top_k(hits, k));
+            }
+        }
+
+        Ok(results)
+    }
+
+    /// [`search`](Self::search) with exact float scores on the CPU.
+    pub fn search_cpu(
+        &self,
+        queries: ArrayView2<f32>,
+        k: usize,
+    ) -> Result<Vec<Vec<SearchHit>>, i32> {
+        if queries.ncols() != self.dim {
+            return Err(rknpu2_sys::RKNN_ERR_PARAM_INVALID);
+        }
+        let mut queries = queries.to_owned();
+        for row in queries.rows_mut() {
+            normalize(row);
+        }
+        let scores = queries.dot(&self.gallery.t());
+
+        Ok(scores
+            .rows()
+            .into_iter()
+            .map(|row| {
+                let hits = row.iter().enumerate().map(|(i, &score)| SearchHit {
+                    id: self.ids[i],
+                    score,
+                });
+                top_k(hits, k)
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gallery() -> (Vec<u64>, Array2<f32>) {
+        let ids: Vec<u64> = (100..140).collect();
+        let embeddings = Array2::from_shape_fn((40, 16), |(r, c)| {
+            ((r * 16 + c) as f32 * 0.37).sin() * (1.0 + r as f32 * 0.1)
+        });
+        (ids, embeddings)
+    }
+
+    #[test]
+    fn test_search_cpu() {
+        let (ids, embeddings) = gallery();
+        let mut index = EmbeddingIndex::new(16, EmbeddingConfig::default());
+        index.add(&ids, embeddings.view()).unwrap();
+        assert_eq!(index.len(), 40);
+        assert!(index.add(&[1], embeddings.view()).is_err());
+
+        // a scaled copy of an entry is a perfect match
+        let queries = embeddings.select(Axis(0), &[3, 25]) * 3.0;
+        let results = index.search_cpu(queries.view(), 5).unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0][0].id, 103);
+        assert!((results[0][0].score - 1.0).abs() < 1e-5);
+        assert_eq!(results[1][0].id, 125);
+        assert_eq!(results[1].len(), 5);
+        assert!(results[1].windows(2).all(|w| w[0].score >= w[1].score));
+
+        assert_eq!(index.remove(103), 1);
+        let results = index.search_cpu(queries.view(), 1).unwrap();
+        assert_ne!(results[0][0].id, 103);
+        assert!(index.search_cpu(Array2::zeros((1, 8)).view(), 1).is_err());
+    }
+
+    #[test]
+    fn test_quantized_scores() {
+        // before SDK v1.6.0 the NPU path computes the quantized reference of
+        // every shard
+        let (ids, embeddings) = gallery();
+        let config = EmbeddingConfig {
+            shard_size: 16,
+            ..Default::default()
+        };
+        let mut index = EmbeddingIndex::new(16, config);
+        index.add(&ids, embeddings.view()).unwrap();
+
+        let queries = embeddings.select(Axis(0), &[0, 20, 39]);
+        let mut normalized = queries.clone();
+        for row in normalized.rows_mut() {
+            normalize(row);
+        }
+        let mut scores = Array2::zeros((3, 0));
+        for start in (0..40).step_by(16) {
+            let b = index.shard_b(start).unwrap();
+            let c = b.matmul_reference(normalized.view());
+            scores.append(Axis(1), c.view()).unwrap();
+        }
+
+        let exact = index.search_cpu(queries.view(), 40).unwrap();
+        for (q, hits) in exact.iter().enumerate() {
+            for hit in hits {
+                let i = (hit.id - 100) as usize;
+                assert!((scores[[q, i]] - hit.score).abs() < 0.02);
+            }
+        }
+    }
+}
@@ -6,7 +6,18 @@ use std::ffi::c_void;
 use std::mem;
 use std::ptr;
 
-pub type RKNNContext = u64;
+#[cfg(all(feature = "custom-op", rknn_v1_6))]
+pub mod custom_op;
+#[cfg(feature = "draw")]
+pub mod draw;
+pub mod embedding;
+pub mod matmul;
+pub mod postprocess;
+pub mod preprocess;
+pub mod quant;
+pub mod track;
+
+pub type RKNNContext = rknpu2_sys::rknn_context;
 
 #[derive(Debug)]
 pub struct RKNNContextPack {
@@ -96,6 +107,93 @@ pub fn get_input_output_number(ctx: RKNNContext) -> Result<RKNNInputOutputNumber
     }
 }
 
+/// The custom string embedded in the model at conversion time.
+pub fn get_custom_string(ctx: RKNNContext) -> Result<String, i32> {
+    let cmd = rknpu2_sys::_rknn_query_cmd_RKNN_QUERY_CUSTOM_STRING;
+    let mut custom: rknpu2_sys::rknn_custom_string = unsafe { mem::zeroed() };
+    let size = mem::size_of::<rknpu2_sys::rknn_custom_string>() as u32;
+    let custom_ptr = &mut custom as *mut _ as *mut c_void;
+
+    unsafe {
+        let ret = rknpu2_sys::rknn_query(ctx, cmd, custom_ptr, size);
+        if ret != 0 {
+            return Err(ret);
+        }
+    }
+
+    Ok(c_chars_to_string(&custom.string))
+}
+
+/// The runtime NUL terminates strings within their buffer.
+fn c_chars_to_string(chars: &[std::ffi::c_char]) -> String {
+    let bytes: Vec<u8> = chars
+        .iter()
+        .take_while(|&&c| c != 0)
+        .map(|&c| c as u8)
+        .collect();
+
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// SDK version of the headers and runtime this crate was built against,
+/// e.g. `1.5.2`. Selected in `rknpu2-sys`.
+pub const SDK_VERSION: &str = env!("RKNPU2_SDK_VERSION");
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RKNNSdkVersion {
+    /// Version of the loaded `librknnrt.so`, e.g. `1.5.2 (c6b7b351a@2023-08-23T15:28:22)`.
+    pub api_version: String,
+    /// Version of the NPU driver.
+    pub drv_version: String,
+}
+
+pub fn get_sdk_version(ctx: RKNNContext) -> Result<RKNNSdkVersion, i32> {
+    let cmd = rknpu2_sys::_rknn_query_cmd_RKNN_QUERY_SDK_VERSION;
+    let mut version: rknpu2_sys::rknn_sdk_version = unsafe { mem::zeroed() };
+    let size = mem::size_of::<rknpu2_sys::rknn_sdk_version>() as u32;
+    let version_ptr = &mut version as *mut _ as *mut c_void;
+
+    unsafe {
+        let ret = rknpu2_sys::rknn_query(ctx, cmd, version_ptr, size);
+        if ret != 0 {
+            return Err(ret);
+        }
+    }
+
+    Ok(RKNNSdkVersion {
+        api_version: c_chars_to_string(&version.api_version),
+        drv_version: c_chars_to_string(&version.drv_version),
+    })
+}
+
+/// Whether two versions are the same release, ignoring the patch version
+/// and anything after the number.
+fn same_release(a: &str, b: &str) -> bool {
+    let release = |v: &str| -> Vec<String> {
+        v.trim_start_matches('v')
+            .split(|c: char| !c.is_ascii_digit() && c != '.')
+            .next()
+            .unwrap_or("")
+            .split('.')
+            .take(2)
+            .map(str::to_string)
+            .collect()
+    };
+    release(a) == release(b)
+}
+
+/// Fail with `RKNN_ERR_DEVICE_UNMATCH` when the runtime loaded on the
+/// device is another release than [`SDK_VERSION`], whose structs and
+/// functions may differ from the ones this crate was compiled for.
+pub fn check_sdk_version(ctx: RKNNContext) -> Result<(), i32> {
+    let version = get_sdk_version(ctx)?;
+    if same_release(&version.api_version, SDK_VERSION) {
+        Ok(())
+    } else {
+        Err(rknpu2_sys::RKNN_ERR_DEVICE_UNMATCH)
+    }
+}
+
 pub type RKNNTensorAttr = rknpu2_sys::rknn_tensor_attr;
 pub fn get_model_input_info(ctx: RKNNContext, input_num: u32) -> Result<Vec<RKNNTensorAttr>, i32> {
     let mut input_attrs: Vec<RKNNTensorAttr> = Vec::with_capacity(input_num as usize);
@@ -165,6 +263,98 @@ pub fn get_model_output_info(
     Ok(output_attrs)
 }
 
+/// Query `num` tensor attributes with `cmd`, one per index.
+fn query_tensor_attrs(
+    ctx: RKNNContext,
+    cmd: rknpu2_sys::_rknn_query_cmd,
+    num: u32,
+) -> Result<Vec<RKNNTensorAttr>, i32> {
+    let size = mem::size_of::<RKNNTensorAttr>() as u32;
+    (0..num)
+        .map(|i| {
+            let mut attr: RKNNTensorAttr = unsafe { mem::zeroed() };
+            attr.index = i;
+            let ret = unsafe {
+                rknpu2_sys::rknn_query(ctx, cmd, &mut attr as *mut _ as *mut c_void, size)
+            };
+            if ret == 0 {
+                Ok(attr)
+            } else {
+                Err(ret)
+            }
+        })
+        .collect()
+}
+
+/// Input attributes of a dynamic shape model for the shapes last set with
+/// [`rknn_set_input_shapes`].
+pub fn get_current_input_info(
+    ctx: RKNNContext,
+    input_num: u32,
+) -> Result<Vec<RKNNTensorAttr>, i32> {
+    let cmd = rknpu2_sys::_rknn_query_cmd_RKNN_QUERY_CURRENT_INPUT_ATTR;
+    query_tensor_attrs(ctx, cmd, input_num)
+}
+
+/// Output attributes of a dynamic shape model for the shapes last set with
+/// [`rknn_set_input_shapes`].
+pub fn get_current_output_info(
+    ctx: RKNNContext,
+    output_num: u32,
+) -> Result<Vec<RKNNTensorAttr>, i32> {
+    let cmd = rknpu2_sys::_rknn_query_cmd_RKNN_QUERY_CURRENT_OUTPUT_ATTR;
+    query_tensor_attrs(ctx, cmd, output_num)
+}
+
+pub type RKNNInputRange = rknpu2_sys::rknn_input_range;
+/// The input shapes a dynamic shape model was converted with, one
+/// [`RKNNInputRange`] per input. Read them with [`input_range_shapes`].
+pub fn get_input_dynamic_range(
+    ctx: RKNNContext,
+    input_num: u32,
+) -> Result<Vec<RKNNInputRange>, i32> {
+    let cmd = rknpu2_sys::_rknn_query_cmd_RKNN_QUERY_INPUT_DYNAMIC_RANGE;
+    let size = mem::size_of::<RKNNInputRange>() as u32;
+    (0..input_num)
+        .map(|i| {
+            let mut range: RKNNInputRange = unsafe { mem::zeroed() };
+            range.index = i;
+            let ret = unsafe {
+                rknpu2_sys::rknn_query(ctx, cmd, &mut range as *mut _ as *mut c_void, size)
+            };
+            if ret == 0 {
+                Ok(range)
+            } else {
+                Err(ret)
+            }
+        })
+        .collect()
+}
+
+/// The shapes listed in `range`, each `n_dims` long.
+pub fn input_range_shapes(range: &RKNNInputRange) -> Vec<&[u32]> {
+    let n_dims = (range.n_dims as usize).min(rknpu2_sys::RKNN_MAX_DIMS as usize);
+    let count = (range.shape_number as usize).min(range.dyn_range.len());
+    range.dyn_range[..count]
+        .iter()
+        .map(|shape| &shape[..n_dims])
+        .collect()
+}
+
+/// Select the input shapes of a dynamic shape model before setting inputs,
+/// one attribute per input with its `dims` and `n_dims` set to one of the
+/// shapes from [`get_input_dynamic_range`].
+pub fn rknn_set_input_shapes(ctx: RKNNContext, attrs: &mut [RKNNTensorAttr]) -> Result<i32, i32> {
+    unsafe {
+        let ret = rknpu2_sys::rknn_set_input_shapes(ctx, attrs.len() as u32, attrs.as_mut_ptr());
+        if ret == 0 {
+            Ok(ret)
+        } else {
+            Err(ret)
+        }
+    }
+}
+
 pub type RKNNInput = rknpu2_sys::rknn_input;
 pub type RKNNOutput = rknpu2_sys::rknn_output;
 pub fn make_rknn_image_input(mut image_array_view: ArrayViewMut<u8, IxDyn>) -> Vec<RKNNInput> {
@@ -228,6 +418,19 @@ pub fn rknn_outputs_get(ctx: RKNNContext, output_num: u32) -> Result<Vec<RKNNOut
     }
 }
 
+/// Release the output buffers the runtime allocated in [`rknn_outputs_get`].
+pub fn rknn_outputs_release(ctx: RKNNContext, mut outputs: Vec<RKNNOutput>) -> Result<i32, i32> {
+    let output_num = outputs.len() as u32;
+    unsafe {
+        let ret = rknpu2_sys::rknn_outputs_release(ctx, output_num, outputs.as_mut_ptr());
+        if ret == 0 {
+            Ok(ret)
+        } else {
+            Err(ret)
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     #![allow(unused_mut)]
@@ -300,6 +503,32 @@ mod tests {
         dbg!(model_output_info.unwrap());
     }
 
+    #[test]
+    fn test_same_release() {
+        assert!(same_release(
+            "1.5.2 (c6b7b351a@2023-08-23T15:28:22)",
+            "1.5.2"
+        ));
+        assert!(same_release("1.5.0", "v1.5.2"));
+        assert!(!same_release(
+            "2.3.0 (c949ad889d@2024-11-07T11:35:33)",
+            "1.6.0"
+        ));
+        assert!(!same_release("1.6.0", "1.5.2"));
+    }
+
+    #[test]
+    fn test_input_range_shapes() {
+        let mut range: RKNNInputRange = unsafe { mem::zeroed() };
+        range.shape_number = 2;
+        range.n_dims = 4;
+        range.dyn_range[0][..4].copy_from_slice(&[1, 3, 224, 224]);
+        range.dyn_range[1][..4].copy_from_slice(&[1, 3, 320, 320]);
+
+        let shapes = input_range_shapes(&range);
+        assert_eq!(shapes, [&[1, 3, 224, 224], &[1, 3, 320, 320]]);
+    }
+
     fn read_image(img_path: String) -> image::ImageBuffer<image::Rgb<u8>, Vec<u8>> {
         let img = ImageReader::open(img_path).unwrap().decode().unwrap();
         let img: image::ImageBuffer<image::Rgb<u8>, Vec<u8>> = img.to_rgb8();
@@ -308,11 +537,12 @@ mod tests {
 
     fn image_to_array_view(
         img_buffer: &mut ImageBuffer<image::Rgb<u8>, Vec<u8>>,
-    ) -> ArrayViewMut<u8, IxDyn> {
+    ) -> ArrayViewMut<'_, u8, IxDyn> {
+        // image rows are height major
         let (w, h) = (img_buffer.width(), img_buffer.height());
         unsafe {
             let arr =
-                ArrayViewMut::from_shape_ptr((w as usize, h as usize, 3), img_buffer.as_mut_ptr());
+                ArrayViewMut::from_shape_ptr((h as usize, w as usize, 3), img_buffer.as_mut_ptr());
 
             arr.into_dyn()
         }
@@ -0,0 +1,136 @@
+//! The native layout of the matmul B matrix.
+//!
+//! The NPU reads B as tiles of `tile_n` columns by `tile_k` rows, each tile
+//! stored column after column, the tiles ordered by N then K:
+//! `[N / tile_n][K / tile_k][tile_n][tile_k]`. The tile size depends on the
+//! chip and data type. K and N are zero padded to whole tiles.
+//!
+//! Packing does not touch the NPU, so weights can be packed on any host and
+//! loaded with [`MatmulContext::set_b_packed`](super::MatmulContext::set_b_packed).
+
+use ndarray::prelude::*;
+
+use super::MatmulType;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Chip {
+    RK3588,
+    RK356X,
+}
+
+/// `(tile_n, tile_k)` of the native B layout, `None` if the chip has no
+/// matmul of that type.
+pub fn b_tile(chip: Chip, ty: rknpu2_sys::rknn_matmul_type) -> Option<(usize, usize)> {
+    match (chip, ty) {
+        (Chip::RK3588, rknpu2_sys::_rknn_matmul_type_RKNN_FLOAT16_MM_FLOAT16_TO_FLOAT32) => {
+            Some((16, 32))
+        }
+        (Chip::RK3588, rknpu2_sys::_rknn_matmul_type_RKNN_INT8_MM_INT8_TO_INT32) => Some((32, 32)),
+        (Chip::RK3588, rknpu2_sys::_rknn_matmul_type_RKNN_INT4_MM_INT4_TO_INT16) => Some((64, 32)),
+        (Chip::RK356X, rknpu2_sys::_rknn_matmul_type_RKNN_FLOAT16_MM_FLOAT16_TO_FLOAT32) => {
+            Some((8, 16))
+        }
+        (Chip::RK356X, rknpu2_sys::_rknn_matmul_type_RKNN_INT8_MM_INT8_TO_INT32) => Some((16, 32)),
+        _ => None,
+    }
+}
+
+fn tiles<T: MatmulType>(k: usize, chip: Chip) -> Result<(usize, usize, usize), i32> {
+    let (tile_n, tile_k) = b_tile(chip, T::RAW).ok_or(rknpu2_sys::RKNN_ERR_PARAM_INVALID)?;
+    Ok((tile_n, tile_k, k.div_ceil(tile_k)))
+}
+
+/// Element offset of `B[k][n]` in the native layout.
+fn native_index(k: usize, n: usize, k_tiles: usize, tile_n: usize, tile_k: usize) -> usize {
+    ((n / tile_n) * k_tiles + k / tile_k) * tile_n * tile_k + (n % tile_n) * tile_k + k % tile_k
+}
+
+/// Number of elements of a `(k, n)` B matrix in the native layout.
+pub fn packed_len<T: MatmulType>(k: usize, n: usize, chip: Chip) -> Result<usize, i32> {
+    let (tile_n, tile_k, k_tiles) = tiles::<T>(k, chip)?;
+    Ok(n.div_ceil(tile_n) * tile_n * k_tiles * tile_k)
+}
+
+/// Pack a `(k, n)` B matrix into the native layout of `chip`, encoded as
+/// the tensor buffer expects it.
+pub fn pack_b<T: MatmulType>(b: ArrayView2<T::Input>, chip: Chip) -> Result<Vec<u8>, i32> {
+    let (k, n) = b.dim();
+    let (tile_n, tile_k, k_tiles) = tiles::<T>(k, chip)?;
+
+    let mut native = vec![T::Input::default(); packed_len::<T>(k, n, chip)?];
+    for ((row, col), &v) in b.indexed_iter() {
+        native[native_index(row, col, k_tiles, tile_n, tile_k)] = v;
+    }
+    let mut packed = vec![0u8; T::input_size(native.len())];
+    T::encode(&native, &mut packed);
+
+    Ok(packed)
+}
+
+/// Unpack a `(k, n)` B matrix packed by [`pack_b`].
+pub fn unpack_b<T: MatmulType>(
+    packed: &[u8],
+    k: usize,
+    n: usize,
+    chip: Chip,
+) -> Result<Array2<T::Input>, i32> {
+    let (tile_n, tile_k, k_tiles) = tiles::<T>(k, chip)?;
+    let len = packed_len::<T>(k, n, chip)?;
+    if packed.len() < T::input_size(len) {
+        return Err(rknpu2_sys::RKNN_ERR_PARAM_INVALID);
+    }
+    let native = T::decode_input(packed, len);
+
+    Ok(Array2::from_shape_fn((k, n), |(row, col)| {
+        native[native_index(row, col, k_tiles, tile_n, tile_k)]
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matmul::{Float16, Int4, Int8};
+
+    fn round_trip<T: MatmulType>(chip: Chip, value: impl Fn(usize, usize) -> T::Input)
+    where
+        T::Input: PartialEq + std::fmt::Debug,
+    {
+        // neither K nor N a whole number of tiles
+        let (k, n) = (40, 70);
+        let b = Array2::from_shape_fn((k, n), |(r, c)| value(r, c));
+        let packed = pack_b::<T>(b.view(), chip).unwrap();
+        assert_eq!(
+            packed.len(),
+            T::input_size(packed_len::<T>(k, n, chip).unwrap())
+        );
+        assert_eq!(unpack_b::<T>(&packed, k, n, chip).unwrap(), b);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let int8 = |r: usize, c: usize| ((r * 7 + c) % 255) as u8 as i8;
+        let int4 = |r: usize, c: usize| ((r * 3 + c) % 16) as i8 - 8;
+        let half = |r: usize, c: usize| (r as f32 - c as f32) * 0.5;
+
+        round_trip::<Int8>(Chip::RK3588, int8);
+        round_trip::<Int4>(Chip::RK3588, int4);
+        round_trip::<Float16>(Chip::RK3588, half);
+        round_trip::<Int8>(Chip::RK356X, int8);
+        round_trip::<Float16>(Chip::RK356X, half);
+
+        let b = Array2::<i8>::zeros((32, 32));
+        assert!(pack_b::<Int4>(b.view(), Chip::RK356X).is_err());
+    }
+
+    #[test]
+    fn test_native_index() {
+        // RK3588 int8: 32x32 tiles, one column of B after the other
+        let (k, n) = (64, 64);
+        let b = Array2::from_shape_fn((k, n), |(r, c)| (r == 33 && c == 2) as i8);
+        let packed = pack_b::<Int8>(b.view(), Chip::RK3588).unwrap();
+        // second K tile of the first N tile, column 2, row 1
+        let offset = 1024 + 2 * 32 + 1;
+        assert_eq!(packed[offset], 1);
+        assert_eq!(packed.iter().filter(|&&v| v != 0).count(), 1);
+    }
+}
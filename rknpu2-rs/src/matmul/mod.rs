@@ -0,0 +1,533 @@
+//! Safe wrapper around the RKNN matmul API, computing `C = A * B` on the NPU
+//! for an `(M, K)` matrix A and a `(K, N)` matrix B.
+//!
+//! The element types are picked by a [`MatmulType`]: [`Float16`] takes f32
+//! matrices, converted to fp16, and returns f32; [`Int8`] returns i32 and
+//! [`Int4`] returns i16. Matrices are passed as ndarrays in row major order,
+//! B can also be kept in the chip native layout of [`layout`]. [`quant`]
+//! quantizes float weights for the integer types. With SDK v1.6.0 or later a
+//! context can also switch between several shapes, see
+//! [`MatmulContext::new_dynamic`].
+
+pub mod layout;
+pub mod quant;
+
+use ndarray::prelude::*;
+use std::marker::PhantomData;
+use std::mem;
+use std::ptr;
+use std::slice;
+
+use self::layout::Chip;
+use crate::quant::{f16_to_f32, f32_slice_to_f16};
+
+pub type RKNNMatmulContext = rknpu2_sys::rknn_matmul_ctx;
+pub type RKNNMatmulInfo = rknpu2_sys::rknn_matmul_info;
+pub type RKNNMatmulTensorAttr = rknpu2_sys::rknn_matmul_tensor_attr;
+pub type RKNNMatmulIOAttr = rknpu2_sys::rknn_matmul_io_attr;
+pub type RKNNCoreMask = rknpu2_sys::rknn_core_mask;
+#[cfg(rknn_v1_6)]
+pub type RKNNMatmulShape = rknpu2_sys::rknn_matmul_shape;
+
+/// The data types of a matmul.
+pub trait MatmulType {
+    /// Element type of A and B.
+    type Input: Copy + Default;
+    /// Element type of C.
+    type Output: Copy;
+    const RAW: rknpu2_sys::rknn_matmul_type;
+    /// Bytes taken by `len` input elements.
+    fn input_size(len: usize) -> usize;
+    /// Write row major input elements into a tensor buffer.
+    fn encode(src: &[Self::Input], dst: &mut [u8]);
+    /// Read `len` input elements back from a tensor buffer.
+    fn decode_input(src: &[u8], len: usize) -> Vec<Self::Input>;
+    /// Read `len` output elements from a tensor buffer.
+    fn decode(src: &[u8], len: usize) -> Vec<Self::Output>;
+}
+
+/// fp16 A and B, f32 C.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Float16;
+
+/// int8 A and B, int32 C.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Int8;
+
+/// int4 A and B, int16 C. Inputs are given as i8 in `-8..=7` and packed two
+/// per byte, the even element in the low nibble.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Int4;
+
+impl MatmulType for Float16 {
+    type Input = f32;
+    type Output = f32;
+    const RAW: rknpu2_sys::rknn_matmul_type =
+        rknpu2_sys::_rknn_matmul_type_RKNN_FLOAT16_MM_FLOAT16_TO_FLOAT32;
+
+    fn input_size(len: usize) -> usize {
+        len * 2
+    }
+
+    fn encode(src: &[f32], dst: &mut [u8]) {
+        let mut half = vec![0u16; src.len()];
+        f32_slice_to_f16(src, &mut half);
+        for (h, d) in half.iter().zip(dst.chunks_exact_mut(2)) {
+            d.copy_from_slice(&h.to_ne_bytes());
+        }
+    }
+
+    fn decode_input(src: &[u8], len: usize) -> Vec<f32> {
+        src.chunks_exact(2)
+            .take(len)
+            .map(|b| f16_to_f32(u16::from_ne_bytes([b[0], b[1]])))
+            .collect()
+    }
+
+    fn decode(src: &[u8], len: usize) -> Vec<f32> {
+        src.chunks_exact(4)
+            .take(len)
+            .map(|b| f32::from_ne_bytes([b[0], b[1], b[2], b[3]]))
+            .collect()
+    }
+}
+
+impl MatmulType for Int8 {
+    type Input = i8;
+    type Output = i32;
+    const RAW: rknpu2_sys::rknn_matmul_type =
+        rknpu2_sys::_rknn_matmul_type_RKNN_INT8_MM_INT8_TO_INT32;
+
+    fn input_size(len: usize) -> usize {
+        len
+    }
+
+    fn encode(src: &[i8], dst: &mut [u8]) {
+        for (s, d) in src.iter().zip(dst.iter_mut()) {
+            *d = *s as u8;
+        }
+    }
+
+    fn decode_input(src: &[u8], len: usize) -> Vec<i8> {
+        src.iter().take(len).map(|&b| b as i8).collect()
+    }
+
+    fn decode(src: &[u8], len: usize) -> Vec<i32> {
+        src.chunks_exact(4)
+            .take(len)
+            .map(|b| i32::from_ne_bytes([b[0], b[1], b[2], b[3]]))
+            .collect()
+    }
+}
+
+impl MatmulType for Int4 {
+    type Input = i8;
+    type Output = i16;
+    const RAW: rknpu2_sys::rknn_matmul_type =
+        rknpu2_sys::_rknn_matmul_type_RKNN_INT4_MM_INT4_TO_INT16;
+
+    fn input_size(len: usize) -> usize {
+        len.div_ceil(2)
+    }
+
+    fn encode(src: &[i8], dst: &mut [u8]) {
+        for (pair, d) in src.chunks(2).zip(dst.iter_mut()) {
+            let low = pair[0] as u8 & 0x0f;
+            let high = pair.get(1).map_or(0, |&v| v as u8 & 0x0f);
+            *d = high << 4 | low;
+        }
+    }
+
+    fn decode_input(src: &[u8], len: usize) -> Vec<i8> {
+        (0..len.min(src.len() * 2))
+            .map(|i| {
+                let nibble = (src[i / 2] >> (4 * (i % 2))) & 0x0f;
+                // sign extend the nibble
+                ((nibble << 4) as i8) >> 4
+            })
+            .collect()
+    }
+
+    fn decode(src: &[u8], len: usize) -> Vec<i16> {
+        src.chunks_exact(2)
+            .take(len)
+            .map(|b| i16::from_ne_bytes([b[0], b[1]]))
+            .collect()
+    }
+}
+
+/// A matmul context with runtime allocated A, B and C buffers, all released
+/// on drop.
+#[derive(Debug)]
+pub struct MatmulContext<T: MatmulType> {
+    ctx: RKNNMatmulContext,
+    info: RKNNMatmulInfo,
+    io_attr: RKNNMatmulIOAttr,
+    a: *mut rknpu2_sys::rknn_tensor_mem,
+    b: *mut rknpu2_sys::rknn_tensor_mem,
+    c: *mut rknpu2_sys::rknn_tensor_mem,
+    /// Chip whose native layout B is kept in.
+    native_b: Option<Chip>,
+    /// Shapes of a dynamic context, with their tensor attributes.
+    #[cfg(rknn_v1_6)]
+    dynamic: Vec<(RKNNMatmulShape, RKNNMatmulIOAttr)>,
+    kind: PhantomData<T>,
+}
+
+impl<T: MatmulType> MatmulContext<T> {
+    /// Create a context for an `(m, k)` by `(k, n)` product with A, B and C in
+    /// the normal row major layout.
+    pub fn new(m: usize, k: usize, n: usize) -> Result<Self, i32> {
+        Self::create(m, k, n, None)
+    }
+
+    /// Create a context taking B in the native layout of `chip`, which saves
+    /// the runtime a reordering of B on every run. [`set_b`](Self::set_b)
+    /// packs B on the fly, [`set_b_packed`](Self::set_b_packed) takes B
+    /// packed ahead of time with [`layout::pack_b`].
+    pub fn new_native(m: usize, k: usize, n: usize, chip: Chip) -> Result<Self, i32> {
+        layout::b_tile(chip, T::RAW).ok_or(rknpu2_sys::RKNN_ERR_PARAM_INVALID)?;
+        Self::create(m, k, n, Some(chip))
+    }
+
+    fn create(m: usize, k: usize, n: usize, native_b: Option<Chip>) -> Result<Self, i32> {
+        Self::create_with(m, k, n, native_b, |_| {})
+    }
+
+    /// [`create`](Self::create) with extra fields of the info set by
+    /// `configure`.
+    pub(crate) fn create_with(
+        m: usize,
+        k: usize,
+        n: usize,
+        native_b: Option<Chip>,
+        configure: impl FnOnce(&mut RKNNMatmulInfo),
+    ) -> Result<Self, i32> {
+        if m == 0 || k == 0 || n == 0 {
+            return Err(rknpu2_sys::RKNN_ERR_PARAM_INVALID);
+        }
+        let mut info: RKNNMatmulInfo = unsafe { mem::zeroed() };
+        info.M = m as i32;
+        info.K = k as i32;
+        info.N = n as i32;
+        info.type_ = T::RAW;
+        #[cfg(not(rknn_v1_6))]
+        {
+            info.native_layout = native_b.is_some() as i32;
+        }
+        #[cfg(rknn_v1_6)]
+        {
+            info.B_layout = native_b.is_some() as i16;
+        }
+        configure(&mut info);
+
+        let mut ctx: RKNNMatmulContext = 0;
+        let mut io_attr: RKNNMatmulIOAttr = unsafe { mem::zeroed() };
+        let ret = unsafe { rknpu2_sys::rknn_matmul_create(&mut ctx, &mut info, &mut io_attr) };
+        if ret != 0 {
+            return Err(ret);
+        }
+
+        // from here on drop cleans up whatever got allocated
+        let mut matmul = MatmulContext {
+            ctx,
+            info,
+            io_attr,
+            a: ptr::null_mut(),
+            b: ptr::null_mut(),
+            c: ptr::null_mut(),
+            native_b,
+            #[cfg(rknn_v1_6)]
+            dynamic: Vec::new(),
+            kind: PhantomData,
+        };
+        matmul.a = matmul.create_mem(io_attr.A.size)?;
+        matmul.b = matmul.create_mem(io_attr.B.size)?;
+        matmul.c = matmul.create_mem(io_attr.C.size)?;
+        matmul.set_io_mems()?;
+
+        Ok(matmul)
+    }
+
+    /// Create a context for several `(m, k, n)` shapes, switched between with
+    /// [`set_shape`](Self::set_shape), starting out with the first one. The
+    /// buffers are sized for the largest shape.
+    #[cfg(rknn_v1_6)]
+    pub fn new_dynamic(shapes: &[(usize, usize, usize)]) -> Result<Self, i32> {
+        if shapes.is_empty() || shapes.iter().any(|&(m, k, n)| m == 0 || k == 0 || n == 0) {
+            return Err(rknpu2_sys::RKNN_ERR_PARAM_INVALID);
+        }
+        let mut raw: Vec<RKNNMatmulShape> = shapes
+            .iter()
+            .map(|&(m, k, n)| RKNNMatmulShape {
+                M: m as i32,
+                K: k as i32,
+                N: n as i32,
+            })
+            .collect();
+        let mut info: RKNNMatmulInfo = unsafe { mem::zeroed() };
+        info.type_ = T::RAW;
+
+        let mut ctx: RKNNMatmulContext = 0;
+        let mut io_attrs: Vec<RKNNMatmulIOAttr> = vec![unsafe { mem::zeroed() }; raw.len()];
+        let ret = unsafe {
+            rknpu2_sys::rknn_matmul_create_dyn_shape(
+                &mut ctx,
+                &mut info,
+                raw.len() as i32,
+                raw.as_mut_ptr(),
+                io_attrs.as_mut_ptr(),
+            )
+        };
+        if ret != 0 {
+            return Err(ret);
+        }
+
+        let mut matmul = MatmulContext {
+            ctx,
+            info,
+            io_attr: io_attrs[0],
+            a: ptr::null_mut(),
+            b: ptr::null_mut(),
+            c: ptr::null_mut(),
+            native_b: None,
+            dynamic: raw.into_iter().zip(io_attrs).collect(),
+            kind: PhantomData,
+        };
+        let largest = |size: fn(&RKNNMatmulIOAttr) -> u32| {
+            matmul
+                .dynamic
+                .iter()
+                .map(|(_, attr)| size(attr))
+                .max()
+                .unwrap()
+        };
+        let (a, b, c) = (
+            largest(|attr| attr.A.size),
+            largest(|attr| attr.B.size),
+            largest(|attr| attr.C.size),
+        );
+        matmul.a = matmul.create_mem(a)?;
+        matmul.b = matmul.create_mem(b)?;
+        matmul.c = matmul.create_mem(c)?;
+
+        let (m, k, n) = shapes[0];
+        matmul.set_shape(m, k, n)?;
+
+        Ok(matmul)
+    }
+
+    /// Switch a dynamic context to one of the shapes it was created for.
+    #[cfg(rknn_v1_6)]
+    pub fn set_shape(&mut self, m: usize, k: usize, n: usize) -> Result<(), i32> {
+        let (mut shape, io_attr) = *self
+            .dynamic
+            .iter()
+            .find(|(s, _)| (s.M as usize, s.K as usize, s.N as usize) == (m, k, n))
+            .ok_or(rknpu2_sys::RKNN_ERR_PARAM_INVALID)?;
+        let ret = unsafe { rknpu2_sys::rknn_matmul_set_dynamic_shape(self.ctx, &mut shape) };
+        if ret != 0 {
+            return Err(ret);
+        }
+        self.info.M = shape.M;
+        self.info.K = shape.K;
+        self.info.N = shape.N;
+        self.io_attr = io_attr;
+
+        self.set_io_mems()
+    }
+
+    /// Bind the buffers to the tensors of the current shape.
+    fn set_io_mems(&mut self) -> Result<(), i32> {
+        let mut attr = self.io_attr;
+        for (mem, attr) in [
+            (self.a, &mut attr.A),
+            (self.b, &mut attr.B),
+            (self.c, &mut attr.C),
+        ] {
+            let ret = unsafe { rknpu2_sys::rknn_matmul_set_io_mem(self.ctx, mem, attr) };
+            if ret != 0 {
+                return Err(ret);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn create_mem(&self, size: u32) -> Result<*mut rknpu2_sys::rknn_tensor_mem, i32> {
+        let mem = unsafe { rknpu2_sys::rknn_create_mem(self.ctx, size) };
+        if mem.is_null() {
+            Err(rknpu2_sys::RKNN_ERR_MALLOC_FAIL)
+        } else {
+            Ok(mem)
+        }
+    }
+
+    pub fn ctx(&self) -> RKNNMatmulContext {
+        self.ctx
+    }
+
+    pub fn info(&self) -> &RKNNMatmulInfo {
+        &self.info
+    }
+
+    /// Tensor attributes of A, B and C as reported by the runtime.
+    pub fn io_attr(&self) -> &RKNNMatmulIOAttr {
+        &self.io_attr
+    }
+
+    /// `(m, k, n)`
+    pub fn shape(&self) -> (usize, usize, usize) {
+        (
+            self.info.M as usize,
+            self.info.K as usize,
+            self.info.N as usize,
+        )
+    }
+
+    /// Pin the matmul to NPU cores, on chips with more than one.
+    pub fn set_core_mask(&mut self, core_mask: RKNNCoreMask) -> Result<(), i32> {
+        let ret = unsafe { rknpu2_sys::rknn_matmul_set_core_mask(self.ctx, core_mask) };
+        if ret == 0 {
+            Ok(())
+        } else {
+            Err(ret)
+        }
+    }
+
+    fn buffer(&mut self, mem: *mut rknpu2_sys::rknn_tensor_mem) -> &mut [u8] {
+        // the runtime allocated `size` bytes at `virt_addr` for this context
+        unsafe { slice::from_raw_parts_mut((*mem).virt_addr as *mut u8, (*mem).size as usize) }
+    }
+
+    fn bytes(&self, mem: *mut rknpu2_sys::rknn_tensor_mem) -> &[u8] {
+        unsafe { slice::from_raw_parts((*mem).virt_addr as *const u8, (*mem).size as usize) }
+    }
+
+    fn write(
+        &mut self,
+        mem: *mut rknpu2_sys::rknn_tensor_mem,
+        src: ArrayView2<T::Input>,
+        shape: (usize, usize),
+    ) -> Result<(), i32> {
+        if src.dim() != shape {
+            return Err(rknpu2_sys::RKNN_ERR_PARAM_INVALID);
+        }
+        let src = src.as_standard_layout();
+        let dst = self.buffer(mem);
+        if dst.len() < T::input_size(src.len()) {
+            return Err(rknpu2_sys::RKNN_ERR_PARAM_INVALID);
+        }
+        T::encode(src.as_slice().unwrap(), dst);
+
+        Ok(())
+    }
+
+    /// Copy an `(m, k)` matrix into the A buffer.
+    pub fn set_a(&mut self, a: ArrayView2<T::Input>) -> Result<(), i32> {
+        let (m, k, _) = self.shape();
+        self.write(self.a, a, (m, k))
+    }
+
+    /// Copy a `(k, n)` matrix into the B buffer, packing it first for a
+    /// native layout context.
+    pub fn set_b(&mut self, b: ArrayView2<T::Input>) -> Result<(), i32> {
+        let (_, k, n) = self.shape();
+        match self.native_b {
+            None => self.write(self.b, b, (k, n)),
+            Some(chip) => {
+                if b.dim() != (k, n) {
+                    return Err(rknpu2_sys::RKNN_ERR_PARAM_INVALID);
+                }
+                let packed = layout::pack_b::<T>(b, chip)?;
+                self.set_b_packed(&packed)
+            }
+        }
+    }
+
+    /// Copy B packed with [`layout::pack_b`] for the chip of this context.
+    pub fn set_b_packed(&mut self, packed: &[u8]) -> Result<(), i32> {
+        if self.native_b.is_none() {
+            return Err(rknpu2_sys::RKNN_ERR_PARAM_INVALID);
+        }
+        self.set_b_bytes(packed)
+    }
+
+    /// Copy an already encoded B, for types whose B differs from A.
+    pub(crate) fn set_b_bytes(&mut self, packed: &[u8]) -> Result<(), i32> {
+        let b = self.b;
+        let dst = self.buffer(b);
+        if dst.len() < packed.len() {
+            return Err(rknpu2_sys::RKNN_ERR_PARAM_INVALID);
+        }
+        dst[..packed.len()].copy_from_slice(packed);
+
+        Ok(())
+    }
+
+    pub fn run(&mut self) -> Result<(), i32> {
+        let ret = unsafe { rknpu2_sys::rknn_matmul_run(self.ctx) };
+        if ret == 0 {
+            Ok(())
+        } else {
+            Err(ret)
+        }
+    }
+
+    /// The `(m, n)` result of the last run.
+    pub fn c(&self) -> Array2<T::Output> {
+        let (m, _, n) = self.shape();
+        let values = T::decode(self.bytes(self.c), m * n);
+
+        Array2::from_shape_vec((m, n), values).unwrap()
+    }
+
+    /// Set both inputs, run and read the result.
+    pub fn matmul(
+        &mut self,
+        a: ArrayView2<T::Input>,
+        b: ArrayView2<T::Input>,
+    ) -> Result<Array2<T::Output>, i32> {
+        self.set_a(a)?;
+        self.set_b(b)?;
+        self.run()?;
+
+        Ok(self.c())
+    }
+}
+
+impl<T: MatmulType> Drop for MatmulContext<T> {
+    fn drop(&mut self) {
+        unsafe {
+            for mem in [self.a, self.b, self.c] {
+                if !mem.is_null() {
+                    rknpu2_sys::rknn_destroy_mem(self.ctx, mem);
+                }
+            }
+            rknpu2_sys::rknn_matmul_destroy(self.ctx);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode() {
+        let values: Vec<i8> = vec![-8, 7, -1, 0, 3];
+        let mut packed = vec![0u8; Int4::input_size(values.len())];
+        Int4::encode(&values, &mut packed);
+        assert_eq!(packed, vec![0x78, 0x0f, 0x03]);
+        assert_eq!(Int4::decode_input(&packed, values.len()), values);
+
+        let mut half = vec![0u8; Float16::input_size(2)];
+        Float16::encode(&[1.0, -2.0], &mut half);
+        assert_eq!(half[..2], 0x3c00u16.to_ne_bytes());
+        assert_eq!(half[2..], 0xc000u16.to_ne_bytes());
+        assert_eq!(Float16::decode_input(&half, 2), vec![1.0, -2.0]);
+
+        let c: Vec<u8> = [5i32, -6].iter().flat_map(|v| v.to_ne_bytes()).collect();
+        assert_eq!(Int8::decode(&c, 2), vec![5, -6]);
+    }
+}
@@ -0,0 +1,488 @@
+//! Symmetric int8 and int4 quantization of matmul weights.
+//!
+//! B is quantized per layer, per output channel (column) or per group of
+//! `group_size` rows within each channel.
+//!
+//! From SDK v1.6.0 on, [`QuantizedMatmul`] hands the scales of B to the
+//! runtime and feeds A as fp16, so the NPU returns the float result of a
+//! single matmul. Per group scales need SDK v2.3.0.
+//!
+//! The v1.5.2 runtime has no quantization parameters on the matmul. There A
+//! is quantized per row when it is fed, every product `C[m][n]` is rescaled
+//! on the CPU by the scale of row `m` of A and column `n` of B, and one
+//! matmul runs per group.
+
+use ndarray::prelude::*;
+use std::fmt;
+use std::marker::PhantomData;
+
+use super::layout::{self, Chip};
+#[cfg(rknn_v1_6)]
+use super::{Float16, RKNNMatmulInfo};
+use super::{Int4, Int8, MatmulContext, MatmulType, RKNNCoreMask};
+
+/// An integer matmul type.
+pub trait IntMatmulType: MatmulType<Input = i8, Output: Into<i32>> {
+    /// Largest magnitude of a quantized value.
+    const QMAX: i8;
+    /// fp16 A by a B of this type, scaled to f32 C by the runtime.
+    #[cfg(rknn_v1_6)]
+    const SCALED: rknpu2_sys::rknn_matmul_type;
+}
+
+impl IntMatmulType for Int8 {
+    const QMAX: i8 = 127;
+    #[cfg(rknn_v1_6)]
+    const SCALED: rknpu2_sys::rknn_matmul_type =
+        rknpu2_sys::_rknn_matmul_type_RKNN_FLOAT16_MM_INT8_TO_FLOAT32;
+}
+
+impl IntMatmulType for Int4 {
+    const QMAX: i8 = 7;
+    #[cfg(rknn_v1_6)]
+    const SCALED: rknpu2_sys::rknn_matmul_type =
+        rknpu2_sys::_rknn_matmul_type_RKNN_FLOAT16_MM_INT4_TO_FLOAT32;
+}
+
+/// fp16 A and f32 C of a matmul with a quantized B of type `T`. B is not
+/// taken as an ndarray, it is written encoded as `T`.
+#[cfg(rknn_v1_6)]
+#[derive(Debug)]
+struct Scaled<T>(PhantomData<T>);
+
+#[cfg(rknn_v1_6)]
+impl<T: IntMatmulType> MatmulType for Scaled<T> {
+    type Input = f32;
+    type Output = f32;
+    const RAW: rknpu2_sys::rknn_matmul_type = T::SCALED;
+
+    fn input_size(len: usize) -> usize {
+        Float16::input_size(len)
+    }
+
+    fn encode(src: &[f32], dst: &mut [u8]) {
+        Float16::encode(src, dst)
+    }
+
+    fn decode_input(src: &[u8], len: usize) -> Vec<f32> {
+        Float16::decode_input(src, len)
+    }
+
+    fn decode(src: &[u8], len: usize) -> Vec<f32> {
+        Float16::decode(src, len)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuantGranularity {
+    /// One scale for the whole matrix.
+    PerLayer,
+    /// One scale per column of B.
+    PerChannel,
+    /// One scale per `group_size` rows of each column of B.
+    PerGroup(usize),
+}
+
+fn scale_for(max_abs: f32, qmax: i8) -> f32 {
+    if max_abs > 0.0 {
+        max_abs / qmax as f32
+    } else {
+        1.0
+    }
+}
+
+fn quantize_value(v: f32, scale: f32, qmax: i8) -> i8 {
+    (v / scale).round().clamp(-qmax as f32, qmax as f32) as i8
+}
+
+/// Quantize every row of A with its own scale.
+pub fn quantize_rows<T: IntMatmulType>(a: ArrayView2<f32>) -> (Array2<i8>, Vec<f32>) {
+    let scales: Vec<f32> = a
+        .rows()
+        .into_iter()
+        .map(|row| scale_for(row.iter().fold(0.0f32, |m, v| m.max(v.abs())), T::QMAX))
+        .collect();
+    let values = Array2::from_shape_fn(a.dim(), |(r, c)| {
+        quantize_value(a[[r, c]], scales[r], T::QMAX)
+    });
+
+    (values, scales)
+}
+
+/// A quantized `(k, n)` B matrix.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuantizedB<T: IntMatmulType> {
+    pub values: Array2<i8>,
+    pub granularity: QuantGranularity,
+    /// `(groups, n)` scales, every row of B in group `k / group_size`.
+    pub scales: Array2<f32>,
+    kind: PhantomData<T>,
+}
+
+impl<T: IntMatmulType> QuantizedB<T> {
+    pub fn quantize(b: ArrayView2<f32>, granularity: QuantGranularity) -> Result<Self, i32> {
+        let (k, n) = b.dim();
+        if k == 0 || n == 0 || granularity == QuantGranularity::PerGroup(0) {
+            return Err(rknpu2_sys::RKNN_ERR_PARAM_INVALID);
+        }
+        let group_size = match granularity {
+            QuantGranularity::PerGroup(size) => size.min(k),
+            _ => k,
+        };
+        let groups = k.div_ceil(group_size);
+
+        let max_abs = |rows: std::ops::Range<usize>, cols: std::ops::Range<usize>| {
+            b.slice(s![rows, cols])
+                .iter()
+                .fold(0.0f32, |m, v| m.max(v.abs()))
+        };
+        let scales = match granularity {
+            QuantGranularity::PerLayer => {
+                Array2::from_elem((1, n), scale_for(max_abs(0..k, 0..n), T::QMAX))
+            }
+            _ => Array2::from_shape_fn((groups, n), |(g, c)| {
+                let rows = g * group_size..((g + 1) * group_size).min(k);
+                scale_for(max_abs(rows, c..c + 1), T::QMAX)
+            }),
+        };
+        let values = Array2::from_shape_fn((k, n), |(r, c)| {
+            quantize_value(b[[r, c]], scales[[r / group_size, c]], T::QMAX)
+        });
+
+        Ok(QuantizedB {
+            values,
+            granularity,
+            scales,
+            kind: PhantomData,
+        })
+    }
+
+    /// Rows of B sharing a scale.
+    pub fn group_size(&self) -> usize {
+        let k = self.values.nrows();
+        match self.granularity {
+            QuantGranularity::PerGroup(size) => size.min(k),
+            _ => k,
+        }
+    }
+
+    pub fn scale(&self, k: usize, n: usize) -> f32 {
+        self.scales[[k / self.group_size(), n]]
+    }
+
+    pub fn dequantize(&self) -> Array2<f32> {
+        Array2::from_shape_fn(self.values.dim(), |(r, c)| {
+            self.values[[r, c]] as f32 * self.scale(r, c)
+        })
+    }
+
+    /// Rows `group * group_size..` of the quantized values.
+    pub fn group(&self, group: usize) -> ArrayView2<'_, i8> {
+        let size = self.group_size();
+        let end = ((group + 1) * size).min(self.values.nrows());
+        self.values.slice(s![group * size..end, ..])
+    }
+
+    /// Pack one group in the native layout of `chip`, as fed to the matmul.
+    pub fn pack_group(&self, group: usize, chip: Chip) -> Result<Vec<u8>, i32> {
+        layout::pack_b::<T>(self.group(group), chip)
+    }
+
+    /// `a * b` on the CPU with A quantized per row, as
+    /// [`QuantizedMatmul::run`] computes it before SDK v1.6.0.
+    pub fn matmul_reference(&self, a: ArrayView2<f32>) -> Array2<f32> {
+        let (a_values, a_scales) = quantize_rows::<T>(a);
+        let (m, n) = (a.nrows(), self.values.ncols());
+        let size = self.group_size();
+
+        let mut c = Array2::zeros((m, n));
+        for g in 0..self.scales.nrows() {
+            let rows = g * size..((g + 1) * size).min(self.values.nrows());
+            for ((r, col), out) in c.indexed_iter_mut() {
+                let acc: i32 = rows
+                    .clone()
+                    .map(|k| a_values[[r, k]] as i32 * self.values[[k, col]] as i32)
+                    .sum();
+                *out += acc as f32 * a_scales[r] * self.scales[[g, col]];
+            }
+        }
+
+        c
+    }
+}
+
+/// A matmul with a fixed quantized B and float A and C.
+#[derive(Debug)]
+pub struct QuantizedMatmul<T: IntMatmulType> {
+    /// One context per group of B.
+    #[cfg(not(rknn_v1_6))]
+    contexts: Vec<MatmulContext<T>>,
+    /// A single context, the runtime applies the scales of B.
+    #[cfg(rknn_v1_6)]
+    ctx: MatmulContext<Scaled<T>>,
+    b: QuantizedB<T>,
+}
+
+impl<T: IntMatmulType> QuantizedMatmul<T> {
+    /// Create the contexts for `m` rows of A and load B, in the native layout
+    /// of `chip` if given.
+    #[cfg(not(rknn_v1_6))]
+    pub fn new(m: usize, b: QuantizedB<T>, chip: Option<Chip>) -> Result<Self, i32> {
+        let n = b.values.ncols();
+        let mut contexts = Vec::with_capacity(b.scales.nrows());
+        for g in 0..b.scales.nrows() {
+            let values = b.group(g);
+            let mut ctx = match chip {
+                Some(chip) => MatmulContext::new_native(m, values.nrows(), n, chip)?,
+                None => MatmulContext::new(m, values.nrows(), n)?,
+            };
+            // packed on the way in for a native layout context
+            ctx.set_b(values)?;
+            contexts.push(ctx);
+        }
+
+        Ok(QuantizedMatmul { contexts, b })
+    }
+
+    /// Create the context for `m` rows of A, load B and its scales, B in the
+    /// native layout of `chip` for its type if given.
+    #[cfg(rknn_v1_6)]
+    pub fn new(m: usize, b: QuantizedB<T>, chip: Option<Chip>) -> Result<Self, i32> {
+        let (k, n) = b.values.dim();
+        if let Some(chip) = chip {
+            layout::b_tile(chip, T::RAW).ok_or(rknpu2_sys::RKNN_ERR_PARAM_INVALID)?;
+        }
+        let quant_type = match b.granularity {
+            QuantGranularity::PerLayer => {
+                rknpu2_sys::_rknn_matmul_quant_type_RKNN_QUANT_TYPE_PER_LAYER_SYM
+            }
+            QuantGranularity::PerChannel => {
+                rknpu2_sys::_rknn_matmul_quant_type_RKNN_QUANT_TYPE_PER_CHANNEL_SYM
+            }
+            #[cfg(rknn_v2)]
+            QuantGranularity::PerGroup(_) => {
+                rknpu2_sys::_rknn_matmul_quant_type_RKNN_QUANT_TYPE_PER_GROUP_SYM
+            }
+            #[cfg(not(rknn_v2))]
+            QuantGranularity::PerGroup(_) => return Err(rknpu2_sys::RKNN_ERR_PARAM_INVALID),
+        };
+        let configure = |info: &mut RKNNMatmulInfo| {
+            info.B_quant_type = quant_type as i16;
+            #[cfg(rknn_v2)]
+            if let QuantGranularity::PerGroup(_) = b.granularity {
+                info.group_size = b.group_size() as i16;
+            }
+        };
+        let mut ctx = MatmulContext::create_with(m, k, n, chip, configure)?;
+
+        let encoded = match chip {
+            Some(chip) => layout::pack_b::<T>(b.values.view(), chip)?,
+            None => {
+                let values = b.values.as_standard_layout();
+                let mut encoded = vec![0u8; T::input_size(values.len())];
+                T::encode(values.as_slice().unwrap(), &mut encoded);
+                encoded
+            }
+        };
+        ctx.set_b_bytes(&encoded)?;
+        set_b_scales(&ctx, &b)?;
+
+        Ok(QuantizedMatmul { ctx, b })
+    }
+
+    pub fn b(&self) -> &QuantizedB<T> {
+        &self.b
+    }
+
+    #[cfg(not(rknn_v1_6))]
+    pub fn set_core_mask(&mut self, core_mask: RKNNCoreMask) -> Result<(), i32> {
+        for ctx in &mut self.contexts {
+            ctx.set_core_mask(core_mask)?;
+        }
+        Ok(())
+    }
+
+    #[cfg(rknn_v1_6)]
+    pub fn set_core_mask(&mut self, core_mask: RKNNCoreMask) -> Result<(), i32> {
+        self.ctx.set_core_mask(core_mask)
+    }
+
+    /// `a * b` for an `(m, k)` A.
+    #[cfg(not(rknn_v1_6))]
+    pub fn run(&mut self, a: ArrayView2<f32>) -> Result<Array2<f32>, i32> {
+        let (a_values, a_scales) = quantize_rows::<T>(a);
+        let size = self.b.group_size();
+        let (m, _, n) = self.contexts[0].shape();
+        if a.dim() != (m, self.b.values.nrows()) {
+            return Err(rknpu2_sys::RKNN_ERR_PARAM_INVALID);
+        }
+
+        let mut c = Array2::zeros((m, n));
+        for (g, ctx) in self.contexts.iter_mut().enumerate() {
+            let (_, k, _) = ctx.shape();
+            ctx.set_a(a_values.slice(s![.., g * size..g * size + k]))?;
+            ctx.run()?;
+            for ((r, col), &acc) in ctx.c().indexed_iter() {
+                c[[r, col]] += acc.into() as f32 * a_scales[r] * self.b.scales[[g, col]];
+            }
+        }
+
+        Ok(c)
+    }
+
+    /// `a * b` for an `(m, k)` A.
+    #[cfg(rknn_v1_6)]
+    pub fn run(&mut self, a: ArrayView2<f32>) -> Result<Array2<f32>, i32> {
+        self.ctx.set_a(a)?;
+        self.ctx.run()?;
+
+        Ok(self.ctx.c())
+    }
+}
+
+/// Hand the scales of B to the runtime, ordered by channel and then by group.
+#[cfg(rknn_v1_6)]
+fn set_b_scales<T: IntMatmulType>(
+    ctx: &MatmulContext<Scaled<T>>,
+    b: &QuantizedB<T>,
+) -> Result<(), i32> {
+    let mut scales: Vec<f32> = match b.granularity {
+        QuantGranularity::PerLayer => vec![b.scales[[0, 0]]],
+        _ => b.scales.t().iter().copied().collect(),
+    };
+    let mut zps = vec![0i32; scales.len()];
+
+    let mut params: rknpu2_sys::rknn_quant_params = unsafe { std::mem::zeroed() };
+    params.name = ctx.io_attr().B.name;
+    params.scale = scales.as_mut_ptr();
+    params.scale_len = scales.len() as i32;
+    params.zp = zps.as_mut_ptr();
+    params.zp_len = zps.len() as i32;
+    // the runtime copies the parameters
+    let ret = unsafe { rknpu2_sys::rknn_matmul_set_quant_params(ctx.ctx(), &mut params) };
+    if ret != 0 {
+        return Err(ret);
+    }
+
+    Ok(())
+}
+
+/// How far a result is from a float reference.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ErrorReport {
+    pub max_abs: f32,
+    pub mean_abs: f32,
+    pub rmse: f32,
+    /// Signal to noise ratio in dB, infinite for an exact result.
+    pub snr_db: f32,
+    /// Cosine similarity of the flattened matrices.
+    pub cosine: f32,
+}
+
+impl ErrorReport {
+    /// Fails with `RKNN_ERR_PARAM_INVALID` if the shapes differ.
+    pub fn compare(result: ArrayView2<f32>, reference: ArrayView2<f32>) -> Result<Self, i32> {
+        if result.dim() != reference.dim() {
+            return Err(rknpu2_sys::RKNN_ERR_PARAM_INVALID);
+        }
+        let len = result.len().max(1) as f64;
+
+        let (mut max_abs, mut sum_abs, mut noise, mut signal) = (0f64, 0f64, 0f64, 0f64);
+        let (mut dot, mut norm_r) = (0f64, 0f64);
+        for (&r, &e) in result.iter().zip(reference.iter()) {
+            let (r, e) = (r as f64, e as f64);
+            let d = (r - e).abs();
+            max_abs = max_abs.max(d);
+            sum_abs += d;
+            noise += d * d;
+            signal += e * e;
+            dot += r * e;
+            norm_r += r * r;
+        }
+
+        Ok(ErrorReport {
+            max_abs: max_abs as f32,
+            mean_abs: (sum_abs / len) as f32,
+            rmse: (noise / len).sqrt() as f32,
+            snr_db: (10.0 * (signal / noise).log10()) as f32,
+            cosine: if signal > 0.0 && norm_r > 0.0 {
+                (dot / (signal * norm_r).sqrt()) as f32
+            } else {
+                0.0
+            },
+        })
+    }
+}
+
+impl fmt::Display for ErrorReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "max abs {:.6}, mean abs {:.6}, rmse {:.6}, snr {:.2} dB, cosine {:.6}",
+            self.max_abs, self.mean_abs, self.rmse, self.snr_db, self.cosine
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Columns and row blocks with very different magnitudes.
+    fn weights() -> Array2<f32> {
+        Array2::from_shape_fn((64, 8), |(r, c)| {
+            let magnitude = (1 + c) as f32 * if r < 32 { 0.1 } else { 1.0 };
+            magnitude * ((r * 7 + c * 3) % 11) as f32 / 5.0 - magnitude
+        })
+    }
+
+    #[test]
+    fn test_granularity() {
+        let b = weights();
+        let error = |granularity| {
+            let q = QuantizedB::<Int4>::quantize(b.view(), granularity).unwrap();
+            ErrorReport::compare(q.dequantize().view(), b.view()).unwrap()
+        };
+        let layer = error(QuantGranularity::PerLayer);
+        let channel = error(QuantGranularity::PerChannel);
+        let group = error(QuantGranularity::PerGroup(32));
+        assert!(channel.rmse < layer.rmse);
+        assert!(group.rmse < channel.rmse);
+
+        let q = QuantizedB::<Int8>::quantize(b.view(), QuantGranularity::PerGroup(32)).unwrap();
+        assert_eq!(q.scales.dim(), (2, 8));
+        assert_eq!(q.values.iter().map(|v| v.abs()).max(), Some(127));
+        assert!(QuantizedB::<Int8>::quantize(b.view(), QuantGranularity::PerGroup(0)).is_err());
+    }
+
+    #[test]
+    fn test_matmul_reference() {
+        let b = weights();
+        let a = Array2::from_shape_fn((4, 64), |(r, c)| ((r * 5 + c) % 9) as f32 - 4.0);
+        let reference = a.dot(&b);
+
+        let q = QuantizedB::<Int8>::quantize(b.view(), QuantGranularity::PerGroup(32)).unwrap();
+        let report =
+            ErrorReport::compare(q.matmul_reference(a.view()).view(), reference.view()).unwrap();
+        assert!(report.cosine > 0.9999, "{report}");
+        assert!(report.snr_db > 30.0, "{report}");
+    }
+
+    #[test]
+    fn test_error_report() {
+        let reference = arr2(&[[1.0, 2.0], [3.0, 4.0]]);
+        let result = arr2(&[[1.0, 2.0], [3.0, 5.0]]);
+        let report = ErrorReport::compare(result.view(), reference.view()).unwrap();
+        assert_eq!(report.max_abs, 1.0);
+        assert_eq!(report.mean_abs, 0.25);
+        assert_eq!(report.rmse, 0.5);
+        assert!((report.snr_db - 10.0 * 30f32.log10()).abs() < 1e-4);
+
+        let exact = ErrorReport::compare(reference.view(), reference.view()).unwrap();
+        assert_eq!(exact.snr_db, f32::INFINITY);
+        assert!((exact.cosine - 1.0).abs() < 1e-6);
+
+        let row = arr2(&[[1.0, 2.0, 3.0, 4.0]]);
+        assert!(ErrorReport::compare(row.view(), reference.view()).is_err());
+    }
+}
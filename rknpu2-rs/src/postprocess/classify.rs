@@ -0,0 +1,133 @@
+//! Classification heads.
+
+use super::labels::Labels;
+use super::{sigmoid, OutputTensor};
+use crate::{RKNNContextPack, RKNNOutput};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Activation {
+    /// Single label, scores sum to one.
+    #[default]
+    Softmax,
+    /// Multi label, every class scored independently.
+    Sigmoid,
+    /// The model already outputs probabilities.
+    None,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClassifyConfig {
+    pub activation: Activation,
+    pub top_k: usize,
+    /// Drop classes scoring below this after activation.
+    pub min_score: f32,
+}
+
+impl Default for ClassifyConfig {
+    fn default() -> Self {
+        ClassifyConfig {
+            activation: Activation::Softmax,
+            top_k: 5,
+            min_score: 0.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Classification<'a> {
+    pub class_id: usize,
+    pub label: Option<&'a str>,
+    pub score: f32,
+}
+
+/// Numerically stable softmax.
+pub fn softmax(logits: &[f32]) -> Vec<f32> {
+    let max = logits.iter().copied().fold(f32::MIN, f32::max);
+    let exp: Vec<f32> = logits.iter().map(|&v| (v - max).exp()).collect();
+    let sum: f32 = exp.iter().sum();
+
+    exp.into_iter().map(|v| v / sum).collect()
+}
+
+/// Top `config.top_k` classes of a logits tensor, highest score first.
+pub fn classify<'a>(
+    logits: &OutputTensor,
+    labels: Option<&'a Labels>,
+    config: &ClassifyConfig,
+) -> Vec<Classification<'a>> {
+    let values = logits.to_f32_vec();
+    let scores = match config.activation {
+        Activation::Softmax => softmax(&values),
+        Activation::Sigmoid => values.into_iter().map(sigmoid).collect(),
+        Activation::None => values,
+    };
+
+    let mut order: Vec<usize> = (0..scores.len())
+        .filter(|&i| scores[i] >= config.min_score)
+        .collect();
+    order.sort_by(|&a, &b| scores[b].total_cmp(&scores[a]));
+    order.truncate(config.top_k);
+
+    order
+        .into_iter()
+        .map(|class_id| Classification {
+            class_id,
+            label: labels.and_then(|l| l.get(class_id)),
+            score: scores[class_id],
+        })
+        .collect()
+}
+
+/// [`classify`] on the first output of `rknn_outputs_get`.
+pub fn post_process<'a>(
+    ctx: &RKNNContextPack,
+    outputs: &[RKNNOutput],
+    labels: Option<&'a Labels>,
+    config: &ClassifyConfig,
+) -> Result<Vec<Classification<'a>>, i32> {
+    // outputs come straight from rknn_outputs_get and match output_info
+    let tensors = unsafe { OutputTensor::from_outputs(&ctx.output_info, outputs)? };
+
+    Ok(match tensors.first() {
+        Some(logits) => classify(logits, labels, config),
+        None => Vec::new(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::postprocess::tests::attr;
+
+    #[test]
+    fn test_classify() {
+        let a = attr(&[1, 4], 0, 0.5);
+        let data: Vec<i8> = vec![2, 8, -4, 4];
+        let logits = OutputTensor::from_i8(&a, &data);
+        let labels = Labels::parse("a\nb\nc\nd\n");
+
+        let config = ClassifyConfig {
+            top_k: 2,
+            ..Default::default()
+        };
+        let top = classify(&logits, Some(&labels), &config);
+        assert_eq!(top.len(), 2);
+        assert_eq!((top[0].class_id, top[0].label), (1, Some("b")));
+        assert_eq!(top[1].class_id, 3);
+        let expected = softmax(&[1.0, 4.0, -2.0, 2.0]);
+        assert!((top[0].score - expected[1]).abs() < 1e-6);
+        assert!((softmax(&[1000.0, 1000.0])[0] - 0.5).abs() < 1e-6);
+
+        let multi = ClassifyConfig {
+            activation: Activation::Sigmoid,
+            top_k: 4,
+            min_score: 0.5,
+        };
+        let top = classify(&logits, None, &multi);
+        assert_eq!(
+            top.iter().map(|c| c.class_id).collect::<Vec<_>>(),
+            vec![1, 3, 0]
+        );
+        assert_eq!(top[0].label, None);
+    }
+}
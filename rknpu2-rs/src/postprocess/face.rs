@@ -0,0 +1,382 @@
+//! Face detection heads with five point landmarks.
+//!
+//! RetinaFace has three outputs over all priors: `[1, N, 4]` box deltas,
+//! `[1, N, 2]` background / face scores and `[1, N, 10]` landmark deltas,
+//! decoded against priors generated with [`RetinaFaceConfig::priors`].
+//!
+//! SCRFD has a score `[1, N, 1]`, box `[1, N, 4]` and landmark `[1, N, 10]`
+//! output per stride, with distances in stride units from anchors on the
+//! grid corners.
+
+use super::nms::nms;
+use super::{BBox, Detection, OutputTensor};
+use crate::preprocess::input_size;
+use crate::preprocess::letterbox::LetterboxTransform;
+use crate::{RKNNContextPack, RKNNOutput};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FaceConfig {
+    pub conf_thresh: f32,
+    pub iou_thresh: f32,
+}
+
+impl Default for FaceConfig {
+    fn default() -> Self {
+        FaceConfig {
+            conf_thresh: 0.5,
+            iou_thresh: 0.4,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Face {
+    pub score: f32,
+    pub bbox: BBox,
+    /// Left eye, right eye, nose, left and right mouth corner.
+    pub landmarks: [(f32, f32); 5],
+}
+
+impl Face {
+    /// Map from model input to source image coordinates.
+    pub fn to_source(&self, transform: &LetterboxTransform) -> Face {
+        Face {
+            score: self.score,
+            bbox: self.bbox.to_source(transform),
+            landmarks: self.landmarks.map(|(x, y)| transform.to_source(x, y)),
+        }
+    }
+}
+
+/// A RetinaFace prior box in input pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Prior {
+    pub cx: f32,
+    pub cy: f32,
+    pub w: f32,
+    pub h: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetinaFaceConfig {
+    /// Prior sizes of each stride, in input pixels.
+    pub min_sizes: Vec<Vec<f32>>,
+    pub steps: Vec<usize>,
+    /// Center and size variance of the box encoding.
+    pub variance: [f32; 2],
+}
+
+impl Default for RetinaFaceConfig {
+    fn default() -> Self {
+        RetinaFaceConfig {
+            min_sizes: vec![vec![16.0, 32.0], vec![64.0, 128.0], vec![256.0, 512.0]],
+            steps: vec![8, 16, 32],
+            variance: [0.1, 0.2],
+        }
+    }
+}
+
+impl RetinaFaceConfig {
+    /// Priors in output order: stride, row, column, then size.
+    pub fn priors(&self, input_h: usize, input_w: usize) -> Vec<Prior> {
+        let mut priors = Vec::new();
+        for (sizes, &step) in self.min_sizes.iter().zip(&self.steps) {
+            let (rows, cols) = (input_h.div_ceil(step), input_w.div_ceil(step));
+            for y in 0..rows {
+                for x in 0..cols {
+                    for &size in sizes {
+                        priors.push(Prior {
+                            cx: (x as f32 + 0.5) * step as f32,
+                            cy: (y as f32 + 0.5) * step as f32,
+                            w: size,
+                            h: size,
+                        });
+                    }
+                }
+            }
+        }
+
+        priors
+    }
+}
+
+/// Outputs of shape `[1, N, channels]`, by channel count.
+fn find_by_channels<'t, 'a>(
+    tensors: &'t [OutputTensor<'a>],
+    channels: u32,
+) -> impl Iterator<Item = &'t OutputTensor<'a>> {
+    tensors
+        .iter()
+        .filter(move |t| t.dims().len() == 3 && t.dims()[2] == channels)
+}
+
+fn suppress(candidates: Vec<Face>, iou_thresh: f32) -> Vec<Face> {
+    let dets: Vec<Detection> = candidates
+        .iter()
+        .map(|f| Detection {
+            class_id: 0,
+            score: f.score,
+            bbox: f.bbox,
+        })
+        .collect();
+
+    nms(&dets, iou_thresh)
+        .into_iter()
+        .map(|i| candidates[i])
+        .collect()
+}
+
+/// Decode RetinaFace outputs and apply NMS. Coordinates are in model input
+/// pixels, map them back with [`Face::to_source`].
+pub fn detect_retinaface(
+    tensors: &[OutputTensor],
+    input_h: usize,
+    input_w: usize,
+    retina: &RetinaFaceConfig,
+    config: &FaceConfig,
+) -> Vec<Face> {
+    let (Some(loc), Some(conf), Some(landms)) = (
+        find_by_channels(tensors, 4).next(),
+        find_by_channels(tensors, 2).next(),
+        find_by_channels(tensors, 10).next(),
+    ) else {
+        return Vec::new();
+    };
+    let priors = retina.priors(input_h, input_w);
+    let [v0, v1] = retina.variance;
+    let score_thresh = conf.raw_threshold(config.conf_thresh);
+
+    let mut candidates = Vec::new();
+    for (n, p) in priors.iter().enumerate().take(conf.len() / 2) {
+        if conf.raw(n * 2 + 1) < score_thresh {
+            continue;
+        }
+        let score = conf.get(n * 2 + 1);
+        if score < config.conf_thresh {
+            continue;
+        }
+
+        let d = |k: usize| loc.get(n * 4 + k);
+        let cx = p.cx + d(0) * v0 * p.w;
+        let cy = p.cy + d(1) * v0 * p.h;
+        let w = p.w * (d(2) * v1).exp();
+        let h = p.h * (d(3) * v1).exp();
+
+        let mut landmarks = [(0f32, 0f32); 5];
+        for (k, l) in landmarks.iter_mut().enumerate() {
+            *l = (
+                p.cx + landms.get(n * 10 + k * 2) * v0 * p.w,
+                p.cy + landms.get(n * 10 + k * 2 + 1) * v0 * p.h,
+            );
+        }
+
+        candidates.push(Face {
+            score,
+            bbox: BBox::new(cx - w / 2.0, cy - h / 2.0, cx + w / 2.0, cy + h / 2.0),
+            landmarks,
+        });
+    }
+
+    suppress(candidates, config.iou_thresh)
+}
+
+/// Decode SCRFD outputs with `num_anchors` anchors per grid cell and apply
+/// NMS. The stride of each output is found from its anchor count.
+///
+/// Fails with `RKNN_ERR_PARAM_INVALID` if `num_anchors` is 0.
+pub fn detect_scrfd(
+    tensors: &[OutputTensor],
+    input_h: usize,
+    input_w: usize,
+    num_anchors: usize,
+    config: &FaceConfig,
+) -> Result<Vec<Face>, i32> {
+    if num_anchors == 0 {
+        return Err(rknpu2_sys::RKNN_ERR_PARAM_INVALID);
+    }
+    let stride_of = |t: &OutputTensor| {
+        let n = *t.dims().get(1)? as usize / num_anchors;
+        [8, 16, 32, 64, 128]
+            .into_iter()
+            .find(|&s| input_h.div_ceil(s) * input_w.div_ceil(s) == n)
+    };
+    let find = |channels: u32, stride: usize| {
+        find_by_channels(tensors, channels).find(|t| stride_of(t) == Some(stride))
+    };
+
+    let mut candidates = Vec::new();
+    for scores in find_by_channels(tensors, 1) {
+        let Some(stride) = stride_of(scores) else {
+            continue;
+        };
+        let (Some(boxes), Some(kps)) = (find(4, stride), find(10, stride)) else {
+            continue;
+        };
+        let cols = input_w.div_ceil(stride);
+        let s = stride as f32;
+        let score_thresh = scores.raw_threshold(config.conf_thresh);
+
+        for n in 0..scores.len() {
+            if scores.raw(n) < score_thresh {
+                continue;
+            }
+            let score = scores.get(n);
+            if score < config.conf_thresh {
+                continue;
+            }
+
+            let cell = n / num_anchors;
+            let (ax, ay) = ((cell % cols) as f32 * s, (cell / cols) as f32 * s);
+            let d = |k: usize| boxes.get(n * 4 + k) * s;
+
+            let mut landmarks = [(0f32, 0f32); 5];
+            for (k, l) in landmarks.iter_mut().enumerate() {
+                *l = (
+                    ax + kps.get(n * 10 + k * 2) * s,
+                    ay + kps.get(n * 10 + k * 2 + 1) * s,
+                );
+            }
+
+            candidates.push(Face {
+                score,
+                bbox: BBox::new(ax - d(0), ay - d(1), ax + d(2), ay + d(3)),
+                landmarks,
+            });
+        }
+    }
+
+    Ok(suppress(candidates, config.iou_thresh))
+}
+
+/// [`detect_retinaface`] on the outputs of `rknn_outputs_get`.
+pub fn post_process_retinaface(
+    ctx: &RKNNContextPack,
+    outputs: &[RKNNOutput],
+    retina: &RetinaFaceConfig,
+    config: &FaceConfig,
+) -> Result<Vec<Face>, i32> {
+    let (input_h, input_w, _) = input_size(ctx.input_info.first().unwrap());
+    // outputs come straight from rknn_outputs_get and match output_info
+    let tensors = unsafe { OutputTensor::from_outputs(&ctx.output_info, outputs)? };
+
+    Ok(detect_retinaface(
+        &tensors, input_h, input_w, retina, config,
+    ))
+}
+
+/// [`detect_scrfd`] on the outputs of `rknn_outputs_get`.
+pub fn post_process_scrfd(
+    ctx: &RKNNContextPack,
+    outputs: &[RKNNOutput],
+    num_anchors: usize,
+    config: &FaceConfig,
+) -> Result<Vec<Face>, i32> {
+    let (input_h, input_w, _) = input_size(ctx.input_info.first().unwrap());
+    // outputs come straight from rknn_outputs_get and match output_info
+    let tensors = unsafe { OutputTensor::from_outputs(&ctx.output_info, outputs)? };
+
+    detect_scrfd(&tensors, input_h, input_w, num_anchors, config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::postprocess::tests::attr;
+    use crate::preprocess::letterbox::LetterboxAlign;
+
+    #[test]
+    fn test_priors() {
+        let priors = RetinaFaceConfig::default().priors(64, 64);
+        assert_eq!(priors.len(), 8 * 8 * 2 + 4 * 4 * 2 + 2 * 2 * 2);
+        assert_eq!(
+            priors[1],
+            Prior {
+                cx: 4.0,
+                cy: 4.0,
+                w: 32.0,
+                h: 32.0
+            }
+        );
+        assert_eq!(priors[128].cx, 8.0);
+    }
+
+    #[test]
+    fn test_retinaface() {
+        let retina = RetinaFaceConfig {
+            min_sizes: vec![vec![16.0]],
+            steps: vec![16],
+            variance: [0.1, 0.2],
+        };
+        // 32x32 input, 2x2 priors, only the last one is a face
+        let attrs = [
+            attr(&[1, 4, 4], 0, 1.0),
+            attr(&[1, 4, 2], 0, 1.0),
+            attr(&[1, 4, 10], 0, 1.0),
+        ];
+        let mut loc = vec![0f32; 16];
+        loc[12..].copy_from_slice(&[1.0, 0.0, 0.0, 5.0]);
+        let conf = vec![1.0, 0.0, 1.0, 0.0, 1.0, 0.0, 0.1, 0.9];
+        let mut landms = vec![0f32; 40];
+        landms[30] = -2.0;
+
+        let tensors = [
+            OutputTensor::from_f32(&attrs[0], &loc),
+            OutputTensor::from_f32(&attrs[1], &conf),
+            OutputTensor::from_f32(&attrs[2], &landms),
+        ];
+        let faces = detect_retinaface(&tensors, 32, 32, &retina, &FaceConfig::default());
+        assert_eq!(faces.len(), 1);
+        let face = faces[0];
+        assert_eq!(face.score, 0.9);
+        // prior at (24, 24) of size 16, center shifted by 0.1 * 16
+        let h = 16.0 * 1f32.exp();
+        assert!((face.bbox.x1 - (25.6 - 8.0)).abs() < 1e-5);
+        assert!((face.bbox.y2 - (24.0 + h / 2.0)).abs() < 1e-4);
+        assert!((face.landmarks[0].0 - 20.8).abs() < 1e-5);
+        assert_eq!(face.landmarks[1], (24.0, 24.0));
+    }
+
+    #[test]
+    fn test_scrfd() {
+        // 16x16 input with strides 8 and 16, one anchor per cell
+        let attrs = [
+            attr(&[1, 4, 1], 0, 1.0 / 128.0),
+            attr(&[1, 1, 1], 0, 1.0 / 128.0),
+            attr(&[1, 4, 4], 0, 1.0),
+            attr(&[1, 1, 4], 0, 1.0),
+            attr(&[1, 4, 10], 0, 1.0),
+            attr(&[1, 1, 10], 0, 1.0),
+        ];
+        let scores8: Vec<i8> = vec![0, 0, 0, 100];
+        let scores16: Vec<i8> = vec![10];
+        let mut boxes8 = vec![0i8; 16];
+        boxes8[12..].copy_from_slice(&[1, 1, 1, 1]);
+        let mut kps8 = vec![0i8; 40];
+        kps8[30..32].copy_from_slice(&[1, -1]);
+
+        let tensors = [
+            OutputTensor::from_i8(&attrs[0], &scores8),
+            OutputTensor::from_i8(&attrs[1], &scores16),
+            OutputTensor::from_i8(&attrs[2], &boxes8),
+            OutputTensor::from_i8(&attrs[3], &[0; 4]),
+            OutputTensor::from_i8(&attrs[4], &kps8),
+            OutputTensor::from_i8(&attrs[5], &[0; 10]),
+        ];
+        let faces = detect_scrfd(&tensors, 16, 16, 1, &FaceConfig::default()).unwrap();
+        assert_eq!(faces.len(), 1);
+        let face = faces[0];
+        assert_eq!(face.bbox, BBox::new(0.0, 0.0, 16.0, 16.0));
+        assert_eq!(face.landmarks[0], (16.0, 0.0));
+        assert_eq!(face.landmarks[3], (8.0, 8.0));
+
+        let transform = LetterboxTransform::new(32, 16, 16, 16, LetterboxAlign::Center).unwrap();
+        let src = face.to_source(&transform);
+        assert_eq!(src.landmarks[0], (32.0, -8.0));
+        assert_eq!(src.bbox, BBox::new(0.0, 0.0, 32.0, 16.0));
+
+        assert_eq!(
+            detect_scrfd(&tensors, 16, 16, 0, &FaceConfig::default()),
+            Err(rknpu2_sys::RKNN_ERR_PARAM_INVALID)
+        );
+    }
+}
@@ -0,0 +1,82 @@
+//! Class label lists.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::{get_custom_string, RKNNContext};
+
+/// Class names indexed by class id.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Labels {
+    pub names: Vec<String>,
+}
+
+impl Labels {
+    /// One label per line. Surrounding whitespace and `\r` are trimmed and
+    /// trailing empty lines dropped, so a final newline doesn't add a label.
+    pub fn parse(text: &str) -> Self {
+        let mut names: Vec<String> = text.lines().map(|l| l.trim().to_string()).collect();
+        while names.last().is_some_and(|l| l.is_empty()) {
+            names.pop();
+        }
+
+        Labels { names }
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(Self::parse(&fs::read_to_string(path)?))
+    }
+
+    /// Labels from a model custom string, either one per line or a single
+    /// comma separated line.
+    pub fn from_custom_string(text: &str) -> Self {
+        let text = text.trim();
+        if !text.contains('\n') && text.contains(',') {
+            Labels {
+                names: text.split(',').map(|l| l.trim().to_string()).collect(),
+            }
+        } else {
+            Self::parse(text)
+        }
+    }
+
+    /// Labels from the custom string of a loaded model.
+    pub fn from_model(ctx: RKNNContext) -> Result<Self, i32> {
+        Ok(Self::from_custom_string(&get_custom_string(ctx)?))
+    }
+
+    pub fn get(&self, class_id: usize) -> Option<&str> {
+        self.names.get(class_id).map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_labels() {
+        let labels = Labels::parse("person\r\nbicycle\n\ncar\n\n");
+        assert_eq!(labels.names, vec!["person", "bicycle", "", "car"]);
+        assert_eq!(labels.get(3), Some("car"));
+        assert_eq!(labels.get(4), None);
+
+        let coco = include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/assets/coco_80_labels_list.txt"
+        ));
+        assert_eq!(Labels::parse(coco).len(), 80);
+
+        let custom = Labels::from_custom_string("cat, dog,bird\n");
+        assert_eq!(custom.names, vec!["cat", "dog", "bird"]);
+    }
+}
@@ -0,0 +1,403 @@
+//! Decoding model outputs into results.
+
+pub mod classify;
+pub mod face;
+pub mod labels;
+pub mod nms;
+pub mod obb;
+pub mod ocr;
+pub mod pose;
+pub mod segmentation;
+pub mod yolo;
+pub mod yolo_seg;
+
+use std::slice;
+
+use crate::preprocess::letterbox::LetterboxTransform;
+use crate::quant::{deqnt_affine_to_f32, dequantize, f16_slice_to_f32, f16_to_f32};
+use crate::{RKNNOutput, RKNNTensorAttr};
+
+#[derive(Debug, Clone, Copy)]
+enum TensorData<'a> {
+    I8(&'a [i8]),
+    U8(&'a [u8]),
+    F16(&'a [u16]),
+    F32(&'a [f32]),
+}
+
+/// A typed view over one model output together with its attribute.
+///
+/// Values are read dequantized with [`get`](Self::get). For thresholding
+/// many values, compare [`raw`](Self::raw) against
+/// [`raw_threshold`](Self::raw_threshold) to stay in the quantized domain.
+#[derive(Debug, Clone, Copy)]
+pub struct OutputTensor<'a> {
+    pub attr: &'a RKNNTensorAttr,
+    data: TensorData<'a>,
+}
+
+impl<'a> OutputTensor<'a> {
+    /// View an output returned by `rknn_outputs_get`.
+    ///
+    /// # Safety
+    /// `output.buf` must point to `output.size` valid bytes that outlive `'a`,
+    /// laid out as described by `attr` (or f32 when `want_float` is set).
+    ///
+    /// Fails with `RKNN_ERR_PARAM_INVALID` for tensor types other than
+    /// INT8, UINT8, FLOAT16 and FLOAT32, and when the buffer is too small
+    /// for `attr.n_elems` values.
+    pub unsafe fn from_output(
+        attr: &'a RKNNTensorAttr,
+        output: &'a RKNNOutput,
+    ) -> Result<Self, i32> {
+        let type_ = if output.want_float != 0 {
+            rknpu2_sys::_rknn_tensor_type_RKNN_TENSOR_FLOAT32
+        } else {
+            attr.type_
+        };
+        let elem_size = match type_ {
+            rknpu2_sys::_rknn_tensor_type_RKNN_TENSOR_INT8
+            | rknpu2_sys::_rknn_tensor_type_RKNN_TENSOR_UINT8 => 1,
+            rknpu2_sys::_rknn_tensor_type_RKNN_TENSOR_FLOAT16 => 2,
+            rknpu2_sys::_rknn_tensor_type_RKNN_TENSOR_FLOAT32 => 4,
+            _ => return Err(rknpu2_sys::RKNN_ERR_PARAM_INVALID),
+        };
+        let buf = output.buf;
+        let len = output.size as usize / elem_size;
+        if buf.is_null() || len < attr.n_elems as usize {
+            return Err(rknpu2_sys::RKNN_ERR_PARAM_INVALID);
+        }
+
+        let data = match type_ {
+            rknpu2_sys::_rknn_tensor_type_RKNN_TENSOR_UINT8 => {
+                TensorData::U8(slice::from_raw_parts(buf as *const u8, len))
+            }
+            rknpu2_sys::_rknn_tensor_type_RKNN_TENSOR_FLOAT16 => {
+                TensorData::F16(slice::from_raw_parts(buf as *const u16, len))
+            }
+            rknpu2_sys::_rknn_tensor_type_RKNN_TENSOR_FLOAT32 => {
+                TensorData::F32(slice::from_raw_parts(buf as *const f32, len))
+            }
+            _ => TensorData::I8(slice::from_raw_parts(buf as *const i8, len)),
+        };
+
+        Ok(OutputTensor { attr, data })
+    }
+
+    /// Views of all outputs of a model.
+    ///
+    /// # Safety
+    /// See [`from_output`](Self::from_output).
+    ///
+    /// Fails with `RKNN_ERR_PARAM_INVALID` when there are not as many
+    /// outputs as attributes.
+    pub unsafe fn from_outputs(
+        attrs: &'a [RKNNTensorAttr],
+        outputs: &'a [RKNNOutput],
+    ) -> Result<Vec<Self>, i32> {
+        if attrs.len() != outputs.len() {
+            return Err(rknpu2_sys::RKNN_ERR_PARAM_INVALID);
+        }
+        attrs
+            .iter()
+            .zip(outputs)
+            .map(|(attr, output)| Self::from_output(attr, output))
+            .collect()
+    }
+
+    pub fn from_i8(attr: &'a RKNNTensorAttr, data: &'a [i8]) -> Self {
+        OutputTensor {
+            attr,
+            data: TensorData::I8(data),
+        }
+    }
+
+    pub fn from_f32(attr: &'a RKNNTensorAttr, data: &'a [f32]) -> Self {
+        OutputTensor {
+            attr,
+            data: TensorData::F32(data),
+        }
+    }
+
+    pub fn dims(&self) -> &'a [u32] {
+        &self.attr.dims[..self.attr.n_dims as usize]
+    }
+
+    pub fn len(&self) -> usize {
+        match self.data {
+            TensorData::I8(d) => d.len(),
+            TensorData::U8(d) => d.len(),
+            TensorData::F16(d) => d.len(),
+            TensorData::F32(d) => d.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// `(channel, height, width)` of a 4D output, whatever its layout.
+    /// For NC1HWC2 outputs the channel count includes the C2 padding.
+    pub fn chw(&self) -> (usize, usize, usize) {
+        let d = self.dims();
+        let d = |i: usize| d.get(i).copied().unwrap_or(1) as usize;
+        match self.attr.fmt {
+            rknpu2_sys::_rknn_tensor_format_RKNN_TENSOR_NHWC => (d(3), d(1), d(2)),
+            rknpu2_sys::_rknn_tensor_format_RKNN_TENSOR_NC1HWC2 => (d(1) * d(4), d(2), d(3)),
+            _ => (d(1), d(2), d(3)),
+        }
+    }
+
+    /// Flat index of `(channel, y, x)` of a 4D output.
+    pub fn index(&self, c: usize, y: usize, x: usize) -> usize {
+        let (channels, h, w) = self.chw();
+        match self.attr.fmt {
+            rknpu2_sys::_rknn_tensor_format_RKNN_TENSOR_NHWC => (y * w + x) * channels + c,
+            rknpu2_sys::_rknn_tensor_format_RKNN_TENSOR_NC1HWC2 => {
+                let c2 = self.dims()[4] as usize;
+                (((c / c2) * h + y) * w + x) * c2 + c % c2
+            }
+            _ => (c * h + y) * w + x,
+        }
+    }
+
+    /// Dequantized value at a flat index.
+    pub fn get(&self, i: usize) -> f32 {
+        let (zp, scale) = (self.attr.zp, self.attr.scale);
+        match self.data {
+            TensorData::I8(d) => deqnt_affine_to_f32(d[i], zp, scale),
+            TensorData::U8(d) => (d[i] as f32 - zp as f32) * scale,
+            TensorData::F16(d) => f16_to_f32(d[i]),
+            TensorData::F32(d) => d[i],
+        }
+    }
+
+    /// Dequantized value at `(channel, y, x)` of a 4D output.
+    pub fn at(&self, c: usize, y: usize, x: usize) -> f32 {
+        self.get(self.index(c, y, x))
+    }
+
+    /// Value at a flat index without dequantization.
+    pub fn raw(&self, i: usize) -> f32 {
+        match self.data {
+            TensorData::I8(d) => d[i] as f32,
+            TensorData::U8(d) => d[i] as f32,
+            TensorData::F16(d) => f16_to_f32(d[i]),
+            TensorData::F32(d) => d[i],
+        }
+    }
+
+    /// `thresh` in the domain of [`raw`](Self::raw): the smallest raw value
+    /// that dequantizes to `thresh` or more, so keep values `>=` it.
+    pub fn raw_threshold(&self, thresh: f32) -> f32 {
+        let (zp, scale) = (self.attr.zp, self.attr.scale);
+        match self.data {
+            // unclamped, a threshold above the range rejects every value
+            TensorData::I8(_) | TensorData::U8(_) => (thresh / scale + zp as f32).ceil(),
+            _ => thresh,
+        }
+    }
+
+    /// Dequantize the whole tensor.
+    pub fn to_f32_vec(&self) -> Vec<f32> {
+        let mut out = vec![0f32; self.len()];
+        match self.data {
+            TensorData::I8(d) => dequantize(d, self.attr.zp, self.attr.scale, &mut out),
+            TensorData::F16(d) => f16_slice_to_f32(d, &mut out),
+            TensorData::F32(d) => out.copy_from_slice(d),
+            TensorData::U8(_) => {
+                for (i, v) in out.iter_mut().enumerate() {
+                    *v = self.get(i);
+                }
+            }
+        }
+        out
+    }
+}
+
+/// Axis aligned box as `(x1, y1, x2, y2)`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct BBox {
+    pub x1: f32,
+    pub y1: f32,
+    pub x2: f32,
+    pub y2: f32,
+}
+
+impl BBox {
+    pub fn new(x1: f32, y1: f32, x2: f32, y2: f32) -> Self {
+        BBox { x1, y1, x2, y2 }
+    }
+
+    pub fn width(&self) -> f32 {
+        (self.x2 - self.x1).max(0.0)
+    }
+
+    pub fn height(&self) -> f32 {
+        (self.y2 - self.y1).max(0.0)
+    }
+
+    pub fn area(&self) -> f32 {
+        self.width() * self.height()
+    }
+
+    pub fn center(&self) -> (f32, f32) {
+        ((self.x1 + self.x2) / 2.0, (self.y1 + self.y2) / 2.0)
+    }
+
+    pub fn iou(&self, other: &BBox) -> f32 {
+        let inter = BBox::new(
+            self.x1.max(other.x1),
+            self.y1.max(other.y1),
+            self.x2.min(other.x2),
+            self.y2.min(other.y2),
+        )
+        .area();
+        let union = self.area() + other.area() - inter;
+
+        if union <= 0.0 {
+            0.0
+        } else {
+            inter / union
+        }
+    }
+
+    /// Map from model input to source image coordinates.
+    pub fn to_source(&self, transform: &LetterboxTransform) -> BBox {
+        let (x1, y1, x2, y2) = transform.rect_to_source((self.x1, self.y1, self.x2, self.y2));
+        BBox { x1, y1, x2, y2 }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Detection {
+    pub class_id: usize,
+    pub score: f32,
+    pub bbox: BBox,
+}
+
+impl Detection {
+    pub fn to_source(&self, transform: &LetterboxTransform) -> Detection {
+        Detection {
+            bbox: self.bbox.to_source(transform),
+            ..*self
+        }
+    }
+}
+
+pub(crate) fn sigmoid(x: f32) -> f32 {
+    1.0 / (1.0 + (-x).exp())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::mem;
+
+    /// A NCHW output attribute for tests.
+    pub(crate) fn attr(dims: &[u32], zp: i32, scale: f32) -> RKNNTensorAttr {
+        let mut attr: RKNNTensorAttr = unsafe { mem::zeroed() };
+        attr.n_dims = dims.len() as u32;
+        attr.dims[..dims.len()].copy_from_slice(dims);
+        attr.n_elems = dims.iter().product();
+        attr.fmt = rknpu2_sys::_rknn_tensor_format_RKNN_TENSOR_NCHW;
+        attr.type_ = rknpu2_sys::_rknn_tensor_type_RKNN_TENSOR_INT8;
+        attr.qnt_type = rknpu2_sys::_rknn_tensor_qnt_type_RKNN_TENSOR_QNT_AFFINE_ASYMMETRIC;
+        attr.zp = zp;
+        attr.scale = scale;
+        attr
+    }
+
+    #[test]
+    fn test_output_tensor() {
+        let mut a = attr(&[1, 2, 2, 3], -10, 0.5);
+        let data: Vec<i8> = (0..12).collect();
+        let t = OutputTensor::from_i8(&a, &data);
+
+        assert_eq!(t.chw(), (2, 2, 3));
+        assert_eq!(t.at(1, 1, 2), (11.0 + 10.0) * 0.5);
+        assert_eq!(t.raw_threshold(1.0), -8.0);
+        assert_eq!(t.raw_threshold(1.2), -7.0);
+        assert_eq!(t.to_f32_vec()[3], 6.5);
+
+        a.fmt = rknpu2_sys::_rknn_tensor_format_RKNN_TENSOR_NHWC;
+        a.dims[..4].copy_from_slice(&[1, 2, 3, 2]);
+        let t = OutputTensor::from_i8(&a, &data);
+        assert_eq!(t.chw(), (2, 2, 3));
+        assert_eq!(t.index(1, 1, 2), 11);
+
+        // 3 channels packed by 2, the last one padding
+        a.fmt = rknpu2_sys::_rknn_tensor_format_RKNN_TENSOR_NC1HWC2;
+        a.n_dims = 5;
+        a.dims[..5].copy_from_slice(&[1, 2, 1, 3, 2]);
+        let t = OutputTensor::from_i8(&a, &data);
+        assert_eq!(t.chw(), (4, 1, 3));
+        assert_eq!(t.index(2, 0, 1), 8);
+        assert_eq!(t.index(1, 0, 2), 5);
+    }
+
+    #[test]
+    fn test_from_output_type() {
+        let mut a = attr(&[1, 2, 2, 3], 0, 1.0);
+        let mut data = [0i32; 12];
+        let mut output: RKNNOutput = unsafe { mem::zeroed() };
+        output.buf = data.as_mut_ptr().cast();
+        output.size = 12;
+
+        assert!(unsafe { OutputTensor::from_output(&a, &output) }.is_ok());
+        a.type_ = rknpu2_sys::_rknn_tensor_type_RKNN_TENSOR_INT32;
+        output.size = 48;
+        assert_eq!(
+            unsafe { OutputTensor::from_output(&a, &output) }.err(),
+            Some(rknpu2_sys::RKNN_ERR_PARAM_INVALID)
+        );
+        output.want_float = 1;
+        assert!(unsafe { OutputTensor::from_output(&a, &output) }.is_ok());
+    }
+
+    #[test]
+    fn test_from_output_size() {
+        let a = attr(&[1, 2, 2, 3], 0, 1.0);
+        let mut data = [0f32; 12];
+        let mut output: RKNNOutput = unsafe { mem::zeroed() };
+        output.buf = data.as_mut_ptr().cast();
+        output.size = 11;
+        assert_eq!(
+            unsafe { OutputTensor::from_output(&a, &output) }.err(),
+            Some(rknpu2_sys::RKNN_ERR_PARAM_INVALID)
+        );
+        // 12 floats are fine, 12 bytes are not
+        output.want_float = 1;
+        output.size = 12;
+        assert_eq!(
+            unsafe { OutputTensor::from_output(&a, &output) }.err(),
+            Some(rknpu2_sys::RKNN_ERR_PARAM_INVALID)
+        );
+        output.size = 48;
+        assert_eq!(
+            unsafe { OutputTensor::from_output(&a, &output) }
+                .unwrap()
+                .len(),
+            12
+        );
+
+        let attrs = [a, a];
+        assert_eq!(
+            unsafe { OutputTensor::from_outputs(&attrs, slice::from_ref(&output)) }.err(),
+            Some(rknpu2_sys::RKNN_ERR_PARAM_INVALID)
+        );
+        let outputs = [output, output];
+        assert!(unsafe { OutputTensor::from_outputs(&attrs, &outputs) }.is_ok());
+    }
+
+    #[test]
+    fn test_bbox_iou() {
+        let a = BBox::new(0.0, 0.0, 10.0, 10.0);
+        let b = BBox::new(5.0, 0.0, 15.0, 10.0);
+        assert!((a.iou(&b) - 1.0 / 3.0).abs() < 1e-6);
+        assert_eq!(a.iou(&BBox::new(20.0, 20.0, 30.0, 30.0)), 0.0);
+        // negative coordinates must not underflow
+        let c = BBox::new(-10.0, -10.0, 0.0, 0.0);
+        assert_eq!(c.iou(&BBox::new(-5.0, -10.0, 5.0, 0.0)), 1.0 / 3.0);
+    }
+}
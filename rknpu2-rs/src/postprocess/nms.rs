@@ -0,0 +1,349 @@
+//! Non maximum suppression.
+//!
+//! [`nms`] is the class aware hard NMS used by the decoders. [`nms_with`]
+//! adds class agnostic suppression, linear and Gaussian soft-NMS, and caps on
+//! the candidates and results. Hard NMS over many candidates is bucketed on a
+//! grid so each candidate is only compared with nearby kept boxes.
+
+use super::obb::OrientedDetection;
+use super::{BBox, Detection};
+
+/// Candidate count from which hard NMS uses the spatial grid.
+const GRID_MIN_CANDIDATES: usize = 512;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum NmsMethod {
+    /// Drop boxes overlapping a kept box by more than the threshold.
+    #[default]
+    Hard,
+    /// Scale the score of overlapping boxes by `1 - iou`.
+    Linear,
+    /// Scale the score of every box by `exp(-iou^2 / sigma)`.
+    Gaussian { sigma: f32 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NmsConfig {
+    pub iou_thresh: f32,
+    pub method: NmsMethod,
+    /// Suppress across classes.
+    pub class_agnostic: bool,
+    /// Soft-NMS drops boxes whose score decays below this.
+    pub score_thresh: f32,
+    /// Only consider the `top_k` highest scoring candidates.
+    pub top_k: Option<usize>,
+    pub max_detections: Option<usize>,
+}
+
+impl Default for NmsConfig {
+    fn default() -> Self {
+        NmsConfig {
+            iou_thresh: 0.45,
+            method: NmsMethod::Hard,
+            class_agnostic: false,
+            score_thresh: 0.001,
+            top_k: None,
+            max_detections: None,
+        }
+    }
+}
+
+/// Class aware hard NMS.
+/// Returns the indices of the kept detections, highest score first.
+pub fn nms(detections: &[Detection], iou_thresh: f32) -> Vec<usize> {
+    let config = NmsConfig {
+        iou_thresh,
+        ..Default::default()
+    };
+
+    hard_nms_indices(detections, &config)
+}
+
+/// NMS as described by `config`. Soft-NMS rescores the detections, so the
+/// kept detections are returned instead of indices, highest score first.
+pub fn nms_with(detections: &[Detection], config: &NmsConfig) -> Vec<Detection> {
+    match config.method {
+        NmsMethod::Hard => hard_nms_indices(detections, config)
+            .into_iter()
+            .map(|i| detections[i])
+            .collect(),
+        method => soft_nms(detections, method, config),
+    }
+}
+
+/// [`nms_with`] over each image of a batch.
+pub fn nms_batch(batch: &[Vec<Detection>], config: &NmsConfig) -> Vec<Vec<Detection>> {
+    batch.iter().map(|dets| nms_with(dets, config)).collect()
+}
+
+/// [`nms`] over rotated boxes.
+pub fn nms_rotated(detections: &[OrientedDetection], iou_thresh: f32) -> Vec<usize> {
+    hard_nms(
+        sorted_order(detections.len(), |i| detections[i].score),
+        None,
+        |i, j| {
+            detections[i].class_id == detections[j].class_id
+                && detections[i].rbox.iou(&detections[j].rbox) > iou_thresh
+        },
+    )
+}
+
+fn sorted_order(len: usize, score: impl Fn(usize) -> f32) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..len).collect();
+    order.sort_by(|&a, &b| score(b).total_cmp(&score(a)));
+    order
+}
+
+fn hard_nms_indices(detections: &[Detection], config: &NmsConfig) -> Vec<usize> {
+    let mut order = sorted_order(detections.len(), |i| detections[i].score);
+    if let Some(k) = config.top_k {
+        order.truncate(k);
+    }
+    let suppresses = |i: usize, j: usize| {
+        (config.class_agnostic || detections[i].class_id == detections[j].class_id)
+            && detections[i].bbox.iou(&detections[j].bbox) > config.iou_thresh
+    };
+
+    if order.len() >= GRID_MIN_CANDIDATES {
+        grid_nms(detections, order, config.max_detections, suppresses)
+    } else {
+        hard_nms(order, config.max_detections, suppresses)
+    }
+}
+
+/// Greedy NMS over `order`. A candidate is kept unless a kept candidate
+/// suppresses it.
+fn hard_nms(
+    order: Vec<usize>,
+    max_detections: Option<usize>,
+    suppresses: impl Fn(usize, usize) -> bool,
+) -> Vec<usize> {
+    let max = max_detections.unwrap_or(usize::MAX);
+    let mut keep: Vec<usize> = Vec::new();
+    for j in order {
+        if keep.len() >= max {
+            break;
+        }
+        if !keep.iter().any(|&i| suppresses(i, j)) {
+            keep.push(j);
+        }
+    }
+
+    keep
+}
+
+/// [`hard_nms`] comparing each candidate only with the kept boxes sharing a
+/// grid cell. Boxes that don't share a cell can't overlap.
+fn grid_nms(
+    detections: &[Detection],
+    order: Vec<usize>,
+    max_detections: Option<usize>,
+    suppresses: impl Fn(usize, usize) -> bool,
+) -> Vec<usize> {
+    let mut extent = BBox::new(f32::MAX, f32::MAX, f32::MIN, f32::MIN);
+    let mut size_sum = 0.0;
+    for &i in &order {
+        let b = &detections[i].bbox;
+        extent = BBox::new(
+            extent.x1.min(b.x1),
+            extent.y1.min(b.y1),
+            extent.x2.max(b.x2),
+            extent.y2.max(b.y2),
+        );
+        size_sum += b.width().max(b.height());
+    }
+    // cells about the size of an average box
+    let cell = (size_sum / order.len() as f32).max(1e-3);
+    let cols = ((extent.width() / cell).ceil() as usize).clamp(1, 256);
+    let rows = ((extent.height() / cell).ceil() as usize).clamp(1, 256);
+    let (cell_w, cell_h) = (extent.width() / cols as f32, extent.height() / rows as f32);
+    let cell_range = |b: &BBox| {
+        let col = |x: f32| (((x - extent.x1) / cell_w) as usize).min(cols - 1);
+        let row = |y: f32| (((y - extent.y1) / cell_h) as usize).min(rows - 1);
+        if cell_w > 0.0 && cell_h > 0.0 {
+            (col(b.x1), row(b.y1), col(b.x2), row(b.y2))
+        } else {
+            (0, 0, 0, 0)
+        }
+    };
+
+    let max = max_detections.unwrap_or(usize::MAX);
+    let mut cells: Vec<Vec<usize>> = vec![Vec::new(); cols * rows];
+    let mut keep = Vec::new();
+    for j in order {
+        if keep.len() >= max {
+            break;
+        }
+
+        let (c1, r1, c2, r2) = cell_range(&detections[j].bbox);
+        let suppressed = (r1..=r2)
+            .any(|r| (c1..=c2).any(|c| cells[r * cols + c].iter().any(|&i| suppresses(i, j))));
+        if suppressed {
+            continue;
+        }
+
+        keep.push(j);
+        for r in r1..=r2 {
+            for c in c1..=c2 {
+                cells[r * cols + c].push(j);
+            }
+        }
+    }
+
+    keep
+}
+
+fn soft_nms(detections: &[Detection], method: NmsMethod, config: &NmsConfig) -> Vec<Detection> {
+    let mut order = sorted_order(detections.len(), |i| detections[i].score);
+    if let Some(k) = config.top_k {
+        order.truncate(k);
+    }
+    let mut remaining: Vec<Detection> = order.into_iter().map(|i| detections[i]).collect();
+
+    let max = config.max_detections.unwrap_or(usize::MAX);
+    let mut keep = Vec::new();
+    while keep.len() < max {
+        let Some(best) = remaining
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.score.total_cmp(&b.1.score))
+            .map(|(i, _)| i)
+        else {
+            break;
+        };
+        let best = remaining.swap_remove(best);
+        if best.score < config.score_thresh {
+            break;
+        }
+        keep.push(best);
+
+        for d in remaining.iter_mut() {
+            if !config.class_agnostic && d.class_id != best.class_id {
+                continue;
+            }
+            let iou = best.bbox.iou(&d.bbox);
+            d.score *= match method {
+                NmsMethod::Linear if iou > config.iou_thresh => 1.0 - iou,
+                NmsMethod::Gaussian { sigma } => (-iou * iou / sigma).exp(),
+                _ => 1.0,
+            };
+        }
+        remaining.retain(|d| d.score >= config.score_thresh);
+    }
+
+    keep
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::postprocess::BBox;
+
+    fn det(class_id: usize, score: f32, x1: f32) -> Detection {
+        Detection {
+            class_id,
+            score,
+            bbox: BBox::new(x1, 0.0, x1 + 10.0, 10.0),
+        }
+    }
+
+    #[test]
+    fn test_nms() {
+        let dets = [
+            det(0, 0.5, 1.0),
+            det(0, 0.9, 0.0),
+            det(1, 0.8, 0.0),
+            det(0, 0.7, 50.0),
+        ];
+        assert_eq!(nms(&dets, 0.5), vec![1, 2, 3]);
+        assert_eq!(nms(&dets, 0.95), vec![1, 2, 3, 0]);
+    }
+
+    #[test]
+    fn test_nms_modes() {
+        let dets = [
+            det(0, 0.5, 1.0),
+            det(0, 0.9, 0.0),
+            det(1, 0.8, 0.0),
+            det(0, 0.7, 50.0),
+        ];
+        let scores = |v: Vec<Detection>| v.iter().map(|d| d.score).collect::<Vec<_>>();
+
+        let agnostic = NmsConfig {
+            iou_thresh: 0.5,
+            class_agnostic: true,
+            ..Default::default()
+        };
+        assert_eq!(scores(nms_with(&dets, &agnostic)), vec![0.9, 0.7]);
+
+        let capped = NmsConfig {
+            iou_thresh: 0.5,
+            max_detections: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(scores(nms_with(&dets, &capped)), vec![0.9, 0.8]);
+        let top_k = NmsConfig {
+            top_k: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(scores(nms_with(&dets, &top_k)), vec![0.9]);
+
+        // iou of the overlapping pair is 9 / 11
+        let iou = 9.0 / 11.0;
+        let linear = NmsConfig {
+            iou_thresh: 0.5,
+            method: NmsMethod::Linear,
+            ..Default::default()
+        };
+        let kept = nms_with(&dets, &linear);
+        assert_eq!(kept.len(), 4);
+        assert!((kept[3].score - 0.5 * (1.0 - iou)).abs() < 1e-6);
+
+        let gaussian = NmsConfig {
+            method: NmsMethod::Gaussian { sigma: 0.5 },
+            score_thresh: 0.2,
+            ..Default::default()
+        };
+        let kept = nms_with(&dets, &gaussian);
+        assert_eq!(kept.len(), 3);
+        let decayed = 0.5 * (-iou * iou / 0.5f32).exp();
+        assert!(decayed < 0.2);
+    }
+
+    #[test]
+    fn test_grid_nms_matches_greedy() {
+        // deterministic pseudo random boxes, some far outside the others
+        let mut seed = 12345u32;
+        let mut next = || {
+            seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+            (seed >> 8) as f32 / (1 << 24) as f32
+        };
+        let dets: Vec<Detection> = (0..3000)
+            .map(|i| {
+                let (x, y) = (next() * 600.0 - 20.0, next() * 400.0);
+                let (w, h) = (5.0 + next() * 60.0, 5.0 + next() * 60.0);
+                Detection {
+                    class_id: i % 3,
+                    score: next(),
+                    bbox: BBox::new(x, y, x + w, y + h),
+                }
+            })
+            .collect();
+
+        for class_agnostic in [false, true] {
+            let config = NmsConfig {
+                class_agnostic,
+                ..Default::default()
+            };
+            let suppresses = |i: usize, j: usize| {
+                (class_agnostic || dets[i].class_id == dets[j].class_id)
+                    && dets[i].bbox.iou(&dets[j].bbox) > config.iou_thresh
+            };
+            let order = sorted_order(dets.len(), |i| dets[i].score);
+            assert_eq!(
+                hard_nms_indices(&dets, &config),
+                hard_nms(order, None, suppresses)
+            );
+        }
+    }
+}
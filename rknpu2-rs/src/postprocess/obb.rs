@@ -0,0 +1,436 @@
+//! Oriented bounding boxes, for YOLOv8-obb heads.
+//!
+//! The head has the usual anchor-free box and score outputs plus one angle
+//! channel per anchor, either as a single `[1, 1, N]` output over all
+//! anchors or as a further output on each branch grid, see [`AngleOutput`].
+//! Box distances are measured along the rotated axes.
+
+use std::f32::consts::PI;
+
+use super::nms::nms_rotated;
+use super::yolo::{dfl, find_branches, YoloConfig};
+use super::{sigmoid, BBox, OutputTensor};
+use crate::preprocess::input_size;
+use crate::preprocess::letterbox::LetterboxTransform;
+use crate::{RKNNContextPack, RKNNOutput};
+
+/// Where the head keeps its angles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AngleOutput {
+    /// One `[1, 1, N]` output over the anchors of all branches.
+    #[default]
+    Global,
+    /// The last output on each branch grid, after the box and scores.
+    Branch,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ObbConfig {
+    pub yolo: YoloConfig,
+    pub angle: AngleOutput,
+    /// The angle output is a logit decoded as `(sigmoid(x) - 0.25) * PI`,
+    /// otherwise it is already in radians.
+    pub angle_logit: bool,
+}
+
+impl Default for ObbConfig {
+    fn default() -> Self {
+        ObbConfig {
+            yolo: YoloConfig::default(),
+            angle: AngleOutput::default(),
+            angle_logit: true,
+        }
+    }
+}
+
+/// A box of size `w` x `h` centered on `(cx, cy)`, rotated clockwise by
+/// `angle` radians in image coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct RotatedBox {
+    pub cx: f32,
+    pub cy: f32,
+    pub w: f32,
+    pub h: f32,
+    pub angle: f32,
+}
+
+impl RotatedBox {
+    pub fn new(cx: f32, cy: f32, w: f32, h: f32, angle: f32) -> Self {
+        RotatedBox {
+            cx,
+            cy,
+            w,
+            h,
+            angle,
+        }
+    }
+
+    pub fn area(&self) -> f32 {
+        self.w.max(0.0) * self.h.max(0.0)
+    }
+
+    /// The four corners, in a consistent winding order.
+    pub fn corners(&self) -> [(f32, f32); 4] {
+        let (sin, cos) = self.angle.sin_cos();
+        let (ux, uy) = (self.w / 2.0 * cos, self.w / 2.0 * sin);
+        let (vx, vy) = (-self.h / 2.0 * sin, self.h / 2.0 * cos);
+
+        [
+            (self.cx - ux - vx, self.cy - uy - vy),
+            (self.cx + ux - vx, self.cy + uy - vy),
+            (self.cx + ux + vx, self.cy + uy + vy),
+            (self.cx - ux + vx, self.cy - uy + vy),
+        ]
+    }
+
+    /// The axis aligned box enclosing the corners.
+    pub fn bounding_box(&self) -> BBox {
+        let corners = self.corners();
+        let xs = corners.iter().map(|p| p.0);
+        let ys = corners.iter().map(|p| p.1);
+
+        BBox::new(
+            xs.clone().fold(f32::MAX, f32::min),
+            ys.clone().fold(f32::MAX, f32::min),
+            xs.fold(f32::MIN, f32::max),
+            ys.fold(f32::MIN, f32::max),
+        )
+    }
+
+    /// Intersection over union, from the polygon intersection of both boxes.
+    pub fn iou(&self, other: &RotatedBox) -> f32 {
+        let (a, b) = (self.area(), other.area());
+        if a <= 0.0 || b <= 0.0 {
+            return 0.0;
+        }
+        // cheap reject
+        if self.bounding_box().iou(&other.bounding_box()) <= 0.0 {
+            return 0.0;
+        }
+
+        let inter = polygon_area(&clip(&self.corners(), &other.corners()));
+        let union = a + b - inter;
+        if union <= 0.0 {
+            0.0
+        } else {
+            inter / union
+        }
+    }
+
+    /// Map from model input to source image coordinates. Assumes the
+    /// letterbox keeps the aspect ratio.
+    pub fn to_source(&self, transform: &LetterboxTransform) -> RotatedBox {
+        let (cx, cy) = transform.to_source(self.cx, self.cy);
+        RotatedBox {
+            cx,
+            cy,
+            w: self.w / transform.scale_x,
+            h: self.h / transform.scale_y,
+            angle: self.angle,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OrientedDetection {
+    pub class_id: usize,
+    pub score: f32,
+    pub rbox: RotatedBox,
+}
+
+impl OrientedDetection {
+    pub fn to_source(&self, transform: &LetterboxTransform) -> OrientedDetection {
+        OrientedDetection {
+            rbox: self.rbox.to_source(transform),
+            ..*self
+        }
+    }
+}
+
+/// Decode oriented boxes and apply rotated NMS. Boxes are in model input
+/// coordinates.
+///
+/// Fails with `RKNN_ERR_PARAM_INVALID` if the model has no angle output
+/// where `config.angle` says.
+pub fn detect(
+    tensors: &[OutputTensor],
+    input_h: usize,
+    input_w: usize,
+    config: &ObbConfig,
+) -> Result<Vec<OrientedDetection>, i32> {
+    let branches = find_branches(tensors, input_h, input_w);
+    let total: usize = branches.iter().map(|b| b.grid_h * b.grid_w).sum();
+    let global_angle = match config.angle {
+        AngleOutput::Global => Some(
+            tensors
+                .iter()
+                .find(|t| t.attr.n_elems as usize == total && t.chw().0 == 1 && t.dims().len() == 3)
+                .ok_or(rknpu2_sys::RKNN_ERR_PARAM_INVALID)?,
+        ),
+        AngleOutput::Branch => None,
+    };
+
+    let mut candidates = Vec::new();
+    for branch in &branches {
+        let boxes = &tensors[branch.boxes];
+        let scores = &tensors[branch.scores];
+        let branch_angle = match config.angle {
+            // a one channel output right after the scores is taken for a
+            // score sum by find_branches
+            AngleOutput::Branch => Some(
+                branch
+                    .extra
+                    .last()
+                    .copied()
+                    .or(branch.score_sum)
+                    .map(|i| &tensors[i])
+                    .ok_or(rknpu2_sys::RKNN_ERR_PARAM_INVALID)?,
+            ),
+            AngleOutput::Global => None,
+        };
+
+        let class_num = scores.chw().0;
+        let dfl_len = boxes.chw().0 / 4;
+        let score_thresh = scores.raw_threshold(config.yolo.conf_thresh);
+        let mut bins = vec![0f32; dfl_len];
+
+        for y in 0..branch.grid_h {
+            for x in 0..branch.grid_w {
+                let mut max_raw = f32::MIN;
+                let mut max_class = None;
+                for class_id in 0..class_num {
+                    let v = scores.raw(scores.index(class_id, y, x));
+                    if v > max_raw {
+                        max_raw = v;
+                        max_class = Some(class_id);
+                    }
+                }
+                let Some(class_id) = max_class.filter(|_| max_raw >= score_thresh) else {
+                    continue;
+                };
+                let score = scores.at(class_id, y, x);
+                if score < config.yolo.conf_thresh {
+                    continue;
+                }
+
+                let angle = match branch_angle {
+                    Some(t) => t.at(0, y, x),
+                    None => global_angle
+                        .unwrap()
+                        .get(branch.anchor_offset + y * branch.grid_w + x),
+                };
+                let angle = if config.angle_logit {
+                    (sigmoid(angle) - 0.25) * PI
+                } else {
+                    angle
+                };
+
+                let mut dist = [0f32; 4];
+                for (side, d) in dist.iter_mut().enumerate() {
+                    *d = if dfl_len == 1 {
+                        boxes.at(side, y, x)
+                    } else {
+                        for (k, b) in bins.iter_mut().enumerate() {
+                            *b = boxes.at(side * dfl_len + k, y, x);
+                        }
+                        dfl(&bins)
+                    };
+                }
+
+                // center offset along the rotated axes
+                let (sin, cos) = angle.sin_cos();
+                let (ox, oy) = ((dist[2] - dist[0]) / 2.0, (dist[3] - dist[1]) / 2.0);
+                let cx = x as f32 + 0.5 + ox * cos - oy * sin;
+                let cy = y as f32 + 0.5 + ox * sin + oy * cos;
+
+                candidates.push(OrientedDetection {
+                    class_id,
+                    score,
+                    rbox: RotatedBox::new(
+                        cx * branch.stride_x,
+                        cy * branch.stride_y,
+                        (dist[0] + dist[2]) * branch.stride_x,
+                        (dist[1] + dist[3]) * branch.stride_y,
+                        angle,
+                    ),
+                });
+            }
+        }
+    }
+
+    Ok(nms_rotated(&candidates, config.yolo.iou_thresh)
+        .into_iter()
+        .map(|i| candidates[i])
+        .collect())
+}
+
+/// [`detect`] on the outputs of `rknn_outputs_get`.
+pub fn post_process(
+    ctx: &RKNNContextPack,
+    outputs: &[RKNNOutput],
+    config: &ObbConfig,
+) -> Result<Vec<OrientedDetection>, i32> {
+    let (input_h, input_w, _) = input_size(ctx.input_info.first().unwrap());
+    // outputs come straight from rknn_outputs_get and match output_info
+    let tensors = unsafe { OutputTensor::from_outputs(&ctx.output_info, outputs)? };
+
+    detect(&tensors, input_h, input_w, config)
+}
+
+fn cross(o: (f32, f32), a: (f32, f32), b: (f32, f32)) -> f32 {
+    (a.0 - o.0) * (b.1 - o.1) - (a.1 - o.1) * (b.0 - o.0)
+}
+
+/// Sutherland-Hodgman clipping of `subject` by the convex `clip_poly`, both
+/// wound like [`RotatedBox::corners`].
+fn clip(subject: &[(f32, f32)], clip_poly: &[(f32, f32)]) -> Vec<(f32, f32)> {
+    let mut output = subject.to_vec();
+    for i in 0..clip_poly.len() {
+        let (a, b) = (clip_poly[i], clip_poly[(i + 1) % clip_poly.len()]);
+        let input = std::mem::take(&mut output);
+        if input.is_empty() {
+            break;
+        }
+
+        for j in 0..input.len() {
+            let (p, q) = (input[j], input[(j + 1) % input.len()]);
+            let (dp, dq) = (cross(a, b, p), cross(a, b, q));
+            if dp >= 0.0 {
+                output.push(p);
+            }
+            if (dp >= 0.0) != (dq >= 0.0) {
+                let t = dp / (dp - dq);
+                output.push((p.0 + (q.0 - p.0) * t, p.1 + (q.1 - p.1) * t));
+            }
+        }
+    }
+
+    output
+}
+
+fn polygon_area(poly: &[(f32, f32)]) -> f32 {
+    let mut sum = 0.0;
+    for i in 0..poly.len() {
+        let (p, q) = (poly[i], poly[(i + 1) % poly.len()]);
+        sum += p.0 * q.1 - q.0 * p.1;
+    }
+
+    (sum / 2.0).abs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::postprocess::tests::attr;
+    use std::f32::consts::FRAC_PI_4;
+
+    #[test]
+    fn test_rotated_iou() {
+        let a = RotatedBox::new(0.0, 0.0, 2.0, 2.0, 0.0);
+        assert!((a.iou(&a) - 1.0).abs() < 1e-6);
+        // axis aligned boxes agree with BBox
+        let b = RotatedBox::new(1.0, 0.0, 2.0, 2.0, 0.0);
+        assert!((a.iou(&b) - 1.0 / 3.0).abs() < 1e-6);
+        // a square and itself rotated by 45 degrees overlap in a regular octagon
+        let r = RotatedBox::new(0.0, 0.0, 2.0, 2.0, FRAC_PI_4);
+        assert!((a.iou(&r) - 0.5f32.sqrt()).abs() < 1e-5);
+        // a 90 degree rotation swaps width and height
+        let c = RotatedBox::new(0.0, 0.0, 4.0, 2.0, 0.0);
+        let d = RotatedBox::new(0.0, 0.0, 2.0, 4.0, PI / 2.0);
+        assert!((c.iou(&d) - 1.0).abs() < 1e-5);
+        // a thin cross
+        let e = RotatedBox::new(0.0, 0.0, 10.0, 1.0, 0.0);
+        let f = RotatedBox::new(0.0, 0.0, 10.0, 1.0, PI / 2.0);
+        assert!((e.iou(&f) - 1.0 / 19.0).abs() < 1e-5);
+        assert_eq!(a.iou(&RotatedBox::new(10.0, 10.0, 2.0, 2.0, 0.3)), 0.0);
+    }
+
+    #[test]
+    fn test_bounding_box() {
+        let r = RotatedBox::new(0.0, 0.0, 2.0, 2.0, FRAC_PI_4);
+        let b = r.bounding_box();
+        assert!((b.x2 - 2f32.sqrt()).abs() < 1e-5);
+        assert!((b.y1 + 2f32.sqrt()).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_detect_obb() {
+        // 16x16 input, one 2x2 branch with direct distances, one class and
+        // a branch angle output
+        let attrs = [
+            attr(&[1, 4, 2, 2], 0, 1.0),
+            attr(&[1, 1, 2, 2], 0, 1.0),
+            attr(&[1, 1, 2, 2], 0, 1.0),
+        ];
+        let mut boxes = vec![0f32; 16];
+        // anchor (1, 0): left 0.5, right 1.5, top and bottom 0.25
+        for (side, d) in [0.5, 0.25, 1.5, 0.25].into_iter().enumerate() {
+            boxes[side * 4 + 1] = d;
+        }
+        let scores = vec![0.0, 0.8, 0.0, 0.0];
+        let angles = vec![0.0, PI / 2.0, 0.0, 0.0];
+        let tensors = [
+            OutputTensor::from_f32(&attrs[0], &boxes),
+            OutputTensor::from_f32(&attrs[1], &scores),
+            OutputTensor::from_f32(&attrs[2], &angles),
+        ];
+        let config = ObbConfig {
+            angle: AngleOutput::Branch,
+            angle_logit: false,
+            ..Default::default()
+        };
+
+        let dets = detect(&tensors, 16, 16, &config).unwrap();
+        assert_eq!(dets.len(), 1);
+        let r = dets[0].rbox;
+        // the box extends 0.5 cells further along its rotated x axis, which
+        // points down the image
+        assert!((r.cx - 12.0).abs() < 1e-5);
+        assert!((r.cy - 8.0).abs() < 1e-5);
+        assert_eq!((r.w, r.h), (16.0, 4.0));
+        assert_eq!(r.angle, PI / 2.0);
+
+        // the same outputs with a global angle instead
+        let global_attr = attr(&[1, 1, 4], 0, 1.0);
+        let global = [
+            tensors[0],
+            tensors[1],
+            OutputTensor::from_f32(&global_attr, &angles),
+        ];
+        let config = ObbConfig {
+            angle_logit: false,
+            ..Default::default()
+        };
+        assert_eq!(detect(&global, 16, 16, &config).unwrap(), dets);
+    }
+
+    #[test]
+    fn test_detect_obb_no_angle() {
+        // a plain YOLOv8 detection head: box, score and score sum
+        let attrs = [
+            attr(&[1, 4, 2, 2], 0, 1.0),
+            attr(&[1, 1, 2, 2], 0, 1.0),
+            attr(&[1, 1, 2, 2], 0, 1.0),
+        ];
+        let boxes = vec![0.5f32; 16];
+        let scores = vec![0.0, 0.8, 0.0, 0.0];
+        let tensors = [
+            OutputTensor::from_f32(&attrs[0], &boxes),
+            OutputTensor::from_f32(&attrs[1], &scores),
+            OutputTensor::from_f32(&attrs[2], &scores),
+        ];
+
+        assert_eq!(
+            detect(&tensors, 16, 16, &ObbConfig::default()),
+            Err(rknpu2_sys::RKNN_ERR_PARAM_INVALID)
+        );
+        let branch = ObbConfig {
+            angle: AngleOutput::Branch,
+            ..Default::default()
+        };
+        assert_eq!(
+            detect(&tensors[..2], 16, 16, &branch),
+            Err(rknpu2_sys::RKNN_ERR_PARAM_INVALID)
+        );
+    }
+}
@@ -0,0 +1,234 @@
+//! CTC decoding of text recognizer outputs.
+//!
+//! The recognizer outputs `[1, T, C]` per step class probabilities, class 0
+//! being the CTC blank and class `i` the `i - 1`th dictionary character.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::postprocess::OutputTensor;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextLine {
+    pub text: String,
+    /// Mean probability of the decoded characters for greedy decoding, the
+    /// per step geometric mean of the sequence probability for beam search.
+    pub score: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CtcDecoder {
+    pub chars: Vec<String>,
+}
+
+impl CtcDecoder {
+    pub fn new(chars: Vec<String>) -> Self {
+        CtcDecoder { chars }
+    }
+
+    /// A PaddleOCR character dictionary, one character per line. Only line
+    /// endings are stripped since a character may be whitespace. With
+    /// `use_space_char` a space is appended, as in the PaddleOCR configs.
+    pub fn from_dict(text: &str, use_space_char: bool) -> Self {
+        let mut chars: Vec<String> = text
+            .split('\n')
+            .map(|l| l.strip_suffix('\r').unwrap_or(l).to_string())
+            .collect();
+        while chars.last().is_some_and(|c| c.is_empty()) {
+            chars.pop();
+        }
+        if use_space_char {
+            chars.push(" ".to_string());
+        }
+
+        CtcDecoder { chars }
+    }
+
+    pub fn from_dict_file<P: AsRef<Path>>(path: P, use_space_char: bool) -> io::Result<Self> {
+        Ok(Self::from_dict(&fs::read_to_string(path)?, use_space_char))
+    }
+
+    fn text(&self, classes: &[usize]) -> String {
+        classes
+            .iter()
+            .filter_map(|&c| self.chars.get(c - 1).map(String::as_str))
+            .collect()
+    }
+
+    /// Best class per step, repeats merged and blanks dropped. An output
+    /// without classes decodes to an empty line.
+    pub fn decode_greedy(&self, output: &OutputTensor) -> TextLine {
+        let (steps, classes) = steps_classes(output);
+        let probs = output.to_f32_vec();
+
+        let mut kept = Vec::new();
+        let mut scores = Vec::new();
+        let mut prev = 0;
+        for t in 0..steps {
+            let row = &probs[t * classes..(t + 1) * classes];
+            let Some((best, &p)) = row.iter().enumerate().max_by(|a, b| a.1.total_cmp(b.1)) else {
+                break;
+            };
+            if best != 0 && best != prev {
+                kept.push(best);
+                scores.push(p);
+            }
+            prev = best;
+        }
+
+        TextLine {
+            text: self.text(&kept),
+            score: if scores.is_empty() {
+                0.0
+            } else {
+                scores.iter().sum::<f32>() / scores.len() as f32
+            },
+        }
+    }
+
+    /// CTC prefix beam search keeping `beam_width` prefixes per step. An
+    /// output without classes decodes to an empty line.
+    pub fn decode_beam(&self, output: &OutputTensor, beam_width: usize) -> TextLine {
+        let (steps, classes) = steps_classes(output);
+        if classes == 0 {
+            return TextLine {
+                text: String::new(),
+                score: 0.0,
+            };
+        }
+        let probs = output.to_f32_vec();
+        let beam_width = beam_width.max(1);
+
+        // prefix -> (log p ending in blank, log p ending in a character)
+        let mut beams: Vec<(Vec<usize>, (f32, f32))> = vec![(Vec::new(), (0.0, f32::NEG_INFINITY))];
+        for t in 0..steps {
+            let row = &probs[t * classes..(t + 1) * classes];
+            let log_p = |c: usize| row[c].max(f32::MIN_POSITIVE).ln();
+            // only extend with the most likely characters of this step
+            let mut candidates: Vec<usize> = (1..classes).collect();
+            candidates.sort_by(|&a, &b| row[b].total_cmp(&row[a]));
+            candidates.truncate(beam_width);
+
+            let mut next: HashMap<Vec<usize>, (f32, f32)> = HashMap::new();
+            for (prefix, (pb, pnb)) in &beams {
+                let total = log_add(*pb, *pnb);
+                let entry = next
+                    .entry(prefix.clone())
+                    .or_insert((f32::NEG_INFINITY, f32::NEG_INFINITY));
+                entry.0 = log_add(entry.0, total + log_p(0));
+                if let Some(&last) = prefix.last() {
+                    entry.1 = log_add(entry.1, pnb + log_p(last));
+                }
+
+                for &c in &candidates {
+                    let mut extended = prefix.clone();
+                    extended.push(c);
+                    // a repeated character needs a blank in between
+                    let from = if prefix.last() == Some(&c) {
+                        *pb
+                    } else {
+                        total
+                    };
+                    let entry = next
+                        .entry(extended)
+                        .or_insert((f32::NEG_INFINITY, f32::NEG_INFINITY));
+                    entry.1 = log_add(entry.1, from + log_p(c));
+                }
+            }
+
+            beams = next.into_iter().collect();
+            beams.sort_by(|a, b| {
+                log_add(b.1 .0, b.1 .1)
+                    .total_cmp(&log_add(a.1 .0, a.1 .1))
+                    .then_with(|| a.0.cmp(&b.0))
+            });
+            beams.truncate(beam_width);
+        }
+
+        let (prefix, (pb, pnb)) = &beams[0];
+        TextLine {
+            text: self.text(prefix),
+            score: (log_add(*pb, *pnb) / steps.max(1) as f32).exp(),
+        }
+    }
+}
+
+fn steps_classes(output: &OutputTensor) -> (usize, usize) {
+    let dims = output.dims();
+    match dims.len() {
+        3 => (dims[1] as usize, dims[2] as usize),
+        4 => (dims[1] as usize * dims[2] as usize, dims[3] as usize),
+        _ => {
+            let d = |i: usize| dims.get(i).copied().unwrap_or(0) as usize;
+            (d(0), d(1))
+        }
+    }
+}
+
+fn log_add(a: f32, b: f32) -> f32 {
+    if a == f32::NEG_INFINITY {
+        return b;
+    }
+    if b == f32::NEG_INFINITY {
+        return a;
+    }
+    let max = a.max(b);
+
+    max + ((a - max).exp() + (b - max).exp()).ln()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::postprocess::tests::attr;
+
+    #[test]
+    fn test_ctc_decode() {
+        let decoder = CtcDecoder::from_dict("a\r\nb\n", true);
+        assert_eq!(decoder.chars, vec!["a", "b", " "]);
+
+        // a a - a b, blank is class 0
+        let a = attr(&[1, 5, 4], 0, 1.0);
+        let probs = vec![
+            0.05, 0.9, 0.05, 0.0, //
+            0.1, 0.8, 0.1, 0.0, //
+            0.9, 0.05, 0.05, 0.0, //
+            0.1, 0.9, 0.0, 0.0, //
+            0.05, 0.05, 0.9, 0.0, //
+        ];
+        let output = OutputTensor::from_f32(&a, &probs);
+
+        let greedy = decoder.decode_greedy(&output);
+        assert_eq!(greedy.text, "aab");
+        assert!((greedy.score - (0.9 + 0.9 + 0.9) / 3.0).abs() < 1e-6);
+
+        let beam = decoder.decode_beam(&output, 4);
+        assert_eq!(beam.text, "aab");
+        assert!(beam.score > 0.0 && beam.score <= 1.0);
+
+        // no classes at all
+        let empty = attr(&[1, 2, 0], 0, 1.0);
+        let output = OutputTensor::from_f32(&empty, &[]);
+        let line = decoder.decode_greedy(&output);
+        assert_eq!((line.text.as_str(), line.score), ("", 0.0));
+        assert_eq!(decoder.decode_beam(&output, 4), line);
+    }
+
+    #[test]
+    fn test_beam_beats_greedy() {
+        // greedy picks blank at both steps, but "a" is more likely overall:
+        // p("") = 0.36, p("a") = 0.4 * 0.6 * 2 + 0.4 * 0.4 = 0.64
+        let decoder = CtcDecoder::new(vec!["a".to_string()]);
+        let a = attr(&[1, 2, 2], 0, 1.0);
+        let probs = vec![0.6, 0.4, 0.6, 0.4];
+        let output = OutputTensor::from_f32(&a, &probs);
+
+        assert_eq!(decoder.decode_greedy(&output).text, "");
+
+        let beam = decoder.decode_beam(&output, 2);
+        assert_eq!(beam.text, "a");
+        assert!((beam.score - 0.64f32.sqrt()).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,336 @@
+//! DB (differentiable binarization) text detection postprocessing.
+//!
+//! The probability map is binarized, connected regions are fitted with a
+//! minimum area rectangle, scored by their mean probability and grown by the
+//! unclip distance `area * unclip_ratio / perimeter`.
+
+use std::collections::{BTreeMap, VecDeque};
+
+use crate::postprocess::obb::RotatedBox;
+use crate::postprocess::OutputTensor;
+use crate::preprocess::letterbox::LetterboxTransform;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DbConfig {
+    /// Binarization threshold of the probability map.
+    pub thresh: f32,
+    /// Minimum mean probability of a region.
+    pub box_thresh: f32,
+    pub unclip_ratio: f32,
+    /// Minimum short side of a box before unclipping, in map pixels.
+    pub min_size: f32,
+    pub max_candidates: usize,
+}
+
+impl Default for DbConfig {
+    fn default() -> Self {
+        DbConfig {
+            thresh: 0.3,
+            box_thresh: 0.6,
+            unclip_ratio: 1.5,
+            min_size: 3.0,
+            max_candidates: 1000,
+        }
+    }
+}
+
+/// A text region as a quadrilateral, top-left, top-right, bottom-right,
+/// bottom-left.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextBox {
+    pub points: [(f32, f32); 4],
+    pub score: f32,
+}
+
+impl TextBox {
+    /// Map from model input to source image coordinates.
+    pub fn to_source(&self, transform: &LetterboxTransform) -> TextBox {
+        TextBox {
+            points: self.points.map(|(x, y)| transform.to_source(x, y)),
+            score: self.score,
+        }
+    }
+
+    pub fn width(&self) -> f32 {
+        dist(self.points[0], self.points[1]).max(dist(self.points[3], self.points[2]))
+    }
+
+    pub fn height(&self) -> f32 {
+        dist(self.points[0], self.points[3]).max(dist(self.points[1], self.points[2]))
+    }
+}
+
+fn dist(a: (f32, f32), b: (f32, f32)) -> f32 {
+    (a.0 - b.0).hypot(a.1 - b.1)
+}
+
+/// Text boxes of a `[1, 1, H, W]` probability map, in map coordinates,
+/// sorted top to bottom then left to right.
+pub fn detect(prob: &OutputTensor, config: &DbConfig) -> Vec<TextBox> {
+    let (_, h, w) = prob.chw();
+    let probs: Vec<f32> = {
+        let values = prob.to_f32_vec();
+        (0..h * w)
+            .map(|i| values[prob.index(0, i / w, i % w)])
+            .collect()
+    };
+
+    let mut boxes = Vec::new();
+    for region in regions(&probs, h, w, config.thresh) {
+        if boxes.len() >= config.max_candidates {
+            break;
+        }
+
+        let rect = min_area_rect(&convex_hull(region.outline));
+        if rect.w.min(rect.h) < config.min_size {
+            continue;
+        }
+        if region.score < config.box_thresh {
+            continue;
+        }
+
+        let rect = unclip(rect, config.unclip_ratio);
+        if rect.w.min(rect.h) < config.min_size + 2.0 {
+            continue;
+        }
+
+        boxes.push(TextBox {
+            points: order_points(rect.corners()),
+            score: region.score,
+        });
+    }
+    boxes.sort_by(|a, b| {
+        let (a, b) = (a.points[0], b.points[0]);
+        a.1.total_cmp(&b.1).then(a.0.total_cmp(&b.0))
+    });
+
+    boxes
+}
+
+struct Region {
+    /// Leftmost and rightmost pixel of every row.
+    outline: Vec<(f32, f32)>,
+    /// Mean probability.
+    score: f32,
+}
+
+/// 8-connected regions above `thresh`.
+fn regions(probs: &[f32], h: usize, w: usize, thresh: f32) -> Vec<Region> {
+    let mut visited = vec![false; h * w];
+    let mut regions = Vec::new();
+    let mut queue = VecDeque::new();
+
+    for start in 0..h * w {
+        if visited[start] || probs[start] <= thresh {
+            continue;
+        }
+        visited[start] = true;
+        queue.push_back(start);
+
+        // row -> (min x, max x)
+        let mut rows: BTreeMap<usize, (usize, usize)> = BTreeMap::new();
+        let (mut sum, mut count) = (0.0, 0usize);
+        while let Some(i) = queue.pop_front() {
+            let (y, x) = (i / w, i % w);
+            sum += probs[i];
+            count += 1;
+            let row = rows.entry(y).or_insert((x, x));
+            *row = (row.0.min(x), row.1.max(x));
+
+            for dy in -1isize..=1 {
+                for dx in -1isize..=1 {
+                    let (ny, nx) = (y as isize + dy, x as isize + dx);
+                    if ny < 0 || nx < 0 || ny >= h as isize || nx >= w as isize {
+                        continue;
+                    }
+                    let j = ny as usize * w + nx as usize;
+                    if !visited[j] && probs[j] > thresh {
+                        visited[j] = true;
+                        queue.push_back(j);
+                    }
+                }
+            }
+        }
+
+        regions.push(Region {
+            outline: rows
+                .iter()
+                .flat_map(|(&y, &(x1, x2))| [(x1 as f32, y as f32), (x2 as f32, y as f32)])
+                .collect(),
+            score: sum / count as f32,
+        });
+    }
+
+    regions
+}
+
+fn cross(o: (f32, f32), a: (f32, f32), b: (f32, f32)) -> f32 {
+    (a.0 - o.0) * (b.1 - o.1) - (a.1 - o.1) * (b.0 - o.0)
+}
+
+/// Monotone chain convex hull.
+fn convex_hull(mut points: Vec<(f32, f32)>) -> Vec<(f32, f32)> {
+    points.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1)));
+    points.dedup();
+    if points.len() < 3 {
+        return points;
+    }
+
+    let mut lower = half_hull(points.iter());
+    let mut upper = half_hull(points.iter().rev());
+    lower.pop();
+    upper.pop();
+    lower.extend(upper);
+
+    lower
+}
+
+fn half_hull<'a>(points: impl Iterator<Item = &'a (f32, f32)>) -> Vec<(f32, f32)> {
+    let mut hull: Vec<(f32, f32)> = Vec::new();
+    for &p in points {
+        while hull.len() >= 2 && cross(hull[hull.len() - 2], hull[hull.len() - 1], p) <= 0.0 {
+            hull.pop();
+        }
+        hull.push(p);
+    }
+
+    hull
+}
+
+/// Minimum area enclosing rectangle of a convex hull, by trying every hull
+/// edge direction.
+fn min_area_rect(hull: &[(f32, f32)]) -> RotatedBox {
+    let mut best = match hull.first() {
+        Some(&(x, y)) => RotatedBox::new(x, y, 0.0, 0.0, 0.0),
+        None => return RotatedBox::default(),
+    };
+    let mut best_area = f32::MAX;
+
+    for i in 0..hull.len() {
+        let (a, b) = (hull[i], hull[(i + 1) % hull.len()]);
+        let len = dist(a, b);
+        if len == 0.0 {
+            continue;
+        }
+        let u = ((b.0 - a.0) / len, (b.1 - a.1) / len);
+
+        let (mut min_u, mut max_u, mut min_v, mut max_v) = (f32::MAX, f32::MIN, f32::MAX, f32::MIN);
+        for p in hull {
+            let pu = p.0 * u.0 + p.1 * u.1;
+            let pv = -p.0 * u.1 + p.1 * u.0;
+            min_u = min_u.min(pu);
+            max_u = max_u.max(pu);
+            min_v = min_v.min(pv);
+            max_v = max_v.max(pv);
+        }
+
+        let area = (max_u - min_u) * (max_v - min_v);
+        if area < best_area {
+            best_area = area;
+            let (cu, cv) = ((min_u + max_u) / 2.0, (min_v + max_v) / 2.0);
+            best = RotatedBox::new(
+                cu * u.0 - cv * u.1,
+                cu * u.1 + cv * u.0,
+                max_u - min_u,
+                max_v - min_v,
+                u.1.atan2(u.0),
+            );
+        }
+    }
+
+    best
+}
+
+/// Grow a rectangle by the DB unclip distance on every side.
+fn unclip(rect: RotatedBox, ratio: f32) -> RotatedBox {
+    let perimeter = 2.0 * (rect.w + rect.h);
+    if perimeter <= 0.0 {
+        return rect;
+    }
+    let d = rect.area() * ratio / perimeter;
+
+    RotatedBox {
+        w: rect.w + 2.0 * d,
+        h: rect.h + 2.0 * d,
+        ..rect
+    }
+}
+
+/// Order corners as top-left, top-right, bottom-right, bottom-left.
+fn order_points(mut points: [(f32, f32); 4]) -> [(f32, f32); 4] {
+    points.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let (tl, bl) = if points[0].1 <= points[1].1 {
+        (points[0], points[1])
+    } else {
+        (points[1], points[0])
+    };
+    let (tr, br) = if points[2].1 <= points[3].1 {
+        (points[2], points[3])
+    } else {
+        (points[3], points[2])
+    };
+
+    [tl, tr, br, bl]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::postprocess::tests::attr;
+
+    #[test]
+    fn test_min_area_rect() {
+        // a diamond fits a rotated square
+        let hull = convex_hull(vec![
+            (0.0, 2.0),
+            (2.0, 0.0),
+            (4.0, 2.0),
+            (2.0, 4.0),
+            (2.0, 2.0),
+        ]);
+        assert_eq!(hull.len(), 4);
+        let rect = min_area_rect(&hull);
+        assert!((rect.area() - 8.0).abs() < 1e-4);
+        assert!((rect.cx - 2.0).abs() < 1e-5 && (rect.cy - 2.0).abs() < 1e-5);
+
+        let unclipped = unclip(RotatedBox::new(0.0, 0.0, 10.0, 2.0, 0.0), 1.5);
+        assert!((unclipped.h - (2.0 + 2.0 * 30.0 / 24.0)).abs() < 1e-5);
+
+        // NaN coordinates sort without panicking
+        let nan = f32::NAN;
+        convex_hull(vec![(0.0, 0.0), (nan, 1.0), (1.0, nan), (1.0, 1.0)]);
+        order_points([(0.0, 0.0), (nan, 1.0), (1.0, 0.0), (1.0, 1.0)]);
+    }
+
+    #[test]
+    fn test_detect() {
+        // two text lines on a 16x32 map, and a speck below min_size
+        let (h, w) = (16, 32);
+        let mut map = vec![0f32; h * w];
+        for y in 2..6 {
+            for x in 4..28 {
+                map[y * w + x] = 0.9;
+            }
+        }
+        for y in 9..14 {
+            for x in 2..12 {
+                map[y * w + x] = 0.8;
+            }
+        }
+        map[15 * w + 30] = 1.0;
+        let a = attr(&[1, 1, h as u32, w as u32], 0, 1.0);
+        let prob = OutputTensor::from_f32(&a, &map);
+
+        let boxes = detect(&prob, &DbConfig::default());
+        assert_eq!(boxes.len(), 2);
+        assert!((boxes[0].score - 0.9).abs() < 1e-6);
+        // pixel centers span 23 x 3, grown by 69 * 1.5 / 52 on every side
+        let d = 69.0 * 1.5 / 52.0;
+        let [tl, tr, br, bl] = boxes[0].points;
+        assert!((tl.0 - (4.0 - d)).abs() < 1e-4 && (tl.1 - (2.0 - d)).abs() < 1e-4);
+        assert!((tr.0 - (27.0 + d)).abs() < 1e-4);
+        assert!((br.1 - (5.0 + d)).abs() < 1e-4);
+        assert!((bl.0 - tl.0).abs() < 1e-4);
+        assert!((boxes[1].score - 0.8).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,210 @@
+//! PP-OCR text detection and recognition.
+//!
+//! [`db`] turns the detector probability map into text boxes, [`ctc`] decodes
+//! the recognizer output. [`OcrPipeline`] chains both models: boxes found by
+//! the detector are cropped upright from the source image and recognized one
+//! by one.
+
+pub mod ctc;
+pub mod db;
+
+use ndarray::prelude::*;
+
+use self::ctc::CtcDecoder;
+use self::db::{DbConfig, TextBox};
+use super::OutputTensor;
+use crate::preprocess::input_size;
+use crate::preprocess::letterbox::{letterbox, resize_bilinear, LetterboxAlign, LetterboxConfig};
+use crate::{
+    make_rknn_image_input, rknn_inputs_set, rknn_outputs_get, rknn_outputs_release, rknn_run,
+    RKNNContextPack,
+};
+
+/// Cut a text box out of a HWC image as an upright `(height, width, C)`
+/// crop. Boxes much taller than wide are rotated to read left to right.
+pub fn crop_text(image: ArrayView3<u8>, text_box: &TextBox) -> Array3<u8> {
+    let (img_h, img_w, channels) = image.dim();
+    let [tl, tr, _, bl] = text_box.points;
+    let w = (text_box.width().round() as usize).max(1);
+    let h = (text_box.height().round() as usize).max(1);
+
+    // affine map from crop pixels to image pixels along the box edges
+    let ux = ((tr.0 - tl.0) / w as f32, (tr.1 - tl.1) / w as f32);
+    let uy = ((bl.0 - tl.0) / h as f32, (bl.1 - tl.1) / h as f32);
+    let sample = |x: f32, y: f32, c: usize| {
+        let x = x.clamp(0.0, (img_w - 1) as f32);
+        let y = y.clamp(0.0, (img_h - 1) as f32);
+        let (x0, y0) = (x as usize, y as usize);
+        let (x1, y1) = ((x0 + 1).min(img_w - 1), (y0 + 1).min(img_h - 1));
+        let (fx, fy) = (x - x0 as f32, y - y0 as f32);
+        let top = image[[y0, x0, c]] as f32 * (1.0 - fx) + image[[y0, x1, c]] as f32 * fx;
+        let bottom = image[[y1, x0, c]] as f32 * (1.0 - fx) + image[[y1, x1, c]] as f32 * fx;
+        top * (1.0 - fy) + bottom * fy
+    };
+
+    let crop = Array3::from_shape_fn((h, w, channels), |(y, x, c)| {
+        let (u, v) = (x as f32 + 0.5, y as f32 + 0.5);
+        let sx = tl.0 + u * ux.0 + v * uy.0 - 0.5;
+        let sy = tl.1 + u * ux.1 + v * uy.1 - 0.5;
+        (sample(sx, sy, c) + 0.5).clamp(0.0, 255.0) as u8
+    });
+
+    if h as f32 / w as f32 >= 1.5 {
+        // rotate 90 degrees counter clockwise
+        crop.slice(s![.., ..;-1, ..])
+            .permuted_axes([1, 0, 2])
+            .as_standard_layout()
+            .into_owned()
+    } else {
+        crop
+    }
+}
+
+/// Resize a crop to `height` keeping its aspect ratio and pad the right side
+/// with zeros up to `width`, as the recognizer expects.
+pub fn rec_input(crop: ArrayView3<u8>, height: usize, width: usize) -> Array3<u8> {
+    let (h, w, channels) = crop.dim();
+    let resized_w = ((height as f32 * w as f32 / h as f32).ceil() as usize).clamp(1, width);
+
+    let mut input = Array3::zeros((height, width, channels));
+    resize_bilinear(crop, input.slice_mut(s![.., ..resized_w, ..]));
+
+    input
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OcrResult {
+    /// In source image coordinates.
+    pub text_box: TextBox,
+    pub text: String,
+    pub score: f32,
+}
+
+/// A detector and a recognizer model chained together.
+#[derive(Debug)]
+pub struct OcrPipeline {
+    pub det: RKNNContextPack,
+    pub rec: RKNNContextPack,
+    pub db: DbConfig,
+    pub decoder: CtcDecoder,
+    /// Use CTC beam search with this width instead of greedy decoding.
+    pub beam_width: Option<usize>,
+    /// Drop lines scoring below this.
+    pub min_score: f32,
+}
+
+impl OcrPipeline {
+    pub fn new(det: RKNNContextPack, rec: RKNNContextPack, decoder: CtcDecoder) -> Self {
+        OcrPipeline {
+            det,
+            rec,
+            db: DbConfig::default(),
+            decoder,
+            beam_width: None,
+            min_score: 0.5,
+        }
+    }
+
+    /// Detect text boxes in a HWC RGB image, in source coordinates.
+    pub fn detect(&self, image: ArrayView3<u8>) -> Result<Vec<TextBox>, i32> {
+        let (h, w, _) = input_size(self.det.input_info.first().unwrap());
+        let config = LetterboxConfig {
+            pad_color: [0, 0, 0],
+            align: LetterboxAlign::TopLeft,
+        };
+        let (mut input, transform) = letterbox(image, h, w, &config)?;
+
+        let boxes = run(&self.det, &mut input, |outputs| {
+            db::detect(&outputs[0], &self.db)
+        })?;
+
+        Ok(boxes.iter().map(|b| b.to_source(&transform)).collect())
+    }
+
+    /// Recognize the text of an upright crop.
+    pub fn recognize(&self, crop: ArrayView3<u8>) -> Result<ctc::TextLine, i32> {
+        let (h, w, _) = input_size(self.rec.input_info.first().unwrap());
+        let mut input = rec_input(crop, h, w);
+
+        run(&self.rec, &mut input, |outputs| match self.beam_width {
+            Some(width) => self.decoder.decode_beam(&outputs[0], width),
+            None => self.decoder.decode_greedy(&outputs[0]),
+        })
+    }
+
+    /// Detect and recognize all text lines of a HWC RGB image.
+    pub fn run(&self, image: ArrayView3<u8>) -> Result<Vec<OcrResult>, i32> {
+        let mut results = Vec::new();
+        for text_box in self.detect(image)? {
+            let crop = crop_text(image, &text_box);
+            let line = self.recognize(crop.view())?;
+            if line.score >= self.min_score && !line.text.is_empty() {
+                results.push(OcrResult {
+                    text_box,
+                    text: line.text,
+                    score: line.score,
+                });
+            }
+        }
+
+        Ok(results)
+    }
+}
+
+/// Run a single input model and decode its outputs before they are released.
+fn run<T>(
+    pack: &RKNNContextPack,
+    input: &mut Array3<u8>,
+    decode: impl FnOnce(&[OutputTensor]) -> T,
+) -> Result<T, i32> {
+    let inputs = make_rknn_image_input(input.view_mut().into_dyn());
+    rknn_inputs_set(pack.ctx, pack.io_info.n_input, inputs)?;
+    rknn_run(pack.ctx)?;
+    let outputs = rknn_outputs_get(pack.ctx, pack.io_info.n_output)?;
+
+    // outputs come straight from rknn_outputs_get and match output_info
+    let result = {
+        let tensors = unsafe { OutputTensor::from_outputs(&pack.output_info, &outputs) };
+        tensors.map(|tensors| decode(&tensors))
+    };
+    rknn_outputs_release(pack.ctx, outputs)?;
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crop_text() {
+        let image = Array3::from_shape_fn((10, 20, 1), |(y, x, _)| (y * 20 + x) as u8);
+        let text_box = TextBox {
+            points: [(2.0, 3.0), (8.0, 3.0), (8.0, 6.0), (2.0, 6.0)],
+            score: 1.0,
+        };
+        let crop = crop_text(image.view(), &text_box);
+        assert_eq!(crop.dim(), (3, 6, 1));
+        assert_eq!(crop[[0, 0, 0]], image[[3, 2, 0]]);
+        assert_eq!(crop[[2, 5, 0]], image[[5, 7, 0]]);
+
+        // a vertical box is turned to read left to right
+        let vertical = TextBox {
+            points: [(2.0, 0.0), (4.0, 0.0), (4.0, 8.0), (2.0, 8.0)],
+            score: 1.0,
+        };
+        let crop = crop_text(image.view(), &vertical);
+        assert_eq!(crop.dim(), (2, 8, 1));
+        assert_eq!(crop[[0, 0, 0]], image[[0, 3, 0]]);
+        assert_eq!(crop[[1, 7, 0]], image[[7, 2, 0]]);
+    }
+
+    #[test]
+    fn test_rec_input() {
+        let crop = Array3::from_elem((4, 8, 3), 200u8);
+        let input = rec_input(crop.view(), 8, 32);
+        assert_eq!(input.dim(), (8, 32, 3));
+        assert_eq!(input[[4, 15, 0]], 200);
+        assert_eq!(input[[4, 16, 0]], 0);
+    }
+}
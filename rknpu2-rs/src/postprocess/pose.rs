@@ -0,0 +1,329 @@
+//! YOLOv8-pose keypoint decoding, as exported by the rknn model zoo.
+//!
+//! Every stride has one output holding the DFL box bins followed by the class
+//! logits, e.g. `[1, 65, H, W]` for one class. A separate keypoint output
+//! `[1, K, 3, N]` holds `(x, y, visibility)` of each keypoint for all `N`
+//! anchors, already decoded to model input pixels.
+
+use super::nms::nms;
+use super::yolo::{dfl, YoloConfig};
+use super::{BBox, Detection, OutputTensor};
+use crate::preprocess::input_size;
+use crate::preprocess::letterbox::LetterboxTransform;
+use crate::{RKNNContextPack, RKNNOutput};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PoseConfig {
+    pub yolo: YoloConfig,
+    pub num_classes: usize,
+    /// Apply sigmoid to the class outputs, the model zoo export leaves
+    /// them as logits.
+    pub sigmoid: bool,
+}
+
+impl Default for PoseConfig {
+    fn default() -> Self {
+        PoseConfig {
+            yolo: YoloConfig::default(),
+            num_classes: 1,
+            sigmoid: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Keypoint {
+    pub x: f32,
+    pub y: f32,
+    /// Visibility, 1.0 for models without a visibility channel.
+    pub score: f32,
+}
+
+/// Keypoint names and the pairs of keypoints joined by a limb.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Skeleton {
+    pub keypoints: &'static [&'static str],
+    pub edges: &'static [(usize, usize)],
+}
+
+impl Skeleton {
+    /// The 17 keypoint COCO person skeleton.
+    pub const COCO: Skeleton = Skeleton {
+        keypoints: &[
+            "nose",
+            "left_eye",
+            "right_eye",
+            "left_ear",
+            "right_ear",
+            "left_shoulder",
+            "right_shoulder",
+            "left_elbow",
+            "right_elbow",
+            "left_wrist",
+            "right_wrist",
+            "left_hip",
+            "right_hip",
+            "left_knee",
+            "right_knee",
+            "left_ankle",
+            "right_ankle",
+        ],
+        edges: &[
+            (15, 13),
+            (13, 11),
+            (16, 14),
+            (14, 12),
+            (11, 12),
+            (5, 11),
+            (6, 12),
+            (5, 6),
+            (5, 7),
+            (6, 8),
+            (7, 9),
+            (8, 10),
+            (1, 2),
+            (0, 1),
+            (0, 2),
+            (1, 3),
+            (2, 4),
+            (3, 5),
+            (4, 6),
+        ],
+    };
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pose {
+    pub detection: Detection,
+    pub keypoints: Vec<Keypoint>,
+}
+
+impl Pose {
+    /// Map from model input to source image coordinates.
+    pub fn to_source(&self, transform: &LetterboxTransform) -> Pose {
+        Pose {
+            detection: self.detection.to_source(transform),
+            keypoints: self
+                .keypoints
+                .iter()
+                .map(|k| {
+                    let (x, y) = transform.to_source(k.x, k.y);
+                    Keypoint { x, y, ..*k }
+                })
+                .collect(),
+        }
+    }
+
+    /// Limbs of `skeleton` whose both ends have a score of at least `min_score`.
+    pub fn limbs<'a>(
+        &'a self,
+        skeleton: &'a Skeleton,
+        min_score: f32,
+    ) -> impl Iterator<Item = (Keypoint, Keypoint)> + 'a {
+        skeleton.edges.iter().filter_map(move |&(a, b)| {
+            let (a, b) = (*self.keypoints.get(a)?, *self.keypoints.get(b)?);
+            (a.score >= min_score && b.score >= min_score).then_some((a, b))
+        })
+    }
+}
+
+/// Decode poses and apply NMS. Coordinates are in model input pixels, map
+/// them back with [`Pose::to_source`].
+pub fn detect(
+    tensors: &[OutputTensor],
+    input_h: usize,
+    input_w: usize,
+    config: &PoseConfig,
+) -> Vec<Pose> {
+    let nc = config.num_classes;
+    let is_head = |t: &OutputTensor| {
+        let (c, h, w) = t.chw();
+        t.dims().len() == 4
+            && c > nc
+            && (c - nc).is_multiple_of(4)
+            && input_h.is_multiple_of(h)
+            && input_w.is_multiple_of(w)
+            && input_h / h == input_w / w
+    };
+    let heads: Vec<&OutputTensor> = tensors.iter().filter(|t| is_head(t)).collect();
+    let total: usize = heads.iter().map(|t| t.chw().1 * t.chw().2).sum();
+    let Some(kpts) = tensors
+        .iter()
+        .find(|t| !is_head(t) && t.dims().len() == 4 && t.dims()[3] as usize == total)
+    else {
+        return Vec::new();
+    };
+
+    let thresh = if config.sigmoid {
+        (config.yolo.conf_thresh / (1.0 - config.yolo.conf_thresh)).ln()
+    } else {
+        config.yolo.conf_thresh
+    };
+
+    let mut candidates = Vec::new();
+    let mut anchors = Vec::new();
+    let mut offset = 0;
+    for t in heads {
+        let (c, h, w) = t.chw();
+        let dfl_len = (c - nc) / 4;
+        let (stride_x, stride_y) = ((input_w / w) as f32, (input_h / h) as f32);
+        let raw_thresh = t.raw_threshold(thresh);
+        let mut bins = vec![0f32; dfl_len];
+
+        for y in 0..h {
+            for x in 0..w {
+                let mut max_raw = f32::MIN;
+                let mut max_class = None;
+                for class_id in 0..nc {
+                    let v = t.raw(t.index(4 * dfl_len + class_id, y, x));
+                    if v > max_raw {
+                        max_raw = v;
+                        max_class = Some(class_id);
+                    }
+                }
+                let Some(class_id) = max_class.filter(|_| max_raw >= raw_thresh) else {
+                    continue;
+                };
+                let score = t.at(4 * dfl_len + class_id, y, x);
+                let score = if config.sigmoid {
+                    super::sigmoid(score)
+                } else {
+                    score
+                };
+                if score < config.yolo.conf_thresh {
+                    continue;
+                }
+
+                let mut dist = [0f32; 4];
+                for (side, d) in dist.iter_mut().enumerate() {
+                    *d = if dfl_len == 1 {
+                        t.at(side, y, x)
+                    } else {
+                        for (k, b) in bins.iter_mut().enumerate() {
+                            *b = t.at(side * dfl_len + k, y, x);
+                        }
+                        dfl(&bins)
+                    };
+                }
+                let (cx, cy) = (x as f32 + 0.5, y as f32 + 0.5);
+                candidates.push(Detection {
+                    class_id,
+                    score,
+                    bbox: BBox::new(
+                        (cx - dist[0]) * stride_x,
+                        (cy - dist[1]) * stride_y,
+                        (cx + dist[2]) * stride_x,
+                        (cy + dist[3]) * stride_y,
+                    ),
+                });
+                anchors.push(offset + y * w + x);
+            }
+        }
+        offset += h * w;
+    }
+
+    // keypoint output is [1, K, D, N] with D = 2 or 3
+    let (num_kpts, kpt_dim) = (kpts.dims()[1] as usize, kpts.dims()[2] as usize);
+    let at = |k: usize, j: usize, a: usize| kpts.get((k * kpt_dim + j) * total + a);
+
+    nms(&candidates, config.yolo.iou_thresh)
+        .into_iter()
+        .map(|i| Pose {
+            detection: candidates[i],
+            keypoints: (0..num_kpts)
+                .map(|k| Keypoint {
+                    x: at(k, 0, anchors[i]),
+                    y: at(k, 1, anchors[i]),
+                    score: if kpt_dim > 2 {
+                        at(k, 2, anchors[i])
+                    } else {
+                        1.0
+                    },
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+/// [`detect`] on the outputs of `rknn_outputs_get`.
+pub fn post_process(
+    ctx: &RKNNContextPack,
+    outputs: &[RKNNOutput],
+    config: &PoseConfig,
+) -> Result<Vec<Pose>, i32> {
+    let (input_h, input_w, _) = input_size(ctx.input_info.first().unwrap());
+    // outputs come straight from rknn_outputs_get and match output_info
+    let tensors = unsafe { OutputTensor::from_outputs(&ctx.output_info, outputs)? };
+
+    Ok(detect(&tensors, input_h, input_w, config))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::postprocess::tests::attr;
+    use crate::preprocess::letterbox::LetterboxAlign;
+
+    #[test]
+    fn test_detect_pose() {
+        // 32x32 input, a 2x2 and a 1x1 grid with direct distances and one
+        // class, two keypoints with visibility
+        let attrs = [
+            attr(&[1, 5, 2, 2], 0, 0.1),
+            attr(&[1, 5, 1, 1], 0, 0.125),
+            attr(&[1, 2, 3, 5], 0, 1.0),
+        ];
+        let mut head0 = vec![0i8; 20];
+        head0[16..].copy_from_slice(&[-50, -50, -50, -50]);
+        // box of the 1x1 grid: 0.25 cells on every side of the center, logit 2.0
+        let head1: Vec<i8> = vec![2, 2, 2, 2, 16];
+        let mut kpts = vec![0f32; 30];
+        for (k, j, v) in [
+            (0, 0, 10.0),
+            (0, 1, 12.0),
+            (0, 2, 0.9),
+            (1, 0, 20.0),
+            (1, 1, 22.0),
+        ] {
+            kpts[(k * 3 + j) * 5 + 4] = v;
+        }
+
+        let tensors = [
+            OutputTensor::from_i8(&attrs[0], &head0),
+            OutputTensor::from_i8(&attrs[1], &head1),
+            OutputTensor::from_f32(&attrs[2], &kpts),
+        ];
+        let poses = detect(&tensors, 32, 32, &PoseConfig::default());
+        assert_eq!(poses.len(), 1);
+        let pose = &poses[0];
+        assert!((pose.detection.score - super::super::sigmoid(2.0)).abs() < 1e-6);
+        assert_eq!(pose.detection.bbox, BBox::new(8.0, 8.0, 24.0, 24.0));
+        assert_eq!(
+            pose.keypoints,
+            vec![
+                Keypoint {
+                    x: 10.0,
+                    y: 12.0,
+                    score: 0.9
+                },
+                Keypoint {
+                    x: 20.0,
+                    y: 22.0,
+                    score: 0.0
+                },
+            ]
+        );
+
+        // letterboxed from 64x32, scale 0.5 with 8 rows of padding
+        let transform = LetterboxTransform::new(64, 32, 32, 32, LetterboxAlign::Center).unwrap();
+        let src = pose.to_source(&transform);
+        assert_eq!((src.keypoints[0].x, src.keypoints[0].y), (20.0, 8.0));
+
+        let skeleton = Skeleton {
+            keypoints: &["a", "b"],
+            edges: &[(0, 1)],
+        };
+        assert_eq!(pose.limbs(&skeleton, 0.0).count(), 1);
+        assert_eq!(pose.limbs(&skeleton, 0.5).count(), 0);
+    }
+}
@@ -0,0 +1,162 @@
+//! Semantic segmentation heads, e.g. DeepLab or PP-LiteSeg.
+//!
+//! The output holds one score map per class in NCHW, NHWC or NC1HWC2 layout.
+//! Every pixel gets the class with the highest score.
+
+use ndarray::prelude::*;
+
+use super::OutputTensor;
+use crate::preprocess::letterbox::LetterboxTransform;
+use crate::{RKNNContextPack, RKNNOutput};
+
+/// Label map of a `[1, C, H, W]` score output. `num_classes` drops the
+/// channel padding of NC1HWC2 outputs, it defaults to every channel.
+/// At most 256 classes are considered.
+pub fn argmax(scores: &OutputTensor, num_classes: Option<usize>) -> Array2<u8> {
+    let (channels, h, w) = scores.chw();
+    let classes = num_classes.unwrap_or(channels).min(channels).min(256);
+
+    // the raw values order like the dequantized ones
+    Array2::from_shape_fn((h, w), |(y, x)| {
+        let mut best = 0;
+        let mut best_raw = f32::MIN;
+        for c in 0..classes {
+            let v = scores.raw(scores.index(c, y, x));
+            if v > best_raw {
+                best_raw = v;
+                best = c;
+            }
+        }
+        best as u8
+    })
+}
+
+/// Map a label map covering the model input back to the source image, with
+/// nearest neighbour sampling. The map may be smaller than the input.
+pub fn labels_to_source(labels: ArrayView2<u8>, transform: &LetterboxTransform) -> Array2<u8> {
+    let (h, w) = labels.dim();
+    let fx = w as f32 / transform.dst_width as f32;
+    let fy = h as f32 / transform.dst_height as f32;
+    let shape = (transform.src_height as usize, transform.src_width as usize);
+
+    Array2::from_shape_fn(shape, |(y, x)| {
+        let (mx, my) = transform.to_model(x as f32 + 0.5, y as f32 + 0.5);
+        let lx = ((mx * fx) as usize).min(w - 1);
+        let ly = ((my * fy) as usize).min(h - 1);
+        labels[[ly, lx]]
+    })
+}
+
+/// The PASCAL VOC color map, which also serves Cityscapes or ADE20K style
+/// label maps well enough for visualization.
+pub fn voc_palette(num_classes: usize) -> Vec<[u8; 3]> {
+    (0..num_classes)
+        .map(|class| {
+            let mut color = [0u8; 3];
+            let mut id = class;
+            for bit in (0..8).rev() {
+                for (c, v) in color.iter_mut().enumerate() {
+                    *v |= (((id >> c) & 1) as u8) << bit;
+                }
+                id >>= 3;
+            }
+            color
+        })
+        .collect()
+}
+
+/// Color a label map as a HWC RGB image. Labels past the end of the palette
+/// are black.
+pub fn colorize(labels: ArrayView2<u8>, palette: &[[u8; 3]]) -> Array3<u8> {
+    let (h, w) = labels.dim();
+
+    Array3::from_shape_fn((h, w, 3), |(y, x, c)| {
+        palette
+            .get(labels[[y, x]] as usize)
+            .map_or(0, |color| color[c])
+    })
+}
+
+/// Pixel count of every class.
+pub fn class_areas(labels: ArrayView2<u8>, num_classes: usize) -> Vec<usize> {
+    let mut areas = vec![0; num_classes];
+    for &label in labels.iter() {
+        if let Some(area) = areas.get_mut(label as usize) {
+            *area += 1;
+        }
+    }
+
+    areas
+}
+
+/// [`argmax`] of the first output of `rknn_outputs_get`, mapped back to the
+/// source image.
+pub fn post_process(
+    ctx: &RKNNContextPack,
+    outputs: &[RKNNOutput],
+    num_classes: Option<usize>,
+    transform: &LetterboxTransform,
+) -> Result<Array2<u8>, i32> {
+    // outputs come straight from rknn_outputs_get and match output_info
+    let tensors = unsafe { OutputTensor::from_outputs(&ctx.output_info, outputs)? };
+    let output = tensors.first().ok_or(rknpu2_sys::RKNN_ERR_PARAM_INVALID)?;
+    let labels = argmax(output, num_classes);
+
+    Ok(labels_to_source(labels.view(), transform))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::postprocess::tests::attr;
+    use crate::preprocess::letterbox::LetterboxAlign;
+
+    #[test]
+    fn test_argmax() {
+        // 3 classes on a 2x2 map, as NCHW and as NC1HWC2 packed by 2
+        let a = attr(&[1, 3, 2, 2], 0, 1.0);
+        let nchw: Vec<i8> = vec![
+            5, 0, 0, 0, //
+            0, 5, 0, 1, //
+            0, 0, 5, 0, //
+        ];
+        let labels = argmax(&OutputTensor::from_i8(&a, &nchw), None);
+        assert_eq!(labels, arr2(&[[0, 1], [2, 1]]));
+
+        let mut packed = a;
+        packed.fmt = rknpu2_sys::_rknn_tensor_format_RKNN_TENSOR_NC1HWC2;
+        packed.n_dims = 5;
+        packed.dims[..5].copy_from_slice(&[1, 2, 2, 2, 2]);
+        let mut nc1hwc2 = vec![0i8; 16];
+        for c in 0..3 {
+            for i in 0..4 {
+                nc1hwc2[((c / 2) * 4 + i) * 2 + c % 2] = nchw[c * 4 + i];
+            }
+        }
+        // the padding channel must not win
+        for i in 0..4 {
+            nc1hwc2[(4 + i) * 2 + 1] = 100;
+        }
+        let t = OutputTensor::from_i8(&packed, &nc1hwc2);
+        assert_eq!(argmax(&t, Some(3)), labels);
+        assert_eq!(class_areas(labels.view(), 4), vec![1, 2, 1, 0]);
+    }
+
+    #[test]
+    fn test_labels_to_source() {
+        // a 2x2 map for a 4x4 input letterboxed from 8x4
+        let labels = arr2(&[[1, 2], [3, 4]]);
+        let transform = LetterboxTransform::new(8, 4, 4, 4, LetterboxAlign::Center).unwrap();
+        let src = labels_to_source(labels.view(), &transform);
+        assert_eq!(src.dim(), (4, 8));
+        assert_eq!(src.row(0).to_vec(), vec![1, 1, 1, 1, 2, 2, 2, 2]);
+        assert_eq!(src.row(3).to_vec(), vec![3, 3, 3, 3, 4, 4, 4, 4]);
+
+        let palette = voc_palette(4);
+        assert_eq!(palette[1], [128, 0, 0]);
+        assert_eq!(palette[3], [128, 128, 0]);
+        let colors = colorize(labels.view(), &palette);
+        assert_eq!(colors.slice(s![0, 0, ..]).to_vec(), vec![128, 0, 0]);
+        assert_eq!(colors.slice(s![1, 1, ..]).to_vec(), vec![0, 0, 0]);
+    }
+}
@@ -0,0 +1,584 @@
+//! YOLO detection heads, as exported by the rknn model zoo.
+//!
+//! For the YOLOv6 / YOLOv8 anchor-free heads every stride has a box output
+//! with `4 * reg_max` channels (`reg_max == 1` means direct ltrb distances,
+//! otherwise DFL bins), a class score output holding sigmoid scores and
+//! optionally a one channel clipped score sum used to skip anchors early.
+//! Outputs of a stride share their grid size, which is how branches are found
+//! from the output attributes.
+//!
+//! YOLOv5 / YOLOv7 anchor-based heads have one output per stride, see
+//! [`AnchorConfig`].
+
+use super::nms::nms;
+use super::{sigmoid, BBox, Detection, OutputTensor};
+use crate::preprocess::input_size;
+use crate::{RKNNContextPack, RKNNOutput};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct YoloConfig {
+    pub conf_thresh: f32,
+    pub iou_thresh: f32,
+    /// Skip anchors whose score sum is below `conf_thresh`, if the model
+    /// has score sum outputs.
+    pub score_sum_filter: bool,
+}
+
+impl Default for YoloConfig {
+    fn default() -> Self {
+        YoloConfig {
+            conf_thresh: 0.25,
+            iou_thresh: 0.45,
+            score_sum_filter: true,
+        }
+    }
+}
+
+/// Outputs belonging to one stride of the head, as indices into the output list.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Branch {
+    pub boxes: usize,
+    pub scores: usize,
+    pub score_sum: Option<usize>,
+    /// Any further outputs on the same grid, e.g. mask coefficients.
+    pub extra: Vec<usize>,
+    pub grid_h: usize,
+    pub grid_w: usize,
+    pub stride_x: f32,
+    pub stride_y: f32,
+    /// Number of anchors in the branches before this one.
+    pub anchor_offset: usize,
+}
+
+/// Where a candidate was found, used to look up per anchor data like mask
+/// coefficients or keypoints.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Anchor {
+    pub branch: usize,
+    pub x: usize,
+    pub y: usize,
+    /// Flat anchor index over all branches.
+    pub index: usize,
+}
+
+/// Group outputs into branches by grid size. Within a branch the first output
+/// is the box output, the second the scores, and a following one channel
+/// output the score sum. Grids with a single output (e.g. mask prototypes)
+/// are ignored.
+pub fn find_branches(tensors: &[OutputTensor], input_h: usize, input_w: usize) -> Vec<Branch> {
+    let mut groups: Vec<((usize, usize), Vec<usize>)> = Vec::new();
+    for (i, t) in tensors.iter().enumerate() {
+        let (_, h, w) = t.chw();
+        match groups.iter_mut().find(|(grid, _)| *grid == (h, w)) {
+            Some((_, members)) => members.push(i),
+            None => groups.push(((h, w), vec![i])),
+        }
+    }
+
+    let mut branches = Vec::new();
+    let mut anchor_offset = 0;
+    for ((grid_h, grid_w), members) in groups {
+        if members.len() < 2 {
+            continue;
+        }
+
+        let mut rest = members[2..].iter().copied().peekable();
+        let score_sum = rest.next_if(|&i| tensors[i].chw().0 == 1);
+        branches.push(Branch {
+            boxes: members[0],
+            scores: members[1],
+            score_sum,
+            extra: rest.collect(),
+            grid_h,
+            grid_w,
+            stride_x: input_w as f32 / grid_w as f32,
+            stride_y: input_h as f32 / grid_h as f32,
+            anchor_offset,
+        });
+        anchor_offset += grid_h * grid_w;
+    }
+
+    branches
+}
+
+/// Decode all candidates above `config.conf_thresh`, before NMS.
+pub fn decode(
+    tensors: &[OutputTensor],
+    branches: &[Branch],
+    config: &YoloConfig,
+) -> Vec<(Detection, Anchor)> {
+    let mut candidates = Vec::new();
+
+    for (branch_idx, branch) in branches.iter().enumerate() {
+        let boxes = &tensors[branch.boxes];
+        let scores = &tensors[branch.scores];
+        let score_sum = match branch.score_sum {
+            Some(i) if config.score_sum_filter => Some(&tensors[i]),
+            _ => None,
+        };
+
+        let class_num = scores.chw().0;
+        let dfl_len = boxes.chw().0 / 4;
+        let score_thresh = scores.raw_threshold(config.conf_thresh);
+        let sum_thresh = score_sum.map(|t| t.raw_threshold(config.conf_thresh));
+        let mut bins = vec![0f32; dfl_len];
+
+        for y in 0..branch.grid_h {
+            for x in 0..branch.grid_w {
+                // fast filter
+                if let (Some(t), Some(thresh)) = (score_sum, sum_thresh) {
+                    if t.raw(t.index(0, y, x)) < thresh {
+                        continue;
+                    }
+                }
+
+                // find most likely class to this anchor, in the raw domain
+                let mut max_raw = f32::MIN;
+                let mut max_class = None;
+                for class_id in 0..class_num {
+                    let v = scores.raw(scores.index(class_id, y, x));
+                    if v > max_raw {
+                        max_raw = v;
+                        max_class = Some(class_id);
+                    }
+                }
+                let Some(class_id) = max_class.filter(|_| max_raw >= score_thresh) else {
+                    continue;
+                };
+                let score = scores.at(class_id, y, x);
+                if score < config.conf_thresh {
+                    continue;
+                }
+
+                let mut dist = [0f32; 4];
+                for (side, d) in dist.iter_mut().enumerate() {
+                    *d = if dfl_len == 1 {
+                        boxes.at(side, y, x)
+                    } else {
+                        for (k, b) in bins.iter_mut().enumerate() {
+                            *b = boxes.at(side * dfl_len + k, y, x);
+                        }
+                        dfl(&bins)
+                    };
+                }
+
+                let (cx, cy) = (x as f32 + 0.5, y as f32 + 0.5);
+                let bbox = BBox::new(
+                    (cx - dist[0]) * branch.stride_x,
+                    (cy - dist[1]) * branch.stride_y,
+                    (cx + dist[2]) * branch.stride_x,
+                    (cy + dist[3]) * branch.stride_y,
+                );
+
+                candidates.push((
+                    Detection {
+                        class_id,
+                        score,
+                        bbox,
+                    },
+                    Anchor {
+                        branch: branch_idx,
+                        x,
+                        y,
+                        index: branch.anchor_offset + y * branch.grid_w + x,
+                    },
+                ));
+            }
+        }
+    }
+
+    candidates
+}
+
+/// Decode and apply NMS. Boxes are in model input coordinates, map them back
+/// with [`Detection::to_source`].
+pub fn detect(
+    tensors: &[OutputTensor],
+    input_h: usize,
+    input_w: usize,
+    config: &YoloConfig,
+) -> Vec<Detection> {
+    let branches = find_branches(tensors, input_h, input_w);
+    let candidates: Vec<Detection> = decode(tensors, &branches, config)
+        .into_iter()
+        .map(|(det, _)| det)
+        .collect();
+
+    nms(&candidates, config.iou_thresh)
+        .into_iter()
+        .map(|i| candidates[i])
+        .collect()
+}
+
+/// [`detect`] on the outputs of `rknn_outputs_get`.
+pub fn post_process(
+    ctx: &RKNNContextPack,
+    outputs: &[RKNNOutput],
+    config: &YoloConfig,
+) -> Result<Vec<Detection>, i32> {
+    let (input_h, input_w, _) = input_size(ctx.input_info.first().unwrap());
+    // outputs come straight from rknn_outputs_get and match output_info
+    let tensors = unsafe { OutputTensor::from_outputs(&ctx.output_info, outputs)? };
+
+    Ok(detect(&tensors, input_h, input_w, config))
+}
+
+/// Anchor boxes and strides of a YOLOv5 / YOLOv7 anchor-based head.
+/// `anchors[i]` and `strides[i]` belong to output `i`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnchorConfig {
+    /// `(width, height)` of each anchor, in input pixels.
+    pub anchors: Vec<Vec<(f32, f32)>>,
+    pub strides: Vec<f32>,
+    /// Apply sigmoid to the outputs. Models exported by the rknn model zoo
+    /// already end with a sigmoid.
+    pub sigmoid: bool,
+}
+
+impl AnchorConfig {
+    pub fn yolov5() -> Self {
+        AnchorConfig {
+            anchors: vec![
+                vec![(10.0, 13.0), (16.0, 30.0), (33.0, 23.0)],
+                vec![(30.0, 61.0), (62.0, 45.0), (59.0, 119.0)],
+                vec![(116.0, 90.0), (156.0, 198.0), (373.0, 326.0)],
+            ],
+            strides: vec![8.0, 16.0, 32.0],
+            sigmoid: false,
+        }
+    }
+
+    pub fn yolov7() -> Self {
+        AnchorConfig {
+            anchors: vec![
+                vec![(12.0, 16.0), (19.0, 36.0), (40.0, 28.0)],
+                vec![(36.0, 75.0), (76.0, 55.0), (72.0, 146.0)],
+                vec![(142.0, 110.0), (192.0, 243.0), (459.0, 401.0)],
+            ],
+            strides: vec![8.0, 16.0, 32.0],
+            sigmoid: false,
+        }
+    }
+}
+
+/// Decode anchor-based outputs of shape `[1, anchors * (5 + classes), H, W]`
+/// above `config.conf_thresh`, before NMS. Objectness is thresholded in the
+/// quantized domain before anything is dequantized.
+///
+/// Fails with `RKNN_ERR_PARAM_INVALID` if the outputs don't match `anchors`.
+pub fn decode_anchor_based(
+    tensors: &[OutputTensor],
+    anchors: &AnchorConfig,
+    config: &YoloConfig,
+) -> Result<Vec<Detection>, i32> {
+    if tensors.len() != anchors.strides.len() || tensors.len() != anchors.anchors.len() {
+        return Err(rknpu2_sys::RKNN_ERR_PARAM_INVALID);
+    }
+
+    let activate = |v: f32| if anchors.sigmoid { sigmoid(v) } else { v };
+    // objectness bounds the final score, so it has to pass the threshold too
+    let obj_thresh = if anchors.sigmoid {
+        (config.conf_thresh / (1.0 - config.conf_thresh)).ln()
+    } else {
+        config.conf_thresh
+    };
+
+    let mut candidates = Vec::new();
+    for ((t, branch_anchors), &stride) in tensors.iter().zip(&anchors.anchors).zip(&anchors.strides)
+    {
+        let (channels, grid_h, grid_w) = t.chw();
+        if branch_anchors.is_empty() || channels % branch_anchors.len() != 0 {
+            return Err(rknpu2_sys::RKNN_ERR_PARAM_INVALID);
+        }
+        let per_anchor = channels / branch_anchors.len();
+        if per_anchor <= 5 {
+            return Err(rknpu2_sys::RKNN_ERR_PARAM_INVALID);
+        }
+        let class_num = per_anchor - 5;
+        let obj_raw_thresh = t.raw_threshold(obj_thresh);
+
+        for (a, &(anchor_w, anchor_h)) in branch_anchors.iter().enumerate() {
+            let base = a * per_anchor;
+            for y in 0..grid_h {
+                for x in 0..grid_w {
+                    if t.raw(t.index(base + 4, y, x)) < obj_raw_thresh {
+                        continue;
+                    }
+
+                    let mut max_raw = f32::MIN;
+                    let mut class_id = 0;
+                    for c in 0..class_num {
+                        let v = t.raw(t.index(base + 5 + c, y, x));
+                        if v > max_raw {
+                            max_raw = v;
+                            class_id = c;
+                        }
+                    }
+
+                    let obj = activate(t.at(base + 4, y, x));
+                    let score = obj * activate(t.at(base + 5 + class_id, y, x));
+                    if score < config.conf_thresh {
+                        continue;
+                    }
+
+                    let v = |k: usize| activate(t.at(base + k, y, x)) * 2.0;
+                    let cx = (v(0) - 0.5 + x as f32) * stride;
+                    let cy = (v(1) - 0.5 + y as f32) * stride;
+                    let w = v(2) * v(2) * anchor_w;
+                    let h = v(3) * v(3) * anchor_h;
+
+                    candidates.push(Detection {
+                        class_id,
+                        score,
+                        bbox: BBox::new(cx - w / 2.0, cy - h / 2.0, cx + w / 2.0, cy + h / 2.0),
+                    });
+                }
+            }
+        }
+    }
+
+    Ok(candidates)
+}
+
+/// Decode an anchor-based head and apply NMS.
+pub fn detect_anchor_based(
+    tensors: &[OutputTensor],
+    anchors: &AnchorConfig,
+    config: &YoloConfig,
+) -> Result<Vec<Detection>, i32> {
+    let candidates = decode_anchor_based(tensors, anchors, config)?;
+
+    Ok(nms(&candidates, config.iou_thresh)
+        .into_iter()
+        .map(|i| candidates[i])
+        .collect())
+}
+
+/// [`detect_anchor_based`] on the outputs of `rknn_outputs_get`.
+pub fn post_process_anchor_based(
+    ctx: &RKNNContextPack,
+    outputs: &[RKNNOutput],
+    anchors: &AnchorConfig,
+    config: &YoloConfig,
+) -> Result<Vec<Detection>, i32> {
+    // outputs come straight from rknn_outputs_get and match output_info
+    let tensors = unsafe { OutputTensor::from_outputs(&ctx.output_info, outputs)? };
+
+    detect_anchor_based(&tensors, anchors, config)
+}
+
+/// Expected value of a distribution focal loss bin set.
+pub(crate) fn dfl(bins: &[f32]) -> f32 {
+    let max = bins.iter().copied().fold(f32::MIN, f32::max);
+    let mut sum = 0.0;
+    let mut acc = 0.0;
+    for (k, &b) in bins.iter().enumerate() {
+        let e = (b - max).exp();
+        sum += e;
+        acc += e * k as f32;
+    }
+
+    acc / sum
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::postprocess::tests::attr;
+
+    #[test]
+    fn test_dfl() {
+        assert_eq!(dfl(&[0.0; 4]), 1.5);
+        let mut bins = [0f32; 16];
+        bins[3] = 20.0;
+        assert!((dfl(&bins) - 3.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_find_branches() {
+        let attrs = [
+            attr(&[1, 64, 4, 4], 0, 1.0),
+            attr(&[1, 2, 4, 4], 0, 1.0),
+            attr(&[1, 1, 4, 4], 0, 1.0),
+            attr(&[1, 32, 4, 4], 0, 1.0),
+            attr(&[1, 64, 2, 2], 0, 1.0),
+            attr(&[1, 2, 2, 2], 0, 1.0),
+            attr(&[1, 32, 8, 8], 0, 1.0),
+        ];
+        let data = [0i8; 2048];
+        let tensors: Vec<_> = attrs
+            .iter()
+            .map(|a| OutputTensor::from_i8(a, &data[..a.n_elems as usize]))
+            .collect();
+
+        let branches = find_branches(&tensors, 32, 32);
+        assert_eq!(branches.len(), 2);
+        assert_eq!(branches[0].score_sum, Some(2));
+        assert_eq!(branches[0].extra, vec![3]);
+        assert_eq!(branches[0].stride_x, 8.0);
+        assert_eq!(branches[1].boxes, 4);
+        assert_eq!(branches[1].score_sum, None);
+        assert_eq!(branches[1].anchor_offset, 16);
+    }
+
+    #[test]
+    fn test_detect_dfl_int8() {
+        // one 2x2 branch on a 32x32 input, 2 classes, reg_max 16
+        let box_attr = attr(&[1, 64, 2, 2], 0, 0.1);
+        let score_attr = attr(&[1, 2, 2, 2], -128, 1.0 / 255.0);
+        let sum_attr = attr(&[1, 1, 2, 2], -128, 1.0 / 255.0);
+
+        let mut boxes = vec![0i8; 64 * 4];
+        // anchor (x=1, y=0): ltrb distances 1, 0, 0, 1 grid cells
+        let anchor = 1;
+        for (side, bin) in [(0, 1), (1, 0), (2, 0), (3, 1)] {
+            boxes[(side * 16 + bin) * 4 + anchor] = 127;
+        }
+        let mut scores = vec![-128i8; 2 * 4];
+        scores[4 + anchor] = 102; // class 1, 0.9
+        scores[2] = 0; // class 0 at (x=0, y=1), 0.5 but score sum filters it
+        let mut sum = vec![-128i8; 4];
+        sum[anchor] = 102;
+
+        let tensors = [
+            OutputTensor::from_i8(&box_attr, &boxes),
+            OutputTensor::from_i8(&score_attr, &scores),
+            OutputTensor::from_i8(&sum_attr, &sum),
+        ];
+        let dets = detect(&tensors, 32, 32, &YoloConfig::default());
+
+        assert_eq!(dets.len(), 1);
+        assert_eq!(dets[0].class_id, 1);
+        assert!((dets[0].score - 230.0 / 255.0).abs() < 1e-6);
+        let b = dets[0].bbox;
+        for (v, expected) in [(b.x1, 8.0), (b.y1, 8.0), (b.x2, 24.0), (b.y2, 24.0)] {
+            assert!((v - expected).abs() < 0.01, "{v} != {expected}");
+        }
+
+        let config = YoloConfig {
+            score_sum_filter: false,
+            ..Default::default()
+        };
+        assert_eq!(detect(&tensors, 32, 32, &config).len(), 2);
+    }
+
+    #[test]
+    fn test_detect_threshold_boundary() {
+        // at zp -128 and scale 1/255 a threshold of 0.25 falls between the
+        // raw values -65 (0.247) and -64 (0.251)
+        let box_attr = attr(&[1, 4, 2, 2], 0, 0.1);
+        let score_attr = attr(&[1, 1, 2, 2], -128, 1.0 / 255.0);
+        let boxes = vec![10i8; 16];
+        let config = YoloConfig {
+            conf_thresh: 0.25,
+            ..Default::default()
+        };
+
+        for (raw, count) in [(-64, 1), (-65, 0)] {
+            let mut scores = vec![-128i8; 4];
+            scores[1] = raw;
+            let tensors = [
+                OutputTensor::from_i8(&box_attr, &boxes),
+                OutputTensor::from_i8(&score_attr, &scores),
+            ];
+            assert_eq!(detect(&tensors, 32, 32, &config).len(), count, "raw {raw}");
+        }
+    }
+
+    #[test]
+    fn test_detect_direct_float() {
+        let box_attr = attr(&[1, 4, 1, 2], 0, 1.0);
+        let score_attr = attr(&[1, 1, 1, 2], 0, 1.0);
+        // two boxes of the same class with an IoU of 1/3
+        let boxes = [1.0; 8];
+        let scores = [0.6, 0.8];
+        let tensors = [
+            OutputTensor::from_f32(&box_attr, &boxes),
+            OutputTensor::from_f32(&score_attr, &scores),
+        ];
+
+        let dets = detect(&tensors, 16, 32, &YoloConfig::default());
+        assert_eq!(dets.len(), 2);
+        assert_eq!(dets[0].bbox, BBox::new(8.0, -8.0, 40.0, 24.0));
+
+        let config = YoloConfig {
+            iou_thresh: 0.3,
+            ..Default::default()
+        };
+        assert_eq!(detect(&tensors, 16, 32, &config).len(), 1);
+    }
+
+    #[test]
+    fn test_detect_anchor_based_float() {
+        // one 2x2 grid on a 16x16 input, one anchor, 2 classes
+        let a = attr(&[1, 7, 2, 2], 0, 1.0);
+        let mut data = vec![0f32; 7 * 4];
+        // anchor (x=1, y=0)
+        for (k, v) in [0.5, 0.5, 0.5, 0.5, 0.9, 0.1, 0.8].into_iter().enumerate() {
+            data[k * 4 + 1] = v;
+        }
+        let tensors = [OutputTensor::from_f32(&a, &data)];
+        let anchors = AnchorConfig {
+            anchors: vec![vec![(10.0, 13.0)]],
+            strides: vec![8.0],
+            sigmoid: false,
+        };
+
+        let dets = detect_anchor_based(&tensors, &anchors, &YoloConfig::default()).unwrap();
+        assert_eq!(dets.len(), 1);
+        assert_eq!(dets[0].class_id, 1);
+        assert!((dets[0].score - 0.72).abs() < 1e-6);
+        assert_eq!(dets[0].bbox, BBox::new(7.0, -2.5, 17.0, 10.5));
+    }
+
+    #[test]
+    fn test_detect_anchor_based_int8_logits() {
+        // logits quantized with scale 0.1, two anchors on a 1x1 grid
+        let a = attr(&[1, 12, 1, 1], 0, 0.1);
+        let mut data = vec![0i8; 12];
+        data[4] = -30; // anchor 0 objectness sigmoid(-3.0), filtered early
+        data[5] = 50;
+        data[6 + 4] = 30; // anchor 1 objectness sigmoid(3.0)
+        data[6 + 5] = 20;
+        let tensors = [OutputTensor::from_i8(&a, &data)];
+        let anchors = AnchorConfig {
+            anchors: vec![vec![(8.0, 8.0), (16.0, 16.0)]],
+            strides: vec![32.0],
+            sigmoid: true,
+        };
+
+        let dets = decode_anchor_based(&tensors, &anchors, &YoloConfig::default()).unwrap();
+        assert_eq!(dets.len(), 1);
+        assert!((dets[0].score - sigmoid(3.0) * sigmoid(2.0)).abs() < 1e-6);
+        // all box logits are 0: sigmoid 0.5, centered in the cell with anchor size
+        assert_eq!(dets[0].bbox, BBox::new(8.0, 8.0, 24.0, 24.0));
+    }
+
+    #[test]
+    fn test_anchor_config_mismatch() {
+        let a = attr(&[1, 12, 1, 1], 0, 0.1);
+        let data = vec![0i8; 12];
+        let tensors = [OutputTensor::from_i8(&a, &data)];
+        let config = YoloConfig::default();
+        let err = Err(rknpu2_sys::RKNN_ERR_PARAM_INVALID);
+
+        // 12 channels split over 3 anchors leaves no class scores
+        let anchors = AnchorConfig {
+            anchors: vec![vec![(8.0, 8.0); 3]],
+            strides: vec![32.0],
+            sigmoid: true,
+        };
+        assert_eq!(decode_anchor_based(&tensors, &anchors, &config), err);
+        // 12 channels don't split over 5 anchors
+        let anchors = AnchorConfig {
+            anchors: vec![vec![(8.0, 8.0); 5]],
+            ..anchors
+        };
+        assert_eq!(decode_anchor_based(&tensors, &anchors, &config), err);
+        // three strides for one output
+        assert_eq!(
+            decode_anchor_based(&tensors, &AnchorConfig::yolov5(), &config),
+            err
+        );
+    }
+}
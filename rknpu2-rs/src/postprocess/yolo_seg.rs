@@ -0,0 +1,331 @@
+//! YOLOv8-seg instance segmentation, as exported by the rknn model zoo.
+//!
+//! On top of the detection head every stride has a mask coefficient output
+//! on the same grid, and a single prototype output `[1, C, H, W]` holds the
+//! mask basis. A mask is `sigmoid(coefficients · prototypes)`, cropped to
+//! the box and upsampled to the source image.
+
+use ndarray::prelude::*;
+
+use super::nms::nms;
+use super::yolo::{decode, find_branches, Branch, YoloConfig};
+use super::{Detection, OutputTensor};
+use crate::preprocess::input_size;
+use crate::preprocess::letterbox::LetterboxTransform;
+use crate::{RKNNContextPack, RKNNOutput};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SegConfig {
+    pub yolo: YoloConfig,
+    /// Probability above which a pixel belongs to the instance.
+    pub mask_thresh: f32,
+}
+
+impl Default for SegConfig {
+    fn default() -> Self {
+        SegConfig {
+            yolo: YoloConfig::default(),
+            mask_thresh: 0.5,
+        }
+    }
+}
+
+/// A binary mask covering the pixels `x..x + width`, `y..y + height` of the
+/// source image.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InstanceMask {
+    pub x: u32,
+    pub y: u32,
+    /// `(height, width)`
+    pub data: Array2<bool>,
+}
+
+impl InstanceMask {
+    pub fn contains(&self, x: u32, y: u32) -> bool {
+        let (h, w) = self.data.dim();
+        x >= self.x
+            && y >= self.y
+            && ((x - self.x) as usize) < w
+            && ((y - self.y) as usize) < h
+            && self.data[[(y - self.y) as usize, (x - self.x) as usize]]
+    }
+
+    pub fn area(&self) -> usize {
+        self.data.iter().filter(|&&v| v).count()
+    }
+}
+
+/// A detection in source image coordinates with its mask.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Segmentation {
+    pub detection: Detection,
+    pub mask: InstanceMask,
+}
+
+/// Decode detections and their masks, mapped back through `transform`.
+///
+/// Fails with `RKNN_ERR_PARAM_INVALID` unless every branch has a mask
+/// coefficient output with as many channels as the prototype output.
+pub fn segment(
+    tensors: &[OutputTensor],
+    input_h: usize,
+    input_w: usize,
+    transform: &LetterboxTransform,
+    config: &SegConfig,
+) -> Result<Vec<Segmentation>, i32> {
+    let branches = find_branches(tensors, input_h, input_w);
+    let (proto_idx, coef_idx) =
+        find_masks(tensors, &branches).ok_or(rknpu2_sys::RKNN_ERR_PARAM_INVALID)?;
+    let proto = &tensors[proto_idx];
+    let (proto_c, proto_h, proto_w) = proto.chw();
+    let proto_values = proto.to_f32_vec();
+
+    let candidates = decode(tensors, &branches, &config.yolo);
+    let dets: Vec<Detection> = candidates.iter().map(|(det, _)| *det).collect();
+    let keep = nms(&dets, config.yolo.iou_thresh);
+
+    // compare logits instead of applying sigmoid on every pixel
+    let logit_thresh = (config.mask_thresh / (1.0 - config.mask_thresh)).ln();
+    let to_proto_x = proto_w as f32 / input_w as f32;
+    let to_proto_y = proto_h as f32 / input_h as f32;
+
+    Ok(keep
+        .into_iter()
+        .map(|i| {
+            let (det, anchor) = candidates[i];
+            let coef_tensor = &tensors[coef_idx[anchor.branch]];
+            let coefs: Vec<f32> = (0..proto_c)
+                .map(|k| coef_tensor.at(k, anchor.y, anchor.x))
+                .collect();
+
+            // low resolution logits over the box, one cell of margin for interpolation
+            // boxes are not clipped, they may start past the right or bottom edge
+            let px0 = ((det.bbox.x1 * to_proto_x).floor() as isize - 1)
+                .clamp(0, proto_w as isize - 1) as usize;
+            let py0 = ((det.bbox.y1 * to_proto_y).floor() as isize - 1)
+                .clamp(0, proto_h as isize - 1) as usize;
+            let px1 = ((det.bbox.x2 * to_proto_x).ceil() as usize + 1).clamp(px0 + 1, proto_w);
+            let py1 = ((det.bbox.y2 * to_proto_y).ceil() as usize + 1).clamp(py0 + 1, proto_h);
+            let logits = Array2::from_shape_fn((py1 - py0, px1 - px0), |(y, x)| {
+                coefs
+                    .iter()
+                    .enumerate()
+                    .map(|(k, c)| c * proto_values[proto.index(k, py0 + y, px0 + x)])
+                    .sum::<f32>()
+            });
+
+            let detection = det.to_source(transform);
+            let b = detection.bbox;
+            let (x0, y0) = (b.x1.floor() as u32, b.y1.floor() as u32);
+            let (x1, y1) = (b.x2.ceil() as u32, b.y2.ceil() as u32);
+            let data = Array2::from_shape_fn(((y1 - y0) as usize, (x1 - x0) as usize), |(y, x)| {
+                let (mx, my) = transform.to_model(
+                    (x0 as usize + x) as f32 + 0.5,
+                    (y0 as usize + y) as f32 + 0.5,
+                );
+                if mx < det.bbox.x1 || mx > det.bbox.x2 || my < det.bbox.y1 || my > det.bbox.y2 {
+                    return false;
+                }
+                let px = mx * to_proto_x - 0.5 - px0 as f32;
+                let py = my * to_proto_y - 0.5 - py0 as f32;
+                bilinear(&logits, px, py) > logit_thresh
+            });
+
+            Segmentation {
+                detection,
+                mask: InstanceMask { x: x0, y: y0, data },
+            }
+        })
+        .collect())
+}
+
+/// [`segment`] on the outputs of `rknn_outputs_get`.
+pub fn post_process(
+    ctx: &RKNNContextPack,
+    outputs: &[RKNNOutput],
+    transform: &LetterboxTransform,
+    config: &SegConfig,
+) -> Result<Vec<Segmentation>, i32> {
+    let (input_h, input_w, _) = input_size(ctx.input_info.first().unwrap());
+    // outputs come straight from rknn_outputs_get and match output_info
+    let tensors = unsafe { OutputTensor::from_outputs(&ctx.output_info, outputs)? };
+
+    segment(&tensors, input_h, input_w, transform, config)
+}
+
+/// The prototype output and the mask coefficient output of each branch. The
+/// coefficients are the first further output of a branch, and must have the
+/// same channel count in all of them. The prototype output is the one not
+/// belonging to any branch with that channel count.
+fn find_masks(tensors: &[OutputTensor], branches: &[Branch]) -> Option<(usize, Vec<usize>)> {
+    let coef_idx = branches
+        .iter()
+        .map(|b| b.extra.first().copied())
+        .collect::<Option<Vec<usize>>>()?;
+    let coef_channels = tensors[*coef_idx.first()?].chw().0;
+    if coef_idx
+        .iter()
+        .any(|&i| tensors[i].chw().0 != coef_channels)
+    {
+        return None;
+    }
+    let used = |i: usize| {
+        branches.iter().any(|b| {
+            b.boxes == i || b.scores == i || b.score_sum == Some(i) || b.extra.contains(&i)
+        })
+    };
+
+    let proto_idx =
+        (0..tensors.len()).find(|&i| !used(i) && tensors[i].chw().0 == coef_channels)?;
+
+    Some((proto_idx, coef_idx))
+}
+
+fn bilinear(a: &Array2<f32>, x: f32, y: f32) -> f32 {
+    let (h, w) = a.dim();
+    let x = x.clamp(0.0, (w - 1) as f32);
+    let y = y.clamp(0.0, (h - 1) as f32);
+    let (x0, y0) = (x as usize, y as usize);
+    let (x1, y1) = ((x0 + 1).min(w - 1), (y0 + 1).min(h - 1));
+    let (fx, fy) = (x - x0 as f32, y - y0 as f32);
+
+    let top = a[[y0, x0]] + (a[[y0, x1]] - a[[y0, x0]]) * fx;
+    let bottom = a[[y1, x0]] + (a[[y1, x1]] - a[[y1, x0]]) * fx;
+    top + (bottom - top) * fy
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::postprocess::tests::attr;
+    use crate::preprocess::letterbox::LetterboxAlign;
+
+    struct Head {
+        attrs: Vec<crate::RKNNTensorAttr>,
+        data: Vec<Vec<f32>>,
+    }
+
+    /// 32x32 input, one 2x2 branch with a single box covering the image,
+    /// 2 mask channels on an 8x8 prototype. Channel 0 is positive on the left
+    /// half and the detection only uses channel 0.
+    fn head() -> Head {
+        let attrs = vec![
+            attr(&[1, 4, 2, 2], 0, 1.0),
+            attr(&[1, 1, 2, 2], 0, 1.0),
+            attr(&[1, 2, 2, 2], 0, 1.0),
+            attr(&[1, 2, 8, 8], 0, 1.0),
+        ];
+        let mut boxes = vec![0f32; 16];
+        for (side, d) in [0.5, 0.5, 1.5, 1.5].into_iter().enumerate() {
+            boxes[side * 4] = d;
+        }
+        let scores = vec![0.9, 0.0, 0.0, 0.0];
+        let coefs = vec![1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0];
+        let proto: Vec<f32> = (0..128)
+            .map(|i| if i < 64 && i % 8 < 4 { 5.0 } else { -5.0 })
+            .collect();
+
+        Head {
+            attrs,
+            data: vec![boxes, scores, coefs, proto],
+        }
+    }
+
+    #[test]
+    fn test_segment() {
+        let h = head();
+        let tensors: Vec<_> = h
+            .attrs
+            .iter()
+            .zip(&h.data)
+            .map(|(a, d)| OutputTensor::from_f32(a, d))
+            .collect();
+        let transform = LetterboxTransform::new(32, 32, 32, 32, LetterboxAlign::Center).unwrap();
+
+        let segs = segment(&tensors, 32, 32, &transform, &SegConfig::default()).unwrap();
+        assert_eq!(segs.len(), 1);
+        let mask = &segs[0].mask;
+        assert_eq!((mask.x, mask.y), (0, 0));
+        assert_eq!(mask.data.dim(), (32, 32));
+        assert!(mask.contains(15, 20));
+        assert!(!mask.contains(16, 20));
+        assert_eq!(mask.area(), 16 * 32);
+    }
+
+    #[test]
+    fn test_segment_letterboxed() {
+        let h = head();
+        let tensors: Vec<_> = h
+            .attrs
+            .iter()
+            .zip(&h.data)
+            .map(|(a, d)| OutputTensor::from_f32(a, d))
+            .collect();
+        // a 64x32 source letterboxed into 32x32 with 8 rows of padding
+        let transform = LetterboxTransform::new(64, 32, 32, 32, LetterboxAlign::Center).unwrap();
+
+        let segs = segment(&tensors, 32, 32, &transform, &SegConfig::default()).unwrap();
+        let mask = &segs[0].mask;
+        assert_eq!(segs[0].detection.bbox.y2, 32.0);
+        assert_eq!(mask.data.dim(), (32, 64));
+        assert!(mask.contains(31, 0));
+        assert!(!mask.contains(32, 0));
+    }
+
+    #[test]
+    fn test_segment_box_outside() {
+        let mut h = head();
+        // the box spans x 40..56, right of the 32 wide input
+        h.data[0][0] = -2.0;
+        h.data[0][8] = 3.0;
+        let tensors: Vec<_> = h
+            .attrs
+            .iter()
+            .zip(&h.data)
+            .map(|(a, d)| OutputTensor::from_f32(a, d))
+            .collect();
+        let transform = LetterboxTransform::new(32, 32, 32, 32, LetterboxAlign::Center).unwrap();
+
+        let segs = segment(&tensors, 32, 32, &transform, &SegConfig::default()).unwrap();
+        assert_eq!(segs.len(), 1);
+        assert_eq!(segs[0].mask.area(), 0);
+    }
+
+    #[test]
+    fn test_segment_missing_coefs() {
+        let mut h = head();
+        // a second 1x1 branch with boxes and scores but no coefficients
+        h.attrs.push(attr(&[1, 4, 1, 1], 0, 1.0));
+        h.attrs.push(attr(&[1, 1, 1, 1], 0, 1.0));
+        h.data.push(vec![0.5; 4]);
+        h.data.push(vec![0.9]);
+        let tensors: Vec<_> = h
+            .attrs
+            .iter()
+            .zip(&h.data)
+            .map(|(a, d)| OutputTensor::from_f32(a, d))
+            .collect();
+        let transform = LetterboxTransform::new(32, 32, 32, 32, LetterboxAlign::Center).unwrap();
+
+        assert_eq!(
+            segment(&tensors, 32, 32, &transform, &SegConfig::default()),
+            Err(rknpu2_sys::RKNN_ERR_PARAM_INVALID)
+        );
+
+        // a score sum and coefficients with fewer channels than the first branch
+        h.attrs.push(attr(&[1, 1, 1, 1], 0, 1.0));
+        h.attrs.push(attr(&[1, 1, 1, 1], 0, 1.0));
+        h.data.push(vec![0.9]);
+        h.data.push(vec![1.0]);
+        let tensors: Vec<_> = h
+            .attrs
+            .iter()
+            .zip(&h.data)
+            .map(|(a, d)| OutputTensor::from_f32(a, d))
+            .collect();
+        assert_eq!(
+            segment(&tensors, 32, 32, &transform, &SegConfig::default()),
+            Err(rknpu2_sys::RKNN_ERR_PARAM_INVALID)
+        );
+    }
+}
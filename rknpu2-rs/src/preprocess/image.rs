@@ -0,0 +1,176 @@
+//! Model inputs from the `image` crate, enabled by the `image` feature.
+
+use image::{DynamicImage, ImageBuffer, Pixel};
+use ndarray::prelude::*;
+use std::ffi::c_void;
+use std::mem;
+use std::ops::{Deref, DerefMut};
+
+use crate::{RKNNInput, RKNNTensorAttr};
+
+use super::input_size;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChannelOrder {
+    #[default]
+    Rgb,
+    Bgr,
+}
+
+/// Borrow an image buffer as a `(height, width, channel)` array.
+pub fn buffer_view<P, C>(buffer: &ImageBuffer<P, C>) -> ArrayView3<'_, u8>
+where
+    P: Pixel<Subpixel = u8>,
+    C: Deref<Target = [u8]>,
+{
+    let shape = (
+        buffer.height() as usize,
+        buffer.width() as usize,
+        P::CHANNEL_COUNT as usize,
+    );
+    let len = shape.0 * shape.1 * shape.2;
+
+    ArrayView3::from_shape(shape, &buffer.as_raw()[..len]).unwrap()
+}
+
+/// Mutably borrow an image buffer as a `(height, width, channel)` array.
+pub fn buffer_view_mut<P, C>(buffer: &mut ImageBuffer<P, C>) -> ArrayViewMut3<'_, u8>
+where
+    P: Pixel<Subpixel = u8>,
+    C: Deref<Target = [u8]> + DerefMut,
+{
+    let shape = (
+        buffer.height() as usize,
+        buffer.width() as usize,
+        P::CHANNEL_COUNT as usize,
+    );
+    let len = shape.0 * shape.1 * shape.2;
+    let raw: &mut [u8] = buffer;
+
+    ArrayViewMut3::from_shape(shape, &mut raw[..len]).unwrap()
+}
+
+/// Convert an image to a HWC array with `channels` channels.
+/// Alpha is dropped and grayscale is expanded as needed.
+pub fn image_to_array(image: &DynamicImage, channels: usize, order: ChannelOrder) -> Array3<u8> {
+    let mut arr = match channels {
+        1 => buffer_view(&image.to_luma8()).to_owned(),
+        4 => buffer_view(&image.to_rgba8()).to_owned(),
+        _ => buffer_view(&image.to_rgb8()).to_owned(),
+    };
+
+    if order == ChannelOrder::Bgr && channels >= 3 {
+        for mut pixel in arr.lanes_mut(Axis(2)) {
+            pixel.swap(0, 2);
+        }
+    }
+
+    arr
+}
+
+/// An u8 image laid out the way a model input expects it.
+#[derive(Debug, Clone)]
+pub struct ImageInput {
+    pub index: u32,
+    pub fmt: rknpu2_sys::rknn_tensor_format,
+    /// HWC for NHWC inputs, CHW for NCHW inputs.
+    pub data: Array3<u8>,
+}
+
+impl ImageInput {
+    /// Describe the buffer as an rknn input, the runtime takes care of
+    /// normalization and quantization. The returned input points into `self`.
+    pub fn make_rknn_input(&mut self) -> RKNNInput {
+        let mut input: RKNNInput = unsafe { mem::zeroed() };
+        input.index = self.index;
+        input.type_ = rknpu2_sys::_rknn_tensor_type_RKNN_TENSOR_UINT8;
+        input.fmt = self.fmt;
+        input.size = self.data.len() as u32;
+        input.buf = self.data.as_mut_ptr() as *mut c_void;
+
+        input
+    }
+}
+
+/// Convert an image to the channel count and layout of a model input.
+/// The image must already have the input's size, see
+/// [`letterbox`](super::letterbox::letterbox).
+pub fn image_input(
+    image: &DynamicImage,
+    attr: &RKNNTensorAttr,
+    order: ChannelOrder,
+) -> Result<ImageInput, i32> {
+    let (h, w, c) = input_size(attr);
+    if (image.height() as usize, image.width() as usize) != (h, w) {
+        return Err(rknpu2_sys::RKNN_ERR_PARAM_INVALID);
+    }
+
+    let data = image_to_array(image, c, order);
+    let (fmt, data) = match attr.fmt {
+        rknpu2_sys::_rknn_tensor_format_RKNN_TENSOR_NCHW => (
+            attr.fmt,
+            data.permuted_axes([2, 0, 1])
+                .as_standard_layout()
+                .into_owned(),
+        ),
+        _ => (rknpu2_sys::_rknn_tensor_format_RKNN_TENSOR_NHWC, data),
+    };
+
+    Ok(ImageInput {
+        index: attr.index,
+        fmt,
+        data,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{GrayImage, Rgb, RgbImage, Rgba, RgbaImage};
+
+    #[test]
+    fn test_buffer_view_is_hwc() {
+        let mut img = RgbImage::new(3, 2);
+        img.put_pixel(2, 1, Rgb([1, 2, 3]));
+        let view = buffer_view(&img);
+        assert_eq!(view.shape(), &[2, 3, 3]);
+        assert_eq!(view.slice(s![1, 2, ..]), arr1(&[1, 2, 3]));
+
+        buffer_view_mut(&mut img)[[0, 1, 2]] = 9;
+        assert_eq!(img.get_pixel(1, 0), &Rgb([0, 0, 9]));
+    }
+
+    #[test]
+    fn test_image_to_array() {
+        let mut img = RgbaImage::new(2, 1);
+        img.put_pixel(1, 0, Rgba([10, 20, 30, 40]));
+        let img = DynamicImage::ImageRgba8(img);
+
+        let rgb = image_to_array(&img, 3, ChannelOrder::Rgb);
+        assert_eq!(rgb.slice(s![0, 1, ..]), arr1(&[10, 20, 30]));
+        let bgr = image_to_array(&img, 3, ChannelOrder::Bgr);
+        assert_eq!(bgr.slice(s![0, 1, ..]), arr1(&[30, 20, 10]));
+
+        let gray = DynamicImage::ImageLuma8(GrayImage::from_raw(2, 1, vec![7, 8]).unwrap());
+        let expanded = image_to_array(&gray, 3, ChannelOrder::Rgb);
+        assert_eq!(expanded.slice(s![0, 1, ..]), arr1(&[8, 8, 8]));
+    }
+
+    #[test]
+    fn test_image_input_layout() {
+        let mut attr: RKNNTensorAttr = unsafe { mem::zeroed() };
+        attr.dims[..4].copy_from_slice(&[1, 3, 2, 4]);
+        attr.fmt = rknpu2_sys::_rknn_tensor_format_RKNN_TENSOR_NCHW;
+
+        let mut img = RgbImage::new(4, 2);
+        img.put_pixel(3, 1, Rgb([1, 2, 3]));
+        let mut input =
+            image_input(&DynamicImage::ImageRgb8(img), &attr, ChannelOrder::Rgb).unwrap();
+        assert_eq!(input.data.shape(), &[3, 2, 4]);
+        assert_eq!(input.data[[2, 1, 3]], 3);
+        assert_eq!(input.make_rknn_input().size, 24);
+
+        let small = DynamicImage::ImageRgb8(RgbImage::new(2, 2));
+        assert!(image_input(&small, &attr, ChannelOrder::Rgb).is_err());
+    }
+}
@@ -0,0 +1,266 @@
+//! Aspect ratio preserving resize ("letterbox") and the inverse mapping back
+//! to source image coordinates.
+
+use ndarray::prelude::*;
+
+/// Where the resized image is placed inside the padded canvas.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LetterboxAlign {
+    /// Pad evenly on both sides.
+    #[default]
+    Center,
+    /// Keep the image at the origin, pad only right and bottom.
+    TopLeft,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LetterboxConfig {
+    /// Pad color, single channel images use the first component.
+    pub pad_color: [u8; 3],
+    pub align: LetterboxAlign,
+}
+
+impl Default for LetterboxConfig {
+    fn default() -> Self {
+        LetterboxConfig {
+            pad_color: [114, 114, 114],
+            align: LetterboxAlign::Center,
+        }
+    }
+}
+
+/// Geometry of a letterbox operation, used to map model space coordinates
+/// (boxes, keypoints, masks) back to the source image.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LetterboxTransform {
+    pub src_width: u32,
+    pub src_height: u32,
+    pub dst_width: u32,
+    pub dst_height: u32,
+    /// Size of the image content inside the canvas.
+    pub resized_width: u32,
+    pub resized_height: u32,
+    /// Offset of the image content inside the canvas.
+    pub pad_x: u32,
+    pub pad_y: u32,
+    pub scale_x: f32,
+    pub scale_y: f32,
+}
+
+impl LetterboxTransform {
+    /// Fails with `RKNN_ERR_PARAM_INVALID` if either size is empty.
+    pub fn new(
+        src_width: u32,
+        src_height: u32,
+        dst_width: u32,
+        dst_height: u32,
+        align: LetterboxAlign,
+    ) -> Result<Self, i32> {
+        if src_width == 0 || src_height == 0 || dst_width == 0 || dst_height == 0 {
+            return Err(rknpu2_sys::RKNN_ERR_PARAM_INVALID);
+        }
+
+        let scale =
+            (dst_width as f32 / src_width as f32).min(dst_height as f32 / src_height as f32);
+        let resized_width = ((src_width as f32 * scale).round() as u32).clamp(1, dst_width);
+        let resized_height = ((src_height as f32 * scale).round() as u32).clamp(1, dst_height);
+
+        let (pad_x, pad_y) = match align {
+            LetterboxAlign::Center => (
+                (dst_width - resized_width) / 2,
+                (dst_height - resized_height) / 2,
+            ),
+            LetterboxAlign::TopLeft => (0, 0),
+        };
+
+        Ok(LetterboxTransform {
+            src_width,
+            src_height,
+            dst_width,
+            dst_height,
+            resized_width,
+            resized_height,
+            pad_x,
+            pad_y,
+            scale_x: resized_width as f32 / src_width as f32,
+            scale_y: resized_height as f32 / src_height as f32,
+        })
+    }
+
+    /// Map a point from source image to model input coordinates.
+    pub fn to_model(&self, x: f32, y: f32) -> (f32, f32) {
+        (
+            x * self.scale_x + self.pad_x as f32,
+            y * self.scale_y + self.pad_y as f32,
+        )
+    }
+
+    /// Map a point from model input to source image coordinates.
+    pub fn to_source(&self, x: f32, y: f32) -> (f32, f32) {
+        (
+            (x - self.pad_x as f32) / self.scale_x,
+            (y - self.pad_y as f32) / self.scale_y,
+        )
+    }
+
+    /// Map a `(x1, y1, x2, y2)` box to source image coordinates, clipped to
+    /// the image bounds.
+    pub fn rect_to_source(&self, rect: (f32, f32, f32, f32)) -> (f32, f32, f32, f32) {
+        let (x1, y1) = self.to_source(rect.0, rect.1);
+        let (x2, y2) = self.to_source(rect.2, rect.3);
+        let (w, h) = (self.src_width as f32, self.src_height as f32);
+
+        (
+            x1.clamp(0.0, w),
+            y1.clamp(0.0, h),
+            x2.clamp(0.0, w),
+            y2.clamp(0.0, h),
+        )
+    }
+}
+
+/// Letterbox a HWC image into a new `(dst_height, dst_width, C)` array.
+pub fn letterbox(
+    src: ArrayView3<u8>,
+    dst_height: usize,
+    dst_width: usize,
+    config: &LetterboxConfig,
+) -> Result<(Array3<u8>, LetterboxTransform), i32> {
+    let mut dst = Array3::zeros((dst_height, dst_width, src.shape()[2]));
+    let transform = letterbox_into(src, dst.view_mut(), config)?;
+
+    Ok((dst, transform))
+}
+
+/// Letterbox a HWC image into an existing HWC buffer with the same channel count.
+pub fn letterbox_into(
+    src: ArrayView3<u8>,
+    mut dst: ArrayViewMut3<u8>,
+    config: &LetterboxConfig,
+) -> Result<LetterboxTransform, i32> {
+    let (src_h, src_w, _) = src.dim();
+    let (dst_h, dst_w, _) = dst.dim();
+    let t = LetterboxTransform::new(
+        src_w as u32,
+        src_h as u32,
+        dst_w as u32,
+        dst_h as u32,
+        config.align,
+    )?;
+
+    for mut pixel in dst.lanes_mut(Axis(2)) {
+        for (c, v) in pixel.iter_mut().enumerate() {
+            *v = config.pad_color[c.min(2)];
+        }
+    }
+
+    let (x0, y0) = (t.pad_x as usize, t.pad_y as usize);
+    let content = dst.slice_mut(s![
+        y0..y0 + t.resized_height as usize,
+        x0..x0 + t.resized_width as usize,
+        ..
+    ]);
+    resize_bilinear(src, content);
+
+    Ok(t)
+}
+
+/// Bilinear resize of a HWC image into `dst`, using half pixel centers.
+pub fn resize_bilinear(src: ArrayView3<u8>, mut dst: ArrayViewMut3<u8>) {
+    let (src_h, src_w, channels) = src.dim();
+    let (dst_h, dst_w, dst_c) = dst.dim();
+    assert_eq!(channels, dst_c, "channel count mismatch");
+
+    let xs = sample_positions(src_w, dst_w);
+    let ys = sample_positions(src_h, dst_h);
+
+    for (y, &(y0, y1, fy)) in ys.iter().enumerate() {
+        for (x, &(x0, x1, fx)) in xs.iter().enumerate() {
+            for c in 0..channels {
+                let top = lerp(src[[y0, x0, c]], src[[y0, x1, c]], fx);
+                let bottom = lerp(src[[y1, x0, c]], src[[y1, x1, c]], fx);
+                let v = top + (bottom - top) * fy;
+                dst[[y, x, c]] = (v + 0.5).clamp(0.0, 255.0) as u8;
+            }
+        }
+    }
+}
+
+/// For each destination index, the two neighbouring source indices and the
+/// weight of the second one.
+pub(crate) fn sample_positions(src_len: usize, dst_len: usize) -> Vec<(usize, usize, f32)> {
+    let ratio = src_len as f32 / dst_len as f32;
+    let last = src_len - 1;
+
+    (0..dst_len)
+        .map(|i| {
+            let pos = ((i as f32 + 0.5) * ratio - 0.5).max(0.0);
+            let i0 = (pos as usize).min(last);
+            let i1 = (i0 + 1).min(last);
+            (i0, i1, pos - i0 as f32)
+        })
+        .collect()
+}
+
+fn lerp(a: u8, b: u8, t: f32) -> f32 {
+    a as f32 + (b as f32 - a as f32) * t
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_letterbox_transform() {
+        let t = LetterboxTransform::new(810, 1080, 640, 640, LetterboxAlign::Center).unwrap();
+        assert_eq!((t.resized_width, t.resized_height), (480, 640));
+        assert_eq!((t.pad_x, t.pad_y), (80, 0));
+
+        let (mx, my) = t.to_model(405.0, 540.0);
+        assert_eq!((mx, my), (320.0, 320.0));
+        let (sx, sy) = t.to_source(mx, my);
+        assert!((sx - 405.0).abs() < 1e-3 && (sy - 540.0).abs() < 1e-3);
+
+        let rect = t.rect_to_source((0.0, 10.0, 700.0, 320.0));
+        assert_eq!(rect, (0.0, 16.875, 810.0, 540.0));
+    }
+
+    #[test]
+    fn test_letterbox_pad() {
+        let src = Array3::from_elem((20, 40, 3), 200u8);
+        let config = LetterboxConfig {
+            pad_color: [1, 2, 3],
+            align: LetterboxAlign::TopLeft,
+        };
+        let (dst, t) = letterbox(src.view(), 16, 16, &config).unwrap();
+
+        assert_eq!((t.resized_width, t.resized_height), (16, 8));
+        assert_eq!(
+            dst.slice(s![0..8, .., ..]),
+            Array3::from_elem((8, 16, 3), 200u8)
+        );
+        assert_eq!(dst.slice(s![8, 0, ..]), arr1(&[1, 2, 3]));
+
+        let (dst, t) = letterbox(src.view(), 16, 16, &LetterboxConfig::default()).unwrap();
+        assert_eq!(t.pad_y, 4);
+        assert_eq!(dst[[3, 5, 0]], 114);
+        assert_eq!(dst[[4, 5, 0]], 200);
+        assert_eq!(dst[[12, 5, 0]], 114);
+
+        assert!(letterbox(src.view(), 0, 16, &config).is_err());
+        assert!(LetterboxTransform::new(0, 20, 16, 16, LetterboxAlign::Center).is_err());
+    }
+
+    #[test]
+    fn test_resize_bilinear() {
+        let src = Array3::from_shape_fn((2, 2, 1), |(y, x, _)| (y * 2 + x) as u8 * 50);
+        let mut dst = Array3::zeros((4, 4, 1));
+        resize_bilinear(src.view(), dst.view_mut());
+
+        assert_eq!(dst[[0, 0, 0]], 0);
+        assert_eq!(dst[[0, 3, 0]], 50);
+        assert_eq!(dst[[3, 3, 0]], 150);
+        assert_eq!(dst[[0, 1, 0]], 13);
+        assert_eq!(dst[[1, 1, 0]], 38);
+    }
+}
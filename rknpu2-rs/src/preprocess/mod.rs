@@ -0,0 +1,20 @@
+//! Image preprocessing before feeding a model.
+
+#[cfg(feature = "image")]
+pub mod image;
+pub mod letterbox;
+pub mod normalize;
+pub mod yuv;
+
+use crate::RKNNTensorAttr;
+
+/// Get `(height, width, channel)` of a model input from its attribute.
+pub fn input_size(attr: &RKNNTensorAttr) -> (usize, usize, usize) {
+    let dims = &attr.dims;
+    match attr.fmt {
+        rknpu2_sys::_rknn_tensor_format_RKNN_TENSOR_NCHW => {
+            (dims[2] as usize, dims[3] as usize, dims[1] as usize)
+        }
+        _ => (dims[1] as usize, dims[2] as usize, dims[3] as usize),
+    }
+}
@@ -0,0 +1,225 @@
+//! `(x - mean) / std` normalization for models without baked-in normalization.
+//!
+//! The result is written in the type and layout of the model input tensor
+//! (float32, float16 or quantized int8/uint8) and passed through to the NPU
+//! without any further conversion by the runtime.
+
+use ndarray::prelude::*;
+use std::ffi::c_void;
+use std::mem;
+
+use crate::quant::{f32_slice_to_f16, quantize};
+use crate::{RKNNContextPack, RKNNInput, RKNNTensorAttr};
+
+use super::input_size;
+
+/// Per channel mean and std, a single value applies to every channel.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Normalization {
+    pub mean: Vec<f32>,
+    pub std: Vec<f32>,
+}
+
+impl Normalization {
+    pub fn new(mean: &[f32], std: &[f32]) -> Self {
+        Normalization {
+            mean: mean.to_vec(),
+            std: std.to_vec(),
+        }
+    }
+
+    /// ImageNet statistics for 0..=255 RGB input.
+    pub fn imagenet() -> Self {
+        Self::new(&[123.675, 116.28, 103.53], &[58.395, 57.12, 57.375])
+    }
+
+    fn channel(values: &[f32], c: usize) -> f32 {
+        if values.len() == 1 {
+            values[0]
+        } else {
+            values[c]
+        }
+    }
+}
+
+/// A normalized input buffer matching the tensor attribute it was made for.
+#[derive(Debug, Clone)]
+pub struct NormalizedInput {
+    pub index: u32,
+    pub type_: rknpu2_sys::rknn_tensor_type,
+    pub fmt: rknpu2_sys::rknn_tensor_format,
+    pub data: Vec<u8>,
+}
+
+impl NormalizedInput {
+    /// Describe the buffer as an rknn input with `pass_through` set.
+    /// The returned input points into `self`, keep it alive until
+    /// `rknn_inputs_set` returns.
+    pub fn make_rknn_input(&mut self) -> RKNNInput {
+        let mut input: RKNNInput = unsafe { mem::zeroed() };
+        input.index = self.index;
+        input.type_ = self.type_;
+        input.fmt = self.fmt;
+        input.size = self.data.len() as u32;
+        input.pass_through = 1;
+        input.buf = self.data.as_mut_ptr() as *mut c_void;
+
+        input
+    }
+}
+
+/// Normalize a HWC image for input `index` of the model.
+pub fn normalize_input(
+    ctx: &RKNNContextPack,
+    index: usize,
+    image: ArrayView3<u8>,
+    norm: &Normalization,
+) -> Result<NormalizedInput, i32> {
+    let attr = ctx
+        .input_info
+        .get(index)
+        .ok_or(rknpu2_sys::RKNN_ERR_PARAM_INVALID)?;
+
+    normalize_for_attr(attr, image, norm)
+}
+
+/// Normalize a HWC image for the given input tensor attribute.
+pub fn normalize_for_attr(
+    attr: &RKNNTensorAttr,
+    image: ArrayView3<u8>,
+    norm: &Normalization,
+) -> Result<NormalizedInput, i32> {
+    let (h, w, c) = input_size(attr);
+    let valid_norm = |v: &[f32]| v.len() == 1 || v.len() == c;
+    if image.dim() != (h, w, c) || !valid_norm(&norm.mean) || !valid_norm(&norm.std) {
+        return Err(rknpu2_sys::RKNN_ERR_PARAM_INVALID);
+    }
+
+    let image = match attr.fmt {
+        rknpu2_sys::_rknn_tensor_format_RKNN_TENSOR_NCHW => image.permuted_axes([2, 0, 1]),
+        _ => image,
+    };
+    let channel_axis = match attr.fmt {
+        rknpu2_sys::_rknn_tensor_format_RKNN_TENSOR_NCHW => 0,
+        _ => 2,
+    };
+
+    // iterating in logical order follows the tensor layout
+    let values: Vec<f32> = image
+        .indexed_iter()
+        .map(|(idx, &v)| {
+            let ch = [idx.0, idx.1, idx.2][channel_axis];
+            (v as f32 - Normalization::channel(&norm.mean, ch))
+                / Normalization::channel(&norm.std, ch)
+        })
+        .collect();
+
+    let data = match attr.type_ {
+        rknpu2_sys::_rknn_tensor_type_RKNN_TENSOR_FLOAT32 => {
+            values.iter().flat_map(|v| v.to_ne_bytes()).collect()
+        }
+        rknpu2_sys::_rknn_tensor_type_RKNN_TENSOR_FLOAT16 => {
+            let mut half = vec![0u16; values.len()];
+            f32_slice_to_f16(&values, &mut half);
+            half.iter().flat_map(|v| v.to_ne_bytes()).collect()
+        }
+        rknpu2_sys::_rknn_tensor_type_RKNN_TENSOR_INT8 => {
+            let mut q = vec![0i8; values.len()];
+            quantize(&values, attr.zp, attr.scale, &mut q);
+            q.iter().map(|&v| v as u8).collect()
+        }
+        rknpu2_sys::_rknn_tensor_type_RKNN_TENSOR_UINT8 => values
+            .iter()
+            .map(|&v| (v / attr.scale + attr.zp as f32).clamp(0.0, 255.0) as u8)
+            .collect(),
+        _ => return Err(rknpu2_sys::RKNN_ERR_PARAM_INVALID),
+    };
+
+    Ok(NormalizedInput {
+        index: attr.index,
+        type_: attr.type_,
+        fmt: attr.fmt,
+        data,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attr(fmt: u32, type_: u32, dims: [u32; 4]) -> RKNNTensorAttr {
+        let mut attr: RKNNTensorAttr = unsafe { mem::zeroed() };
+        attr.n_dims = 4;
+        attr.dims[..4].copy_from_slice(&dims);
+        attr.fmt = fmt;
+        attr.type_ = type_;
+        attr.zp = -128;
+        attr.scale = 1.0 / 64.0;
+        attr
+    }
+
+    #[test]
+    fn test_normalize_int8_nhwc() {
+        let attr = attr(
+            rknpu2_sys::_rknn_tensor_format_RKNN_TENSOR_NHWC,
+            rknpu2_sys::_rknn_tensor_type_RKNN_TENSOR_INT8,
+            [1, 1, 2, 2],
+        );
+        let image = ndarray::arr3(&[[[0u8, 255], [128, 64]]]);
+        let norm = Normalization::new(&[0.0, 64.0], &[255.0, 64.0]);
+        let mut input = normalize_for_attr(&attr, image.view(), &norm).unwrap();
+
+        // 0.0, 2.984, 0.502, 0.0 with truncation like the rknn reference code
+        let expected = [-128i8, 63, -95, -128];
+        assert_eq!(input.data, expected.map(|v| v as u8));
+
+        let rknn_input = input.make_rknn_input();
+        assert_eq!(rknn_input.pass_through, 1);
+        assert_eq!(rknn_input.size, 4);
+    }
+
+    #[test]
+    fn test_normalize_float_nchw() {
+        let image = Array3::from_shape_fn((2, 3, 3), |(y, x, c)| (y * 30 + x * 3 + c) as u8);
+        let norm = Normalization::new(&[1.0, 2.0, 3.0], &[2.0]);
+
+        let attr32 = attr(
+            rknpu2_sys::_rknn_tensor_format_RKNN_TENSOR_NCHW,
+            rknpu2_sys::_rknn_tensor_type_RKNN_TENSOR_FLOAT32,
+            [1, 3, 2, 3],
+        );
+        let input = normalize_for_attr(&attr32, image.view(), &norm).unwrap();
+        let values: Vec<f32> = input
+            .data
+            .chunks(4)
+            .map(|b| f32::from_ne_bytes(b.try_into().unwrap()))
+            .collect();
+        assert_eq!(values.len(), 18);
+        // channel 1 of pixel (y=1, x=2) is 37
+        assert_eq!(values[6 + 5], (37.0 - 2.0) / 2.0);
+
+        let attr16 = attr(
+            rknpu2_sys::_rknn_tensor_format_RKNN_TENSOR_NCHW,
+            rknpu2_sys::_rknn_tensor_type_RKNN_TENSOR_FLOAT16,
+            [1, 3, 2, 3],
+        );
+        let input = normalize_for_attr(&attr16, image.view(), &norm).unwrap();
+        assert_eq!(input.data.len(), 36);
+        assert_eq!(
+            u16::from_ne_bytes([input.data[22], input.data[23]]),
+            crate::quant::f32_to_f16(17.5)
+        );
+    }
+
+    #[test]
+    fn test_normalize_shape_mismatch() {
+        let attr = attr(
+            rknpu2_sys::_rknn_tensor_format_RKNN_TENSOR_NHWC,
+            rknpu2_sys::_rknn_tensor_type_RKNN_TENSOR_INT8,
+            [1, 4, 4, 3],
+        );
+        let image = Array3::zeros((4, 5, 3));
+        let norm = Normalization::imagenet();
+        assert!(normalize_for_attr(&attr, image.view(), &norm).is_err());
+    }
+}
@@ -0,0 +1,537 @@
+//! YUV to RGB conversion for frames coming from hardware decoders and cameras.
+//!
+//! Cropping and resizing are fused into the conversion so a decoded frame can
+//! be turned into a model input in a single pass.
+
+use ndarray::prelude::*;
+
+use super::letterbox::{LetterboxConfig, LetterboxTransform};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum YuvFormat {
+    /// Y plane followed by interleaved UV plane (4:2:0).
+    Nv12,
+    /// Y plane followed by interleaved VU plane (4:2:0).
+    Nv21,
+    /// Y, U and V planes (4:2:0).
+    I420,
+    /// Packed Y0 U Y1 V (4:2:2).
+    Yuyv,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorSpace {
+    #[default]
+    Bt601,
+    Bt709,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum YuvRange {
+    /// Y in 16..=235, UV in 16..=240.
+    #[default]
+    Limited,
+    /// Y and UV in 0..=255.
+    Full,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RgbOrder {
+    #[default]
+    Rgb,
+    Bgr,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct YuvConversion {
+    pub color_space: ColorSpace,
+    pub range: YuvRange,
+    pub order: RgbOrder,
+}
+
+/// Source region of a frame, in pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CropRect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+/// A borrowed YUV frame.
+#[derive(Debug, Clone, Copy)]
+pub struct YuvFrame<'a> {
+    pub data: &'a [u8],
+    pub width: usize,
+    pub height: usize,
+    pub format: YuvFormat,
+    /// Bytes per row of the Y plane (of the packed plane for YUYV).
+    pub stride: usize,
+    /// Rows of the Y plane before the chroma plane starts.
+    pub height_stride: usize,
+}
+
+impl<'a> YuvFrame<'a> {
+    /// Wrap a tightly packed frame.
+    pub fn new(
+        data: &'a [u8],
+        width: usize,
+        height: usize,
+        format: YuvFormat,
+    ) -> Result<Self, i32> {
+        let stride = match format {
+            YuvFormat::Yuyv => width * 2,
+            _ => width,
+        };
+        Self::with_strides(data, width, height, format, stride, height)
+    }
+
+    /// Wrap a frame with padded rows, as produced by most hardware decoders.
+    pub fn with_strides(
+        data: &'a [u8],
+        width: usize,
+        height: usize,
+        format: YuvFormat,
+        stride: usize,
+        height_stride: usize,
+    ) -> Result<Self, i32> {
+        let frame = YuvFrame {
+            data,
+            width,
+            height,
+            format,
+            stride,
+            height_stride,
+        };
+
+        let min_stride = match format {
+            YuvFormat::Nv12 | YuvFormat::Nv21 => width.div_ceil(2) * 2,
+            YuvFormat::I420 => width,
+            YuvFormat::Yuyv => width.div_ceil(2) * 4,
+        };
+        if width == 0
+            || height == 0
+            || stride < min_stride
+            || height_stride < height
+            || data.len() < frame.required_len()
+        {
+            return Err(rknpu2_sys::RKNN_ERR_PARAM_INVALID);
+        }
+
+        Ok(frame)
+    }
+
+    fn required_len(&self) -> usize {
+        let luma = self.stride * self.height_stride;
+        let chroma_rows = self.height.div_ceil(2);
+        match self.format {
+            YuvFormat::Nv12 | YuvFormat::Nv21 => luma + self.stride * chroma_rows,
+            YuvFormat::I420 => {
+                let c_stride = self.stride.div_ceil(2);
+                let v_offset = luma + c_stride * self.height_stride.div_ceil(2);
+                v_offset + c_stride * (chroma_rows - 1) + self.width.div_ceil(2)
+            }
+            YuvFormat::Yuyv => self.stride * (self.height - 1) + self.width.div_ceil(2) * 4,
+        }
+    }
+
+    /// Size of the chroma planes as `(width, height)`.
+    fn chroma_size(&self) -> (usize, usize) {
+        match self.format {
+            YuvFormat::Yuyv => (self.width.div_ceil(2), self.height),
+            _ => (self.width.div_ceil(2), self.height.div_ceil(2)),
+        }
+    }
+
+    fn luma(&self, x: usize, y: usize) -> u8 {
+        match self.format {
+            YuvFormat::Yuyv => self.data[y * self.stride + x * 2],
+            _ => self.data[y * self.stride + x],
+        }
+    }
+
+    /// `(u, v)` sample at chroma plane coordinates.
+    fn chroma(&self, cx: usize, cy: usize) -> (u8, u8) {
+        let luma = self.stride * self.height_stride;
+        match self.format {
+            YuvFormat::Nv12 | YuvFormat::Nv21 => {
+                let i = luma + cy * self.stride + cx * 2;
+                let (a, b) = (self.data[i], self.data[i + 1]);
+                if self.format == YuvFormat::Nv12 {
+                    (a, b)
+                } else {
+                    (b, a)
+                }
+            }
+            YuvFormat::I420 => {
+                let c_stride = self.stride.div_ceil(2);
+                let v_offset = luma + c_stride * self.height_stride.div_ceil(2);
+                let i = cy * c_stride + cx;
+                (self.data[luma + i], self.data[v_offset + i])
+            }
+            YuvFormat::Yuyv => {
+                let i = cy * self.stride + cx * 4;
+                (self.data[i + 1], self.data[i + 3])
+            }
+        }
+    }
+}
+
+/// Convert a whole frame into a `(height, width, 3)` buffer.
+///
+/// Fails with `RKNN_ERR_PARAM_INVALID` unless `dst` has 3 channels.
+pub fn yuv_to_rgb(
+    frame: &YuvFrame,
+    conversion: &YuvConversion,
+    dst: ArrayViewMut3<u8>,
+) -> Result<(), i32> {
+    let crop = CropRect {
+        x: 0,
+        y: 0,
+        width: frame.width,
+        height: frame.height,
+    };
+    yuv_to_rgb_resized(frame, conversion, crop, dst)
+}
+
+/// Crop a region of the frame, resize it to the size of `dst` and convert it,
+/// all in one pass.
+///
+/// Fails with `RKNN_ERR_PARAM_INVALID` for an empty crop or one reaching
+/// past the frame, and unless `dst` has 3 channels.
+pub fn yuv_to_rgb_resized(
+    frame: &YuvFrame,
+    conversion: &YuvConversion,
+    crop: CropRect,
+    mut dst: ArrayViewMut3<u8>,
+) -> Result<(), i32> {
+    let in_frame = crop.width > 0
+        && crop.height > 0
+        && crop.x < frame.width
+        && crop.y < frame.height
+        && crop.width <= frame.width - crop.x
+        && crop.height <= frame.height - crop.y;
+    let (dst_h, dst_w, channels) = dst.dim();
+    if !in_frame || channels != 3 {
+        return Err(rknpu2_sys::RKNN_ERR_PARAM_INVALID);
+    }
+
+    let (chroma_w, chroma_h) = frame.chroma_size();
+    let (sub_x, sub_y) = (
+        frame.width.div_ceil(chroma_w),
+        frame.height.div_ceil(chroma_h),
+    );
+    let lx = positions(crop.x, crop.width, dst_w, 1, frame.width);
+    let ly = positions(crop.y, crop.height, dst_h, 1, frame.height);
+    let cx = positions(crop.x, crop.width, dst_w, sub_x, chroma_w);
+    let cy = positions(crop.y, crop.height, dst_h, sub_y, chroma_h);
+    let coef = Coefficients::new(conversion);
+
+    for y in 0..dst_h {
+        let (ly0, ly1, lfy) = ly[y];
+        let (cy0, cy1, cfy) = cy[y];
+        for x in 0..dst_w {
+            let (lx0, lx1, lfx) = lx[x];
+            let (cx0, cx1, cfx) = cx[x];
+
+            let luma = bilerp(
+                [
+                    frame.luma(lx0, ly0),
+                    frame.luma(lx1, ly0),
+                    frame.luma(lx0, ly1),
+                    frame.luma(lx1, ly1),
+                ],
+                lfx,
+                lfy,
+            );
+            let c = [
+                frame.chroma(cx0, cy0),
+                frame.chroma(cx1, cy0),
+                frame.chroma(cx0, cy1),
+                frame.chroma(cx1, cy1),
+            ];
+            let u = bilerp(c.map(|c| c.0), cfx, cfy);
+            let v = bilerp(c.map(|c| c.1), cfx, cfy);
+
+            let rgb = coef.apply(luma, u, v);
+            dst[[y, x, 0]] = rgb[0];
+            dst[[y, x, 1]] = rgb[1];
+            dst[[y, x, 2]] = rgb[2];
+        }
+    }
+
+    Ok(())
+}
+
+/// Letterbox a frame into `dst` while converting it, see
+/// [`letterbox_into`](super::letterbox::letterbox_into).
+pub fn yuv_letterbox_into(
+    frame: &YuvFrame,
+    conversion: &YuvConversion,
+    mut dst: ArrayViewMut3<u8>,
+    config: &LetterboxConfig,
+) -> Result<LetterboxTransform, i32> {
+    let (dst_h, dst_w, _) = dst.dim();
+    let t = LetterboxTransform::new(
+        frame.width as u32,
+        frame.height as u32,
+        dst_w as u32,
+        dst_h as u32,
+        config.align,
+    )?;
+
+    for mut pixel in dst.lanes_mut(Axis(2)) {
+        for (c, v) in pixel.iter_mut().enumerate() {
+            *v = config.pad_color[c.min(2)];
+        }
+    }
+
+    let (x0, y0) = (t.pad_x as usize, t.pad_y as usize);
+    let content = dst.slice_mut(s![
+        y0..y0 + t.resized_height as usize,
+        x0..x0 + t.resized_width as usize,
+        ..
+    ]);
+    yuv_to_rgb(frame, conversion, content)?;
+
+    Ok(t)
+}
+
+/// Per destination index: the two source indices in a plane subsampled by
+/// `sub` and the weight of the second one.
+fn positions(
+    offset: usize,
+    len: usize,
+    dst_len: usize,
+    sub: usize,
+    plane_len: usize,
+) -> Vec<(usize, usize, f32)> {
+    let ratio = len as f32 / dst_len as f32;
+    let last = plane_len - 1;
+
+    (0..dst_len)
+        .map(|i| {
+            let pos = offset as f32 + (i as f32 + 0.5) * ratio;
+            let pos = (pos / sub as f32 - 0.5).max(0.0);
+            let i0 = (pos as usize).min(last);
+            let i1 = (i0 + 1).min(last);
+            (i0, i1, pos - i0 as f32)
+        })
+        .collect()
+}
+
+fn bilerp(v: [u8; 4], fx: f32, fy: f32) -> f32 {
+    let top = v[0] as f32 + (v[1] as f32 - v[0] as f32) * fx;
+    let bottom = v[2] as f32 + (v[3] as f32 - v[2] as f32) * fx;
+    top + (bottom - top) * fy
+}
+
+struct Coefficients {
+    y_offset: f32,
+    y_scale: f32,
+    c_scale: f32,
+    r_v: f32,
+    g_u: f32,
+    g_v: f32,
+    b_u: f32,
+    order: RgbOrder,
+}
+
+impl Coefficients {
+    fn new(conversion: &YuvConversion) -> Self {
+        let (kr, kb) = match conversion.color_space {
+            ColorSpace::Bt601 => (0.299f32, 0.114f32),
+            ColorSpace::Bt709 => (0.2126f32, 0.0722f32),
+        };
+        let kg = 1.0 - kr - kb;
+        let (y_offset, y_scale, c_scale) = match conversion.range {
+            YuvRange::Limited => (16.0, 255.0 / 219.0, 255.0 / 224.0),
+            YuvRange::Full => (0.0, 1.0, 1.0),
+        };
+
+        Coefficients {
+            y_offset,
+            y_scale,
+            c_scale,
+            r_v: 2.0 * (1.0 - kr),
+            g_u: 2.0 * kb * (1.0 - kb) / kg,
+            g_v: 2.0 * kr * (1.0 - kr) / kg,
+            b_u: 2.0 * (1.0 - kb),
+            order: conversion.order,
+        }
+    }
+
+    fn apply(&self, y: f32, u: f32, v: f32) -> [u8; 3] {
+        let y = (y - self.y_offset) * self.y_scale;
+        let u = (u - 128.0) * self.c_scale;
+        let v = (v - 128.0) * self.c_scale;
+
+        let r = to_u8(y + self.r_v * v);
+        let g = to_u8(y - self.g_u * u - self.g_v * v);
+        let b = to_u8(y + self.b_u * u);
+
+        match self.order {
+            RgbOrder::Rgb => [r, g, b],
+            RgbOrder::Bgr => [b, g, r],
+        }
+    }
+}
+
+fn to_u8(v: f32) -> u8 {
+    (v + 0.5).clamp(0.0, 255.0) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 4x2 frame, left half one color, right half another.
+    fn planes(y: [u8; 2], u: [u8; 2], v: [u8; 2]) -> (Vec<u8>, Vec<u8>, Vec<u8>) {
+        let luma = vec![y[0], y[0], y[1], y[1], y[0], y[0], y[1], y[1]];
+        (luma, u.to_vec(), v.to_vec())
+    }
+
+    #[test]
+    fn test_formats_agree() {
+        let (y, u, v) = planes([81, 145], [90, 54], [240, 34]);
+        let nv12: Vec<u8> = [y.clone(), vec![u[0], v[0], u[1], v[1]]].concat();
+        let nv21: Vec<u8> = [y.clone(), vec![v[0], u[0], v[1], u[1]]].concat();
+        let i420: Vec<u8> = [y.clone(), u.clone(), v.clone()].concat();
+        let yuyv: Vec<u8> = (0..2)
+            .flat_map(|_| [y[0], u[0], y[0], v[0], y[2], u[1], y[2], v[1]])
+            .collect();
+
+        let conversion = YuvConversion::default();
+        let convert = |data: &[u8], format| {
+            let frame = YuvFrame::new(data, 4, 2, format).unwrap();
+            let mut dst = Array3::zeros((2, 4, 3));
+            yuv_to_rgb(&frame, &conversion, dst.view_mut()).unwrap();
+            dst
+        };
+
+        let expected = convert(&nv12, YuvFormat::Nv12);
+        assert_eq!(convert(&nv21, YuvFormat::Nv21), expected);
+        assert_eq!(convert(&i420, YuvFormat::I420), expected);
+        assert_eq!(convert(&yuyv, YuvFormat::Yuyv), expected);
+
+        // BT.601 limited range red and green
+        assert_eq!(expected.slice(s![0, 0, ..]), arr1(&[254, 0, 0]));
+        assert_eq!(expected.slice(s![1, 3, ..]), arr1(&[0, 255, 1]));
+    }
+
+    #[test]
+    fn test_odd_size_i420() {
+        // 5x3 frame, the chroma planes are 3x2
+        let y: Vec<u8> = (0..15).map(|i| 40 + i * 10).collect();
+        let u = [90, 54, 128, 200, 16, 240];
+        let v = [240, 34, 128, 16, 200, 90];
+        let i420: Vec<u8> = [y.clone(), u.to_vec(), v.to_vec()].concat();
+
+        // the same content as NV12 with an even stride
+        let mut nv12 = vec![0u8; 6 * 3 + 6 * 2];
+        for row in 0..3 {
+            nv12[row * 6..row * 6 + 5].copy_from_slice(&y[row * 5..row * 5 + 5]);
+        }
+        for i in 0..6 {
+            nv12[18 + (i / 3) * 6 + (i % 3) * 2] = u[i];
+            nv12[18 + (i / 3) * 6 + (i % 3) * 2 + 1] = v[i];
+        }
+
+        let conversion = YuvConversion::default();
+        let i420 = YuvFrame::new(&i420, 5, 3, YuvFormat::I420).unwrap();
+        let nv12 = YuvFrame::with_strides(&nv12, 5, 3, YuvFormat::Nv12, 6, 3).unwrap();
+        assert_eq!(i420.chroma(2, 1), (240, 90));
+        assert_eq!(nv12.chroma(2, 1), (240, 90));
+
+        let mut a = Array3::zeros((3, 5, 3));
+        let mut b = Array3::zeros((3, 5, 3));
+        yuv_to_rgb(&i420, &conversion, a.view_mut()).unwrap();
+        yuv_to_rgb(&nv12, &conversion, b.view_mut()).unwrap();
+        assert_eq!(a, b);
+
+        let data = vec![0u8; 15 + 2 * 6 - 1];
+        assert!(YuvFrame::new(&data, 5, 3, YuvFormat::I420).is_err());
+        assert!(YuvFrame::new(&[0u8; 27], 5, 3, YuvFormat::Nv12).is_err());
+    }
+
+    #[test]
+    fn test_coefficients() {
+        let gray = YuvConversion {
+            color_space: ColorSpace::Bt709,
+            range: YuvRange::Full,
+            order: RgbOrder::Bgr,
+        };
+        assert_eq!(Coefficients::new(&gray).apply(77.0, 128.0, 128.0), [77; 3]);
+
+        let blue = YuvConversion {
+            color_space: ColorSpace::Bt709,
+            range: YuvRange::Limited,
+            order: RgbOrder::Bgr,
+        };
+        assert_eq!(
+            Coefficients::new(&blue).apply(32.0, 240.0, 118.0),
+            [255, 0, 1]
+        );
+    }
+
+    #[test]
+    fn test_crop_resize_and_strides() {
+        // 4x4 frame with row stride 8 and a padded height of 6
+        let mut data = vec![0u8; 8 * 6 + 8 * 2];
+        for y in 0..4 {
+            for x in 0..4 {
+                data[y * 8 + x] = if x >= 2 && y >= 2 { 235 } else { 16 };
+            }
+        }
+        data[48..].fill(128);
+        let frame = YuvFrame::with_strides(&data, 4, 4, YuvFormat::Nv12, 8, 6).unwrap();
+
+        let crop = CropRect {
+            x: 2,
+            y: 2,
+            width: 2,
+            height: 2,
+        };
+        let mut dst = Array3::zeros((1, 1, 3));
+        yuv_to_rgb_resized(&frame, &YuvConversion::default(), crop, dst.view_mut()).unwrap();
+        assert_eq!(dst, Array3::from_elem((1, 1, 3), 255));
+
+        // crops past the frame and destinations without 3 channels
+        let conversion = YuvConversion::default();
+        for (x, width) in [(3, 2), (4, 1), (usize::MAX, 2), (0, 0)] {
+            let crop = CropRect { x, width, ..crop };
+            assert_eq!(
+                yuv_to_rgb_resized(&frame, &conversion, crop, dst.view_mut()),
+                Err(rknpu2_sys::RKNN_ERR_PARAM_INVALID)
+            );
+        }
+        let mut gray = Array3::zeros((1, 1, 1));
+        assert_eq!(
+            yuv_to_rgb(&frame, &conversion, gray.view_mut()),
+            Err(rknpu2_sys::RKNN_ERR_PARAM_INVALID)
+        );
+
+        assert!(YuvFrame::with_strides(&data[..60], 4, 4, YuvFormat::Nv12, 8, 6).is_err());
+        assert!(YuvFrame::new(&data, 4, 4, YuvFormat::Yuyv).is_ok());
+        assert!(YuvFrame::with_strides(&data, 4, 4, YuvFormat::Yuyv, 6, 4).is_err());
+    }
+
+    #[test]
+    fn test_yuv_letterbox() {
+        let data = [vec![235u8; 8 * 4], vec![128u8; 8 * 2]].concat();
+        let frame = YuvFrame::new(&data, 8, 4, YuvFormat::Nv12).unwrap();
+        let mut dst = Array3::zeros((8, 8, 3));
+        let t = yuv_letterbox_into(
+            &frame,
+            &YuvConversion::default(),
+            dst.view_mut(),
+            &LetterboxConfig::default(),
+        )
+        .unwrap();
+
+        assert_eq!((t.pad_x, t.pad_y), (0, 2));
+        assert_eq!(dst[[1, 0, 0]], 114);
+        assert_eq!(dst[[2, 0, 0]], 255);
+        assert_eq!(dst[[6, 7, 2]], 114);
+    }
+}
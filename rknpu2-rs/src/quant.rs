@@ -0,0 +1,301 @@
+//! Slice level (de)quantization and fp16 conversion.
+//!
+//! On aarch64 the slice functions use NEON, everywhere else they fall back to
+//! the scalar versions. Both paths produce bit-identical results.
+
+/// Quantize a single value with the affine asymmetric scheme used by RKNN.
+/// Note: like the RKNN reference code the result is truncated, not rounded.
+pub fn qnt_f32_to_affine(n: f32, zp: i32, scale: f32) -> i8 {
+    let dst_val = (n / scale) + zp as f32;
+
+    clip(dst_val, -128f32, 127f32) as i8
+}
+
+/// Dequantize a single affine asymmetric value.
+pub fn deqnt_affine_to_f32(qnt: i8, zp: i32, scale: f32) -> f32 {
+    (qnt as f32 - zp as f32) * scale
+}
+
+/// Dequantize `src` into `dst`, both slices must have the same length.
+pub fn dequantize(src: &[i8], zp: i32, scale: f32, dst: &mut [f32]) {
+    assert_eq!(src.len(), dst.len(), "dequantize: length mismatch");
+
+    #[cfg(target_arch = "aarch64")]
+    let done = unsafe { neon::dequantize(src, zp, scale, dst) };
+    #[cfg(not(target_arch = "aarch64"))]
+    let done = 0;
+
+    for (q, d) in src[done..].iter().zip(dst[done..].iter_mut()) {
+        *d = deqnt_affine_to_f32(*q, zp, scale);
+    }
+}
+
+/// Quantize `src` into `dst`, both slices must have the same length.
+pub fn quantize(src: &[f32], zp: i32, scale: f32, dst: &mut [i8]) {
+    assert_eq!(src.len(), dst.len(), "quantize: length mismatch");
+
+    #[cfg(target_arch = "aarch64")]
+    let done = unsafe { neon::quantize(src, zp, scale, dst) };
+    #[cfg(not(target_arch = "aarch64"))]
+    let done = 0;
+
+    for (f, d) in src[done..].iter().zip(dst[done..].iter_mut()) {
+        *d = qnt_f32_to_affine(*f, zp, scale);
+    }
+}
+
+/// Convert IEEE 754 half precision bits to f32.
+pub fn f16_to_f32(h: u16) -> f32 {
+    let sign = ((h & 0x8000) as u32) << 16;
+    let exp = ((h >> 10) & 0x1f) as u32;
+    let man = (h & 0x3ff) as u32;
+
+    let bits = match exp {
+        0 => {
+            // zero or subnormal, man * 2^-24 is exact in f32
+            let v = man as f32 * (1.0 / 16777216.0);
+            sign | v.to_bits()
+        }
+        0x1f if man == 0 => sign | 0x7f80_0000,
+        // NaN, quiet it and keep the payload like the hardware does
+        0x1f => sign | 0x7fc0_0000 | (man << 13),
+        _ => sign | ((exp + 112) << 23) | (man << 13),
+    };
+
+    f32::from_bits(bits)
+}
+
+/// Convert f32 to IEEE 754 half precision bits, rounding to nearest even.
+pub fn f32_to_f16(v: f32) -> u16 {
+    let x = v.to_bits();
+    let sign = ((x >> 16) & 0x8000) as u16;
+    let exp = ((x >> 23) & 0xff) as i32;
+    let man = x & 0x7f_ffff;
+
+    if exp == 0xff {
+        if man == 0 {
+            return sign | 0x7c00;
+        }
+        return sign | 0x7e00 | (man >> 13) as u16;
+    }
+
+    let e = exp - 127 + 15;
+    if e >= 0x1f {
+        return sign | 0x7c00;
+    }
+
+    if e <= 0 {
+        // result is subnormal or zero
+        if e < -10 {
+            return sign;
+        }
+        let m = man | 0x80_0000;
+        let shift = (14 - e) as u32;
+        let half = 1u32 << (shift - 1);
+        let rem = m & ((1u32 << shift) - 1);
+        let mut r = m >> shift;
+        if rem > half || (rem == half && r & 1 == 1) {
+            r += 1;
+        }
+        return sign | r as u16;
+    }
+
+    // a carry out of the mantissa bumps the exponent, up to infinity
+    let mut r = ((e as u32) << 10) | (man >> 13);
+    let rem = man & 0x1fff;
+    if rem > 0x1000 || (rem == 0x1000 && r & 1 == 1) {
+        r += 1;
+    }
+    sign | r as u16
+}
+
+/// Convert a slice of half precision bits to f32.
+pub fn f16_slice_to_f32(src: &[u16], dst: &mut [f32]) {
+    assert_eq!(src.len(), dst.len(), "f16_slice_to_f32: length mismatch");
+
+    #[cfg(target_arch = "aarch64")]
+    let done = unsafe { neon::f16_to_f32(src, dst) };
+    #[cfg(not(target_arch = "aarch64"))]
+    let done = 0;
+
+    for (h, d) in src[done..].iter().zip(dst[done..].iter_mut()) {
+        *d = f16_to_f32(*h);
+    }
+}
+
+/// Convert a slice of f32 to half precision bits.
+pub fn f32_slice_to_f16(src: &[f32], dst: &mut [u16]) {
+    assert_eq!(src.len(), dst.len(), "f32_slice_to_f16: length mismatch");
+
+    #[cfg(target_arch = "aarch64")]
+    let done = unsafe { neon::f32_to_f16(src, dst) };
+    #[cfg(not(target_arch = "aarch64"))]
+    let done = 0;
+
+    for (f, d) in src[done..].iter().zip(dst[done..].iter_mut()) {
+        *d = f32_to_f16(*f);
+    }
+}
+
+fn clip<T: PartialOrd>(n: T, low: T, high: T) -> T {
+    if n > high {
+        high
+    } else if n < low {
+        low
+    } else {
+        n
+    }
+}
+
+/// NEON kernels, each one handles the largest multiple of its lane count and
+/// returns how many elements it processed. The caller finishes the tail.
+#[cfg(target_arch = "aarch64")]
+mod neon {
+    use std::arch::aarch64::*;
+    use std::arch::asm;
+
+    pub unsafe fn dequantize(src: &[i8], zp: i32, scale: f32, dst: &mut [f32]) -> usize {
+        let n = src.len() / 16 * 16;
+        let zp_v = vdupq_n_f32(zp as f32);
+        let scale_v = vdupq_n_f32(scale);
+
+        for i in (0..n).step_by(16) {
+            let q = vld1q_s8(src.as_ptr().add(i));
+            let lo = vmovl_s8(vget_low_s8(q));
+            let hi = vmovl_high_s8(q);
+            let parts = [
+                vmovl_s16(vget_low_s16(lo)),
+                vmovl_high_s16(lo),
+                vmovl_s16(vget_low_s16(hi)),
+                vmovl_high_s16(hi),
+            ];
+            for (k, p) in parts.into_iter().enumerate() {
+                // sub then mul, same order as the scalar path
+                let f = vmulq_f32(vsubq_f32(vcvtq_f32_s32(p), zp_v), scale_v);
+                vst1q_f32(dst.as_mut_ptr().add(i + k * 4), f);
+            }
+        }
+
+        n
+    }
+
+    pub unsafe fn quantize(src: &[f32], zp: i32, scale: f32, dst: &mut [i8]) -> usize {
+        let n = src.len() / 8 * 8;
+        let zp_v = vdupq_n_f32(zp as f32);
+        let scale_v = vdupq_n_f32(scale);
+        let low = vdupq_n_f32(-128.0);
+        let high = vdupq_n_f32(127.0);
+
+        for i in (0..n).step_by(8) {
+            let mut q = [vdupq_n_s32(0); 2];
+            for (k, q) in q.iter_mut().enumerate() {
+                let f = vld1q_f32(src.as_ptr().add(i + k * 4));
+                let f = vaddq_f32(vdivq_f32(f, scale_v), zp_v);
+                let f = vminq_f32(vmaxq_f32(f, low), high);
+                // truncating conversion, NaN becomes 0 like `as i8`
+                *q = vcvtq_s32_f32(f);
+            }
+            let h = vcombine_s16(vmovn_s32(q[0]), vmovn_s32(q[1]));
+            vst1_s8(dst.as_mut_ptr().add(i), vmovn_s16(h));
+        }
+
+        n
+    }
+
+    pub unsafe fn f16_to_f32(src: &[u16], dst: &mut [f32]) -> usize {
+        let n = src.len() / 4 * 4;
+
+        for i in (0..n).step_by(4) {
+            let h = vld1_u16(src.as_ptr().add(i));
+            let f: float32x4_t;
+            asm!(
+                "fcvtl {f:v}.4s, {h:v}.4h",
+                h = in(vreg) h,
+                f = out(vreg) f,
+                options(pure, nomem, nostack)
+            );
+            vst1q_f32(dst.as_mut_ptr().add(i), f);
+        }
+
+        n
+    }
+
+    pub unsafe fn f32_to_f16(src: &[f32], dst: &mut [u16]) -> usize {
+        let n = src.len() / 4 * 4;
+
+        for i in (0..n).step_by(4) {
+            let f = vld1q_f32(src.as_ptr().add(i));
+            let h: uint16x4_t;
+            asm!(
+                "fcvtn {h:v}.4h, {f:v}.4s",
+                f = in(vreg) f,
+                h = out(vreg) h,
+                options(pure, nomem, nostack)
+            );
+            vst1_u16(dst.as_mut_ptr().add(i), h);
+        }
+
+        n
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dequantize_matches_scalar() {
+        let src: Vec<i8> = (-128..=127).chain(0..37).map(|v| v as i8).collect();
+        let mut dst = vec![0f32; src.len()];
+        dequantize(&src, -14, 0.0173, &mut dst);
+        for (q, d) in src.iter().zip(dst.iter()) {
+            assert_eq!(d.to_bits(), deqnt_affine_to_f32(*q, -14, 0.0173).to_bits());
+        }
+    }
+
+    #[test]
+    fn test_quantize_matches_scalar() {
+        let src: Vec<f32> = (0..301)
+            .map(|i| (i as f32 - 150.0) * 0.031)
+            .chain([f32::NAN, f32::INFINITY, f32::NEG_INFINITY])
+            .collect();
+        let mut dst = vec![0i8; src.len()];
+        quantize(&src, 3, 0.02, &mut dst);
+        for (f, d) in src.iter().zip(dst.iter()) {
+            assert_eq!(*d, qnt_f32_to_affine(*f, 3, 0.02));
+        }
+        assert_eq!(dst[0], -128);
+        assert_eq!(dst[300], 127);
+        assert_eq!(&dst[301..], &[0, 127, -128]);
+    }
+
+    #[test]
+    fn test_f16_known_values() {
+        assert_eq!(f32_to_f16(0.0), 0x0000);
+        assert_eq!(f32_to_f16(-0.0), 0x8000);
+        assert_eq!(f32_to_f16(1.0), 0x3c00);
+        assert_eq!(f32_to_f16(-2.0), 0xc000);
+        assert_eq!(f32_to_f16(65504.0), 0x7bff);
+        assert_eq!(f32_to_f16(65520.0), 0x7c00);
+        assert_eq!(f32_to_f16(5.960_464_5e-8), 0x0001);
+        // ties round to even
+        assert_eq!(f32_to_f16(1.0 + 1.0 / 2048.0), 0x3c00);
+        assert_eq!(f32_to_f16(1.0 + 3.0 / 2048.0), 0x3c02);
+        assert_eq!(f32_to_f16(2.980_232_2e-8), 0x0000);
+        assert_eq!(f16_to_f32(0x3555), 0.333_251_95);
+        assert_eq!(f16_to_f32(0x0001), 5.960_464_5e-8);
+        assert!(f16_to_f32(0x7e00).is_nan());
+    }
+
+    #[test]
+    fn test_f16_round_trip() {
+        let src: Vec<u16> = (0..=u16::MAX)
+            .filter(|h| h & 0x7c00 != 0x7c00 || h & 0x3ff == 0)
+            .collect();
+        let mut f = vec![0f32; src.len()];
+        let mut back = vec![0u16; src.len()];
+        f16_slice_to_f32(&src, &mut f);
+        f32_slice_to_f16(&f, &mut back);
+        assert_eq!(src, back);
+    }
+}
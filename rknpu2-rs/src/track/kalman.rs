@@ -0,0 +1,210 @@
+//! Constant velocity Kalman filter over `(cx, cy, aspect, height)` boxes, as
+//! used by SORT, DeepSORT and ByteTrack.
+
+use crate::postprocess::BBox;
+
+const STD_WEIGHT_POSITION: f32 = 1.0 / 20.0;
+const STD_WEIGHT_VELOCITY: f32 = 1.0 / 160.0;
+
+type Vec4 = [f32; 4];
+type Vec8 = [f32; 8];
+type Mat4 = [[f32; 4]; 4];
+type Mat8 = [[f32; 8]; 8];
+
+/// `(cx, cy, width / height, height)` of a box.
+pub fn to_xyah(bbox: &BBox) -> Vec4 {
+    let (cx, cy) = bbox.center();
+    let h = bbox.height().max(1e-6);
+    [cx, cy, bbox.width() / h, h]
+}
+
+pub fn from_xyah(xyah: &[f32]) -> BBox {
+    let (w, h) = (xyah[2] * xyah[3], xyah[3]);
+    BBox::new(
+        xyah[0] - w / 2.0,
+        xyah[1] - h / 2.0,
+        xyah[0] + w / 2.0,
+        xyah[1] + h / 2.0,
+    )
+}
+
+/// State mean (position then velocity) and covariance of one track.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KalmanState {
+    pub mean: Vec8,
+    pub covariance: Mat8,
+}
+
+impl KalmanState {
+    /// A new state at `measurement` with unknown velocity.
+    pub fn new(measurement: Vec4) -> Self {
+        let h = measurement[3];
+        let std = [
+            2.0 * STD_WEIGHT_POSITION * h,
+            2.0 * STD_WEIGHT_POSITION * h,
+            1e-2,
+            2.0 * STD_WEIGHT_POSITION * h,
+            10.0 * STD_WEIGHT_VELOCITY * h,
+            10.0 * STD_WEIGHT_VELOCITY * h,
+            1e-5,
+            10.0 * STD_WEIGHT_VELOCITY * h,
+        ];
+        let mut mean = [0.0; 8];
+        mean[..4].copy_from_slice(&measurement);
+
+        KalmanState {
+            mean,
+            covariance: diag8(std.map(|s| s * s)),
+        }
+    }
+
+    pub fn bbox(&self) -> BBox {
+        from_xyah(&self.mean[..4])
+    }
+
+    /// Advance one step.
+    pub fn predict(&mut self) {
+        let h = self.mean[3];
+        let pos = STD_WEIGHT_POSITION * h;
+        let vel = STD_WEIGHT_VELOCITY * h;
+        let std = [pos, pos, 1e-2, pos, vel, vel, 1e-5, vel];
+
+        for i in 0..4 {
+            self.mean[i] += self.mean[i + 4];
+        }
+        // F P F^T with F = [[I, I], [0, I]]
+        let p = self.covariance;
+        let mut fp = p;
+        for i in 0..4 {
+            for j in 0..8 {
+                fp[i][j] = p[i][j] + p[i + 4][j];
+            }
+        }
+        let mut fpf = fp;
+        for i in 0..8 {
+            for j in 0..4 {
+                fpf[i][j] = fp[i][j] + fp[i][j + 4];
+            }
+        }
+        for (i, s) in std.iter().enumerate() {
+            fpf[i][i] += s * s;
+        }
+        self.covariance = fpf;
+    }
+
+    /// Correct with a measured box.
+    pub fn update(&mut self, measurement: Vec4) {
+        let h = self.mean[3];
+        let pos = STD_WEIGHT_POSITION * h;
+        let std = [pos, pos, 1e-1, pos];
+
+        // S = H P H^T + R, H selects the first four states
+        let mut s: Mat4 = [[0.0; 4]; 4];
+        for i in 0..4 {
+            s[i].copy_from_slice(&self.covariance[i][..4]);
+            s[i][i] += std[i] * std[i];
+        }
+        let Some(s_inv) = invert4(&s) else {
+            return;
+        };
+
+        // K = P H^T S^-1, an 8x4 matrix
+        let mut k = [[0f32; 4]; 8];
+        for (i, row) in k.iter_mut().enumerate() {
+            for (j, v) in row.iter_mut().enumerate() {
+                *v = (0..4).map(|l| self.covariance[i][l] * s_inv[l][j]).sum();
+            }
+        }
+
+        let innovation: Vec4 = std::array::from_fn(|i| measurement[i] - self.mean[i]);
+        for (i, row) in k.iter().enumerate() {
+            self.mean[i] += (0..4).map(|j| row[j] * innovation[j]).sum::<f32>();
+        }
+
+        // P -= K S K^T, which is K H P
+        let p = self.covariance;
+        for (i, row) in k.iter().enumerate() {
+            for (j, v) in self.covariance[i].iter_mut().enumerate() {
+                *v -= (0..4).map(|l| row[l] * p[l][j]).sum::<f32>();
+            }
+        }
+    }
+}
+
+fn diag8(values: Vec8) -> Mat8 {
+    let mut m = [[0.0; 8]; 8];
+    for (i, v) in values.into_iter().enumerate() {
+        m[i][i] = v;
+    }
+    m
+}
+
+/// Gauss-Jordan inverse with partial pivoting.
+fn invert4(m: &Mat4) -> Option<Mat4> {
+    let mut a = *m;
+    let mut inv: Mat4 = std::array::from_fn(|i| std::array::from_fn(|j| (i == j) as u8 as f32));
+
+    for col in 0..4 {
+        let pivot = (col..4).max_by(|&x, &y| a[x][col].abs().total_cmp(&a[y][col].abs()))?;
+        if a[pivot][col].abs() < f32::EPSILON {
+            return None;
+        }
+        a.swap(col, pivot);
+        inv.swap(col, pivot);
+
+        let d = a[col][col];
+        for j in 0..4 {
+            a[col][j] /= d;
+            inv[col][j] /= d;
+        }
+        for row in 0..4 {
+            if row != col {
+                let f = a[row][col];
+                for j in 0..4 {
+                    a[row][j] -= f * a[col][j];
+                    inv[row][j] -= f * inv[col][j];
+                }
+            }
+        }
+    }
+
+    Some(inv)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_invert4() {
+        let m = [
+            [4.0, 1.0, 0.0, 0.0],
+            [1.0, 3.0, 0.0, 0.0],
+            [0.0, 0.0, 2.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ];
+        let inv = invert4(&m).unwrap();
+        for n in 0..16 {
+            let (i, j) = (n / 4, n % 4);
+            let v: f32 = (0..4).map(|k| m[i][k] * inv[k][j]).sum();
+            assert!((v - (i == j) as u8 as f32).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_constant_velocity() {
+        let bbox = |x: f32| BBox::new(x, 10.0, x + 20.0, 50.0);
+        let mut state = KalmanState::new(to_xyah(&bbox(0.0)));
+        for step in 1..20 {
+            state.predict();
+            state.update(to_xyah(&bbox(step as f32 * 5.0)));
+        }
+
+        // the velocity has been learned
+        assert!((state.mean[4] - 5.0).abs() < 0.5);
+        state.predict();
+        let predicted = state.bbox();
+        assert!((predicted.x1 - 100.0).abs() < 1.0);
+        assert!((predicted.height() - 40.0).abs() < 1e-2);
+    }
+}
@@ -0,0 +1,451 @@
+//! ByteTrack multi-object tracking over [`Detection`]s.
+//!
+//! Every frame, confirmed and lost tracks are predicted with a Kalman filter
+//! and matched by IoU to the high score detections first, then the still
+//! unmatched tracks to the low score detections, so briefly occluded objects
+//! keep their id. New tracks need a second matching frame to be confirmed.
+//! Matching is class agnostic, a track takes the class of its latest
+//! detection.
+
+pub mod kalman;
+
+use self::kalman::{to_xyah, KalmanState};
+use crate::postprocess::{BBox, Detection};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ByteTrackConfig {
+    /// Detections scoring at least this are matched in the first stage.
+    pub track_thresh: f32,
+    /// Detections between this and `track_thresh` are matched in the second
+    /// stage, lower ones are ignored.
+    pub low_thresh: f32,
+    /// Unmatched high detections scoring at least this start a new track.
+    pub new_track_thresh: f32,
+    /// Maximum `1 - iou` of a first stage match.
+    pub match_thresh: f32,
+    /// Frames a lost track is kept for before it is removed.
+    pub track_buffer: u32,
+}
+
+impl Default for ByteTrackConfig {
+    fn default() -> Self {
+        ByteTrackConfig {
+            track_thresh: 0.5,
+            low_thresh: 0.1,
+            new_track_thresh: 0.6,
+            match_thresh: 0.8,
+            track_buffer: 30,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrackState {
+    /// Started last frame, waiting for a second match.
+    Tentative,
+    Tracked,
+    Lost,
+    Removed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Track {
+    pub id: u64,
+    pub state: TrackState,
+    /// Kalman filtered box.
+    pub bbox: BBox,
+    pub class_id: usize,
+    /// Score of the latest matched detection.
+    pub score: f32,
+    /// Index into the detections of the current frame, if matched.
+    pub detection: Option<usize>,
+    pub start_frame: u64,
+    /// Last frame the track was matched in.
+    pub frame: u64,
+    /// Number of frames the track was matched in.
+    pub hits: u32,
+    kalman: KalmanState,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrackEvent {
+    /// A track got confirmed.
+    New(u64),
+    /// A tracked object was not matched this frame.
+    Lost(u64),
+    /// A lost track was matched again.
+    Recovered(u64),
+    /// A lost track exceeded the track buffer.
+    Removed(u64),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrackUpdate {
+    /// Tracks matched this frame.
+    pub tracks: Vec<Track>,
+    pub events: Vec<TrackEvent>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ByteTracker {
+    pub config: ByteTrackConfig,
+    frame: u64,
+    next_id: u64,
+    tracks: Vec<Track>,
+}
+
+impl ByteTracker {
+    pub fn new(config: ByteTrackConfig) -> Self {
+        ByteTracker {
+            config,
+            frame: 0,
+            next_id: 1,
+            tracks: Vec::new(),
+        }
+    }
+
+    /// Tracked and lost tracks.
+    pub fn tracks(&self) -> &[Track] {
+        &self.tracks
+    }
+
+    /// Feed the detections of the next frame.
+    pub fn update(&mut self, detections: &[Detection]) -> TrackUpdate {
+        self.frame += 1;
+        let config = self.config;
+        let mut events = Vec::new();
+
+        for t in self.tracks.iter_mut() {
+            t.detection = None;
+            t.kalman.predict();
+            t.bbox = t.kalman.bbox();
+        }
+
+        let high: Vec<usize> = (0..detections.len())
+            .filter(|&i| detections[i].score >= config.track_thresh)
+            .collect();
+        let low: Vec<usize> = (0..detections.len())
+            .filter(|&i| {
+                let s = detections[i].score;
+                s >= config.low_thresh && s < config.track_thresh
+            })
+            .collect();
+
+        // first stage: confirmed and lost tracks with high detections
+        let pool: Vec<usize> = (0..self.tracks.len())
+            .filter(|&t| self.tracks[t].state != TrackState::Tentative)
+            .collect();
+        let (matches, pool_left, high_left) =
+            self.associate(&pool, &high, detections, config.match_thresh);
+        for (t, d) in matches {
+            if self.tracks[t].state == TrackState::Lost {
+                events.push(TrackEvent::Recovered(self.tracks[t].id));
+            }
+            self.apply(t, d, &detections[d]);
+        }
+
+        // second stage: remaining tracked ones with low detections
+        let tracked_left: Vec<usize> = pool_left
+            .into_iter()
+            .filter(|&t| self.tracks[t].state == TrackState::Tracked)
+            .collect();
+        let (matches, tracked_left, _) = self.associate(&tracked_left, &low, detections, 0.5);
+        for (t, d) in matches {
+            self.apply(t, d, &detections[d]);
+        }
+        for t in tracked_left {
+            self.tracks[t].state = TrackState::Lost;
+            events.push(TrackEvent::Lost(self.tracks[t].id));
+        }
+
+        // tentative tracks need a high detection to be confirmed
+        let tentative: Vec<usize> = (0..self.tracks.len())
+            .filter(|&t| self.tracks[t].state == TrackState::Tentative)
+            .collect();
+        let (matches, tentative_left, high_left) =
+            self.associate(&tentative, &high_left, detections, 0.7);
+        for (t, d) in matches {
+            self.apply(t, d, &detections[d]);
+            events.push(TrackEvent::New(self.tracks[t].id));
+        }
+        for t in tentative_left {
+            self.tracks[t].state = TrackState::Removed;
+        }
+
+        for d in high_left {
+            let det = &detections[d];
+            if det.score < config.new_track_thresh {
+                continue;
+            }
+            // only tracks of the very first frame are confirmed right away
+            let state = if self.frame == 1 {
+                TrackState::Tracked
+            } else {
+                TrackState::Tentative
+            };
+            let kalman = KalmanState::new(to_xyah(&det.bbox));
+            let track = Track {
+                id: self.next_id,
+                state,
+                bbox: det.bbox,
+                class_id: det.class_id,
+                score: det.score,
+                detection: Some(d),
+                start_frame: self.frame,
+                frame: self.frame,
+                hits: 1,
+                kalman,
+            };
+            self.next_id += 1;
+            if state == TrackState::Tracked {
+                events.push(TrackEvent::New(track.id));
+            }
+            self.tracks.push(track);
+        }
+
+        for t in self.tracks.iter_mut() {
+            if t.state == TrackState::Lost && self.frame - t.frame > config.track_buffer as u64 {
+                t.state = TrackState::Removed;
+                events.push(TrackEvent::Removed(t.id));
+            }
+        }
+        self.tracks.retain(|t| t.state != TrackState::Removed);
+
+        TrackUpdate {
+            tracks: self
+                .tracks
+                .iter()
+                .filter(|t| t.state == TrackState::Tracked && t.frame == self.frame)
+                .copied()
+                .collect(),
+            events,
+        }
+    }
+
+    fn apply(&mut self, t: usize, d: usize, det: &Detection) {
+        let frame = self.frame;
+        let track = &mut self.tracks[t];
+        track.kalman.update(to_xyah(&det.bbox));
+        track.bbox = track.kalman.bbox();
+        track.class_id = det.class_id;
+        track.score = det.score;
+        track.detection = Some(d);
+        track.frame = frame;
+        track.hits += 1;
+        track.state = TrackState::Tracked;
+    }
+
+    /// Match tracks to detections minimizing `1 - iou`, rejecting pairs above
+    /// `thresh`. Returns the matches and the unmatched tracks and detections.
+    fn associate(
+        &self,
+        tracks: &[usize],
+        dets: &[usize],
+        detections: &[Detection],
+        thresh: f32,
+    ) -> (Vec<(usize, usize)>, Vec<usize>, Vec<usize>) {
+        let cost: Vec<Vec<f32>> = tracks
+            .iter()
+            .map(|&t| {
+                dets.iter()
+                    .map(|&d| 1.0 - self.tracks[t].bbox.iou(&detections[d].bbox))
+                    .collect()
+            })
+            .collect();
+
+        let pairs = linear_assignment(&cost, thresh);
+        let mut track_used = vec![false; tracks.len()];
+        let mut det_used = vec![false; dets.len()];
+        let matches = pairs
+            .into_iter()
+            .map(|(i, j)| {
+                track_used[i] = true;
+                det_used[j] = true;
+                (tracks[i], dets[j])
+            })
+            .collect();
+
+        (
+            matches,
+            (0..tracks.len())
+                .filter(|&i| !track_used[i])
+                .map(|i| tracks[i])
+                .collect(),
+            (0..dets.len())
+                .filter(|&j| !det_used[j])
+                .map(|j| dets[j])
+                .collect(),
+        )
+    }
+}
+
+impl Default for ByteTracker {
+    fn default() -> Self {
+        Self::new(ByteTrackConfig::default())
+    }
+}
+
+/// Minimum cost assignment of rows to columns (Hungarian algorithm), keeping
+/// only pairs with a cost of at most `thresh`.
+pub fn linear_assignment(cost: &[Vec<f32>], thresh: f32) -> Vec<(usize, usize)> {
+    let rows = cost.len();
+    let cols = cost.first().map_or(0, |r| r.len());
+    if rows == 0 || cols == 0 {
+        return Vec::new();
+    }
+    // the algorithm below needs rows <= cols
+    let transposed = rows > cols;
+    let (n, m) = if transposed {
+        (cols, rows)
+    } else {
+        (rows, cols)
+    };
+    // pairs above the threshold are kept out of the optimum where possible
+    let forbidden = 1e6;
+    let at = |i: usize, j: usize| {
+        let c = if transposed { cost[j][i] } else { cost[i][j] };
+        if c > thresh {
+            forbidden
+        } else {
+            c as f64
+        }
+    };
+
+    // potentials, column assignment and path, 1 based with 0 as a sentinel
+    let mut u = vec![0f64; n + 1];
+    let mut v = vec![0f64; m + 1];
+    let mut p = vec![0usize; m + 1];
+    let mut way = vec![0usize; m + 1];
+    for i in 1..=n {
+        p[0] = i;
+        let mut j0 = 0;
+        let mut minv = vec![f64::INFINITY; m + 1];
+        let mut used = vec![false; m + 1];
+        loop {
+            used[j0] = true;
+            let i0 = p[j0];
+            let mut delta = f64::INFINITY;
+            let mut j1 = 0;
+            for j in 1..=m {
+                if used[j] {
+                    continue;
+                }
+                let cur = at(i0 - 1, j - 1) - u[i0] - v[j];
+                if cur < minv[j] {
+                    minv[j] = cur;
+                    way[j] = j0;
+                }
+                if minv[j] < delta {
+                    delta = minv[j];
+                    j1 = j;
+                }
+            }
+            for j in 0..=m {
+                if used[j] {
+                    u[p[j]] += delta;
+                    v[j] -= delta;
+                } else {
+                    minv[j] -= delta;
+                }
+            }
+            j0 = j1;
+            if p[j0] == 0 {
+                break;
+            }
+        }
+        loop {
+            let j1 = way[j0];
+            p[j0] = p[j1];
+            j0 = j1;
+            if j0 == 0 {
+                break;
+            }
+        }
+    }
+
+    (1..=m)
+        .filter(|&j| p[j] != 0)
+        .map(|j| {
+            if transposed {
+                (j - 1, p[j] - 1)
+            } else {
+                (p[j] - 1, j - 1)
+            }
+        })
+        .filter(|&(i, j)| cost[i][j] <= thresh)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn det(x: f32, score: f32) -> Detection {
+        Detection {
+            class_id: 0,
+            score,
+            bbox: BBox::new(x, 0.0, x + 20.0, 40.0),
+        }
+    }
+
+    #[test]
+    fn test_linear_assignment() {
+        // greedy would take (0, 0) and leave row 1 with a bad match
+        let cost = vec![vec![0.1, 0.2], vec![0.15, 0.9]];
+        let mut pairs = linear_assignment(&cost, 0.5);
+        pairs.sort();
+        assert_eq!(pairs, vec![(0, 1), (1, 0)]);
+
+        let tall = vec![vec![0.9], vec![0.3], vec![0.6]];
+        assert_eq!(linear_assignment(&tall, 0.5), vec![(1, 0)]);
+        assert!(linear_assignment(&tall, 0.1).is_empty());
+    }
+
+    #[test]
+    fn test_track_ids_are_stable() {
+        let mut tracker = ByteTracker::default();
+        let first = tracker.update(&[det(0.0, 0.9), det(100.0, 0.9)]);
+        assert_eq!(first.events, vec![TrackEvent::New(1), TrackEvent::New(2)]);
+
+        for frame in 1..10 {
+            let x = frame as f32 * 2.0;
+            // the second object drops to a low score, matched in stage two
+            let update = tracker.update(&[det(100.0 - x, 0.3), det(x, 0.9)]);
+            let mut ids: Vec<(u64, Option<usize>)> =
+                update.tracks.iter().map(|t| (t.id, t.detection)).collect();
+            ids.sort();
+            assert_eq!(ids, vec![(1, Some(1)), (2, Some(0))]);
+            assert!(update.events.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_track_lifecycle() {
+        let config = ByteTrackConfig {
+            track_buffer: 3,
+            ..Default::default()
+        };
+        let mut tracker = ByteTracker::new(config);
+        tracker.update(&[det(0.0, 0.9)]);
+
+        // a detection appearing later needs two frames to be confirmed
+        let update = tracker.update(&[det(0.0, 0.9), det(200.0, 0.9)]);
+        assert_eq!(update.tracks.len(), 1);
+        assert!(update.events.is_empty());
+        let update = tracker.update(&[det(0.0, 0.9), det(200.0, 0.9)]);
+        assert_eq!(update.events, vec![TrackEvent::New(2)]);
+
+        let update = tracker.update(&[det(200.0, 0.9)]);
+        assert_eq!(update.events, vec![TrackEvent::Lost(1)]);
+        let update = tracker.update(&[det(0.0, 0.9), det(200.0, 0.9)]);
+        assert_eq!(update.events, vec![TrackEvent::Recovered(1)]);
+
+        for _ in 0..3 {
+            let update = tracker.update(&[det(200.0, 0.9)]);
+            assert!(!update.events.contains(&TrackEvent::Removed(1)));
+        }
+        let update = tracker.update(&[det(200.0, 0.9)]);
+        assert_eq!(update.events, vec![TrackEvent::Removed(1)]);
+        assert_eq!(tracker.tracks().len(), 1);
+    }
+}
@@ -10,8 +10,6 @@ use std::fs::File;
 use std::path::{Path, PathBuf};
 use std::sync::OnceLock;
 
-const VERSION: &str = "v1.5.2";
-
 #[cfg(feature = "rk3588")]
 const CHIP: Chip = Chip::RK3588;
 
@@ -28,34 +26,238 @@ const ARCH: Arch = Arch::AArch64;
 const ARCH: Arch = Arch::Armhf;
 
 fn main() {
-    let lib_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap()).join("runtime");
-    download_rknn_libs(&lib_dir);
+    let sdk = sdk_version().unwrap_or_else(|e| fail(e));
+    // Read by dependents as `DEP_RKNNRT_VERSION`
+    println!("cargo:version={}", sdk.number());
+
+    let runtime = locate_runtime(sdk).unwrap_or_else(|e| fail(e));
+    let lib = runtime.lib_dir.join("librknnrt.so");
+    println!("cargo:rerun-if-changed={}", lib.display());
+    check_runtime_version(&lib, sdk).unwrap_or_else(|e| fail(e));
 
     // Tell cargo to look for shared libraries in the specified directory
-    println!("cargo:rustc-link-search={}", lib_dir.display());
+    println!("cargo:rustc-link-search={}", runtime.lib_dir.display());
     println!("cargo:rustc-link-lib=rknnrt");
 
+    println!("cargo:rustc-check-cfg=cfg(rknn_sdk, values(\"v1_5_2\", \"v1_6_0\", \"v2_3_0\"))");
+    println!("cargo:rustc-cfg=rknn_sdk=\"{}\"", sdk.ident());
+
+    #[cfg(feature = "bindgen")]
+    generate_bindings(&runtime, sdk).unwrap_or_else(|e| fail(e));
+
+    #[cfg(not(feature = "bindgen"))]
+    {
+        let bindings = checked_in_bindings(sdk);
+        if !bindings.is_file() {
+            fail(anyhow::anyhow!(
+                "No pre-generated bindings for SDK {sdk} on {}-bit targets at {}. \
+                 Enable the 'bindgen' feature to generate them from the headers.",
+                pointer_width(),
+                bindings.display()
+            ));
+        }
+    }
+}
+
+fn pointer_width() -> String {
+    env::var("CARGO_CFG_TARGET_POINTER_WIDTH").unwrap()
+}
+
+/// `src/bindings/<sdk>_<pointer width>.rs`, picked by `src/lib.rs` with cfgs.
+fn checked_in_bindings(sdk: Sdk) -> PathBuf {
+    PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap())
+        .join("src/bindings")
+        .join(format!("{}_{}.rs", sdk.ident(), pointer_width()))
+}
+
+/// Generate `$OUT_DIR/bindings.rs`, and replace the checked in bindings with
+/// them when `RKNPU2_UPDATE_BINDINGS` is set.
+#[cfg(feature = "bindgen")]
+fn generate_bindings(runtime: &Runtime, sdk: Sdk) -> Result<()> {
     // The bindgen::Builder is the main entry point
     // to bindgen, and lets you build up options for
     // the resulting bindings.
-    let bindings = bindgen::Builder::default()
+    let mut builder = bindgen::Builder::default()
         // The input header we would like to generate
         // bindings for.
         .header("headers/wrapper.h")
+        .clang_args(
+            runtime
+                .include_dirs
+                .iter()
+                .map(|dir| format!("-I{}", dir.display())),
+        )
+        // Leave out the C library, so the output is the same on every host
+        .allowlist_function("rknn_.*")
+        .allowlist_type("_?rknn_.*")
+        .allowlist_var("RKNN_.*");
+
+    // The custom op API is only in v1.6.0 and later
+    if sdk >= Sdk::V1_6_0 {
+        builder = builder.clang_arg("-DRKNPU2_CUSTOM_OP");
+    }
+
+    let bindings = builder
         // Tell cargo to invalidate the built crate whenever any of the
         // included header files changed.
         .parse_callbacks(Box::new(bindgen::CargoCallbacks::new()))
         // Finish the builder and generate the bindings.
         .generate()
-        // Unwrap the Result and panic on failure.
-        .expect("Unable to generate bindings");
+        .with_context(|| {
+            let dirs: Vec<String> = runtime
+                .include_dirs
+                .iter()
+                .map(|dir| dir.display().to_string())
+                .collect();
+            format!(
+                "Unable to generate bindings from the headers in {}",
+                dirs.join(", ")
+            )
+        })?;
 
     // Write the bindings to the $OUT_DIR/bindings.rs file.
     let out_path = PathBuf::from(env::var("OUT_DIR").unwrap());
 
     bindings
         .write_to_file(out_path.join("bindings.rs"))
-        .expect("Couldn't write bindings!");
+        .context("Couldn't write bindings")?;
+
+    println!("cargo:rerun-if-env-changed=RKNPU2_UPDATE_BINDINGS");
+    if env::var_os("RKNPU2_UPDATE_BINDINGS").is_some() {
+        let path = checked_in_bindings(sdk);
+        let header = format!(
+            "// Bindings for the RKNN SDK {sdk} headers on {}-bit targets.\n\
+             // Regenerate with `RKNPU2_UPDATE_BINDINGS=1 cargo build --features bindgen`.\n\n",
+            pointer_width()
+        );
+        std::fs::write(&path, header + &bindings.to_string())
+            .with_context(|| format!("Couldn't write {}", path.display()))?;
+    }
+
+    Ok(())
+}
+
+/// Stop the build with the error and its causes, without a panic backtrace.
+fn fail(e: anyhow::Error) -> ! {
+    eprintln!("error: {e:#}");
+    std::process::exit(1);
+}
+
+/// Where the headers and `librknnrt.so` are.
+struct Runtime {
+    include_dirs: Vec<PathBuf>,
+    lib_dir: PathBuf,
+}
+
+/// Headers needed to generate the bindings, none without the `bindgen`
+/// feature.
+fn headers(sdk: Sdk) -> Vec<&'static str> {
+    if !cfg!(feature = "bindgen") {
+        return Vec::new();
+    }
+    let mut headers = vec!["rknn_api.h", "rknn_matmul_api.h"];
+    if sdk >= Sdk::V1_6_0 {
+        headers.push("rknn_custom_op.h");
+    }
+    headers
+}
+
+/// The first of `dirs` holding `file`.
+fn find_dir(file: &str, dirs: &[PathBuf]) -> Option<PathBuf> {
+    dirs.iter().find(|dir| dir.join(file).is_file()).cloned()
+}
+
+/// Look for the runtime in `include_dirs` and `lib_dirs`, naming the first
+/// missing file and where it was looked for.
+fn local_runtime(sdk: Sdk, include_dirs: &[PathBuf], lib_dirs: &[PathBuf]) -> Result<Runtime> {
+    let missing = |file: &str, dirs: &[PathBuf]| {
+        let dirs: Vec<String> = dirs.iter().map(|d| d.display().to_string()).collect();
+        anyhow::anyhow!("{file} not found, looked in {}", dirs.join(", "))
+    };
+
+    let mut found = Vec::new();
+    for header in headers(sdk) {
+        let dir = find_dir(header, include_dirs).ok_or_else(|| missing(header, include_dirs))?;
+        if !found.contains(&dir) {
+            found.push(dir);
+        }
+    }
+    let lib_dir =
+        find_dir("librknnrt.so", lib_dirs).ok_or_else(|| missing("librknnrt.so", lib_dirs))?;
+
+    Ok(Runtime {
+        include_dirs: found,
+        lib_dir,
+    })
+}
+
+/// Use a local runtime when one is configured or installed, download it
+/// from GitHub otherwise.
+///
+/// 1. `RKNN_RUNTIME_DIR`: headers in the directory or its `include`, the
+///    library in the directory, its `lib` or its `aarch64`/`armhf`.
+/// 2. `RKNN_INCLUDE_DIR` and `RKNN_LIB_DIR`.
+/// 3. The paths of the pkg-config package `rknnrt`, and `/usr` and
+///    `/usr/local` when not cross compiling.
+///
+/// The first two never fall back to a download.
+fn locate_runtime(sdk: Sdk) -> Result<Runtime> {
+    for var in ["RKNN_RUNTIME_DIR", "RKNN_INCLUDE_DIR", "RKNN_LIB_DIR"] {
+        println!("cargo:rerun-if-env-changed={var}");
+    }
+
+    if let Some(dir) = env::var_os("RKNN_RUNTIME_DIR").map(PathBuf::from) {
+        let include_dirs = [dir.clone(), dir.join("include")];
+        let lib_dirs = [dir.clone(), dir.join("lib"), dir.join(ARCH.to_string())];
+        return local_runtime(sdk, &include_dirs, &lib_dirs)
+            .with_context(|| format!("RKNN_RUNTIME_DIR is {}", dir.display()));
+    }
+
+    match (env::var_os("RKNN_INCLUDE_DIR"), env::var_os("RKNN_LIB_DIR")) {
+        (Some(include_dir), Some(lib_dir)) => {
+            return local_runtime(sdk, &[include_dir.into()], &[lib_dir.into()])
+                .context("Using RKNN_INCLUDE_DIR and RKNN_LIB_DIR");
+        }
+        (Some(_), None) => anyhow::bail!("RKNN_INCLUDE_DIR is set, but RKNN_LIB_DIR is not"),
+        (None, Some(_)) => anyhow::bail!("RKNN_LIB_DIR is set, but RKNN_INCLUDE_DIR is not"),
+        (None, None) => {}
+    }
+
+    // pkg-config leaves out the default paths, so look there too
+    let native = env::var("TARGET").ok() == env::var("HOST").ok();
+    let mut include_dirs = Vec::new();
+    let mut lib_dirs = Vec::new();
+    if let Ok(lib) = pkg_config::Config::new()
+        .cargo_metadata(false)
+        .env_metadata(true)
+        .probe("rknnrt")
+    {
+        include_dirs.extend(lib.include_paths);
+        lib_dirs.extend(lib.link_paths);
+    }
+    if native {
+        include_dirs.extend(["/usr/include", "/usr/local/include"].map(PathBuf::from));
+        lib_dirs.extend(
+            [
+                "/usr/lib",
+                "/usr/local/lib",
+                "/usr/lib/aarch64-linux-gnu",
+                "/usr/lib/arm-linux-gnueabihf",
+            ]
+            .map(PathBuf::from),
+        );
+    }
+    if let Ok(runtime) = local_runtime(sdk, &include_dirs, &lib_dirs) {
+        return Ok(runtime);
+    }
+
+    let runtime_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap()).join("runtime");
+    download_rknn_libs(&runtime_dir, sdk)?;
+
+    Ok(Runtime {
+        include_dirs: vec![runtime_dir.clone()],
+        lib_dir: runtime_dir,
+    })
 }
 
 fn features_check() {
@@ -84,12 +286,84 @@ fn features_check() {
     }
 }
 
-fn download_rknn_libs(out_dir: &Path) {
+/// The SDK version from `RKNN_SDK_VERSION`, else the newest one enabled by
+/// a feature, else v1.5.2.
+fn sdk_version() -> Result<Sdk> {
+    println!("cargo:rerun-if-env-changed=RKNN_SDK_VERSION");
+    if let Ok(version) = env::var("RKNN_SDK_VERSION") {
+        return match version.trim_start_matches('v') {
+            "1.5.2" => Ok(Sdk::V1_5_2),
+            "1.6.0" => Ok(Sdk::V1_6_0),
+            "2.3.0" => Ok(Sdk::V2_3_0),
+            _ => anyhow::bail!(
+                "Unsupported RKNN_SDK_VERSION '{version}', expected one of 1.5.2, 1.6.0 and 2.3.0"
+            ),
+        };
+    }
+
+    Ok(if cfg!(feature = "v2_3_0") {
+        Sdk::V2_3_0
+    } else if cfg!(feature = "v1_6_0") {
+        Sdk::V1_6_0
+    } else {
+        Sdk::V1_5_2
+    })
+}
+
+/// Fail when the runtime library is from another SDK than the headers. The
+/// library embeds its version as `librknnrt version: 1.5.2 (...)`.
+fn check_runtime_version(lib: &Path, sdk: Sdk) -> Result<()> {
+    const MARKER: &[u8] = b"librknnrt version: ";
+
+    let bytes = std::fs::read(lib).with_context(|| format!("Failed to read {}", lib.display()))?;
+    let Some(start) = bytes
+        .windows(MARKER.len())
+        .position(|w| w == MARKER)
+        .map(|i| i + MARKER.len())
+    else {
+        println!(
+            "cargo:warning=No version found in {}, assuming it is {sdk}",
+            lib.display()
+        );
+        return Ok(());
+    };
+    let version: String = bytes[start..]
+        .iter()
+        .take_while(|b| b.is_ascii_digit() || **b == b'.')
+        .map(|&b| b as char)
+        .collect();
+
+    // Patch releases keep the API
+    let minor = |v: &str| v.split('.').take(2).collect::<Vec<_>>().join(".");
+    if minor(&version) != minor(sdk.number()) {
+        anyhow::bail!(
+            "{} is runtime version {version}, but SDK {sdk} is selected. Select SDK \
+             v{version} with the RKNN_SDK_VERSION environment variable or the matching \
+             rknpu2-sys feature, or use the runtime of SDK {sdk}.",
+            lib.display()
+        );
+    }
+
+    Ok(())
+}
+
+fn download_rknn_libs(out_dir: &Path, sdk: Sdk) -> Result<()> {
     let mut runtime = HashMap::new();
 
-    runtime.insert("librknnrt.so", format!("https://github.com/rockchip-linux/rknpu2/raw/{VERSION}/runtime/{CHIP}/Linux/librknn_api/{ARCH}/librknnrt.so"));
-    runtime.insert("rknn_matmul_api.h", format!("https://github.com/rockchip-linux/rknpu2/raw/{VERSION}/runtime/{CHIP}/Linux/librknn_api/include/rknn_matmul_api.h"));
-    runtime.insert("rknn_api.h", format!("https://github.com/rockchip-linux/rknpu2/raw/{VERSION}/runtime/{CHIP}/Linux/librknn_api/include/rknn_api.h"));
+    // From v1.6.0 on the runtime is released in rknn-toolkit2, the same for
+    // every chip
+    let api_url = if sdk >= Sdk::V1_6_0 {
+        format!("https://github.com/airockchip/rknn-toolkit2/raw/{sdk}/rknpu2/runtime/Linux/librknn_api")
+    } else {
+        format!(
+            "https://github.com/rockchip-linux/rknpu2/raw/{sdk}/runtime/{CHIP}/Linux/librknn_api"
+        )
+    };
+
+    runtime.insert("librknnrt.so", format!("{api_url}/{ARCH}/librknnrt.so"));
+    for header in headers(sdk) {
+        runtime.insert(header, format!("{api_url}/include/{header}"));
+    }
 
     #[cfg(feature = "mirror")]
     {
@@ -99,24 +373,21 @@ fn download_rknn_libs(out_dir: &Path) {
     }
 
     let download_dir = out_dir
-        .join(VERSION)
+        .join(sdk.to_string())
         .join(CHIP.to_string())
         .join(ARCH.to_string());
 
-    if !download_dir.exists() {
-        std::fs::create_dir_all(&download_dir).unwrap();
-    }
-
-    let symlink_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap()).join("runtime");
-
-    if !symlink_dir.exists() {
-        std::fs::create_dir_all(&symlink_dir).unwrap();
-    }
+    std::fs::create_dir_all(&download_dir)
+        .with_context(|| format!("Failed to create {}", download_dir.display()))?;
 
     for (file, url) in runtime.iter() {
-        download_file(url, download_dir.join(file)).unwrap();
-        symlink(download_dir.join(file), symlink_dir.join(file)).unwrap();
+        download_file(url, download_dir.join(file)).with_context(|| {
+            format!("Failed to download {file}, set RKNN_RUNTIME_DIR to build from a local copy")
+        })?;
+        symlink(download_dir.join(file), out_dir.join(file))?;
     }
+
+    Ok(())
 }
 
 fn download_file<P: AsRef<Path>>(url: &str, path: P) -> Result<()> {
@@ -170,6 +441,34 @@ fn symlink<P1: AsRef<Path>, P2: AsRef<Path>>(path: P1, symlink_path: P2) -> Resu
     Ok(())
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Sdk {
+    V1_5_2,
+    V1_6_0,
+    V2_3_0,
+}
+
+impl Sdk {
+    /// `v1_5_2` for v1.5.2, as in cfgs and file names.
+    fn ident(self) -> String {
+        format!("v{}", self.number().replace('.', "_"))
+    }
+
+    fn number(self) -> &'static str {
+        match self {
+            Sdk::V1_5_2 => "1.5.2",
+            Sdk::V1_6_0 => "1.6.0",
+            Sdk::V2_3_0 => "2.3.0",
+        }
+    }
+}
+
+impl Display for Sdk {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "v{}", self.number())
+    }
+}
+
 enum Chip {
     RV1106,
     RK356X,
@@ -1,2 +1,6 @@
-#include "../runtime/rknn_api.h"
-#include "../runtime/rknn_matmul_api.h"
\ No newline at end of file
+#include "rknn_api.h"
+#include "rknn_matmul_api.h"
+
+#ifdef RKNPU2_CUSTOM_OP
+#include "rknn_custom_op.h"
+#endif
@@ -0,0 +1,1453 @@
+// Bindings for the RKNN SDK v1.5.2 headers on 32-bit targets.
+// Regenerate with `RKNPU2_UPDATE_BINDINGS=1 cargo build --features bindgen`.
+
+pub const RKNN_FLAG_PRIOR_HIGH: u32 = 0;
+pub const RKNN_FLAG_PRIOR_MEDIUM: u32 = 1;
+pub const RKNN_FLAG_PRIOR_LOW: u32 = 2;
+pub const RKNN_FLAG_ASYNC_MASK: u32 = 4;
+pub const RKNN_FLAG_COLLECT_PERF_MASK: u32 = 8;
+pub const RKNN_FLAG_MEM_ALLOC_OUTSIDE: u32 = 16;
+pub const RKNN_FLAG_SHARE_WEIGHT_MEM: u32 = 32;
+pub const RKNN_FLAG_FENCE_IN_OUTSIDE: u32 = 64;
+pub const RKNN_FLAG_FENCE_OUT_OUTSIDE: u32 = 128;
+pub const RKNN_FLAG_COLLECT_MODEL_INFO_ONLY: u32 = 256;
+pub const RKNN_FLAG_INTERNAL_ALLOC_OUTSIDE: u32 = 512;
+pub const RKNN_FLAG_EXECUTE_FALLBACK_PRIOR_DEVICE_GPU: u32 = 1024;
+pub const RKNN_FLAG_ENABLE_SRAM: u32 = 2048;
+pub const RKNN_FLAG_SHARE_SRAM: u32 = 4096;
+pub const RKNN_FLAG_DISABLE_PROC_HIGH_PRIORITY: u32 = 8192;
+pub const RKNN_FLAG_DISABLE_FLUSH_INPUT_MEM_CACHE: u32 = 16384;
+pub const RKNN_FLAG_DISABLE_FLUSH_OUTPUT_MEM_CACHE: u32 = 32768;
+pub const RKNN_SUCC: u32 = 0;
+pub const RKNN_ERR_FAIL: i32 = -1;
+pub const RKNN_ERR_TIMEOUT: i32 = -2;
+pub const RKNN_ERR_DEVICE_UNAVAILABLE: i32 = -3;
+pub const RKNN_ERR_MALLOC_FAIL: i32 = -4;
+pub const RKNN_ERR_PARAM_INVALID: i32 = -5;
+pub const RKNN_ERR_MODEL_INVALID: i32 = -6;
+pub const RKNN_ERR_CTX_INVALID: i32 = -7;
+pub const RKNN_ERR_INPUT_INVALID: i32 = -8;
+pub const RKNN_ERR_OUTPUT_INVALID: i32 = -9;
+pub const RKNN_ERR_DEVICE_UNMATCH: i32 = -10;
+pub const RKNN_ERR_INCOMPATILE_PRE_COMPILE_MODEL: i32 = -11;
+pub const RKNN_ERR_INCOMPATILE_OPTIMIZATION_LEVEL_VERSION: i32 = -12;
+pub const RKNN_ERR_TARGET_PLATFORM_UNMATCH: i32 = -13;
+pub const RKNN_MAX_DIMS: u32 = 16;
+pub const RKNN_MAX_NUM_CHANNEL: u32 = 15;
+pub const RKNN_MAX_NAME_LEN: u32 = 256;
+pub const RKNN_MAX_DYNAMIC_SHAPE_NUM: u32 = 512;
+pub type rknn_context = u32;
+pub const _rknn_query_cmd_RKNN_QUERY_IN_OUT_NUM: _rknn_query_cmd = 0;
+pub const _rknn_query_cmd_RKNN_QUERY_INPUT_ATTR: _rknn_query_cmd = 1;
+pub const _rknn_query_cmd_RKNN_QUERY_OUTPUT_ATTR: _rknn_query_cmd = 2;
+pub const _rknn_query_cmd_RKNN_QUERY_PERF_DETAIL: _rknn_query_cmd = 3;
+pub const _rknn_query_cmd_RKNN_QUERY_PERF_RUN: _rknn_query_cmd = 4;
+pub const _rknn_query_cmd_RKNN_QUERY_SDK_VERSION: _rknn_query_cmd = 5;
+pub const _rknn_query_cmd_RKNN_QUERY_MEM_SIZE: _rknn_query_cmd = 6;
+pub const _rknn_query_cmd_RKNN_QUERY_CUSTOM_STRING: _rknn_query_cmd = 7;
+pub const _rknn_query_cmd_RKNN_QUERY_NATIVE_INPUT_ATTR: _rknn_query_cmd = 8;
+pub const _rknn_query_cmd_RKNN_QUERY_NATIVE_OUTPUT_ATTR: _rknn_query_cmd = 9;
+pub const _rknn_query_cmd_RKNN_QUERY_NATIVE_NC1HWC2_INPUT_ATTR: _rknn_query_cmd = 8;
+pub const _rknn_query_cmd_RKNN_QUERY_NATIVE_NC1HWC2_OUTPUT_ATTR: _rknn_query_cmd = 9;
+pub const _rknn_query_cmd_RKNN_QUERY_NATIVE_NHWC_INPUT_ATTR: _rknn_query_cmd = 10;
+pub const _rknn_query_cmd_RKNN_QUERY_NATIVE_NHWC_OUTPUT_ATTR: _rknn_query_cmd = 11;
+pub const _rknn_query_cmd_RKNN_QUERY_DEVICE_MEM_INFO: _rknn_query_cmd = 12;
+pub const _rknn_query_cmd_RKNN_QUERY_INPUT_DYNAMIC_RANGE: _rknn_query_cmd = 13;
+pub const _rknn_query_cmd_RKNN_QUERY_CURRENT_INPUT_ATTR: _rknn_query_cmd = 14;
+pub const _rknn_query_cmd_RKNN_QUERY_CURRENT_OUTPUT_ATTR: _rknn_query_cmd = 15;
+pub const _rknn_query_cmd_RKNN_QUERY_CURRENT_NATIVE_INPUT_ATTR: _rknn_query_cmd = 16;
+pub const _rknn_query_cmd_RKNN_QUERY_CURRENT_NATIVE_OUTPUT_ATTR: _rknn_query_cmd = 17;
+pub const _rknn_query_cmd_RKNN_QUERY_CMD_MAX: _rknn_query_cmd = 18;
+pub type _rknn_query_cmd = ::std::os::raw::c_uint;
+pub use self::_rknn_query_cmd as rknn_query_cmd;
+pub const _rknn_tensor_type_RKNN_TENSOR_FLOAT32: _rknn_tensor_type = 0;
+pub const _rknn_tensor_type_RKNN_TENSOR_FLOAT16: _rknn_tensor_type = 1;
+pub const _rknn_tensor_type_RKNN_TENSOR_INT8: _rknn_tensor_type = 2;
+pub const _rknn_tensor_type_RKNN_TENSOR_UINT8: _rknn_tensor_type = 3;
+pub const _rknn_tensor_type_RKNN_TENSOR_INT16: _rknn_tensor_type = 4;
+pub const _rknn_tensor_type_RKNN_TENSOR_UINT16: _rknn_tensor_type = 5;
+pub const _rknn_tensor_type_RKNN_TENSOR_INT32: _rknn_tensor_type = 6;
+pub const _rknn_tensor_type_RKNN_TENSOR_UINT32: _rknn_tensor_type = 7;
+pub const _rknn_tensor_type_RKNN_TENSOR_INT64: _rknn_tensor_type = 8;
+pub const _rknn_tensor_type_RKNN_TENSOR_BOOL: _rknn_tensor_type = 9;
+pub const _rknn_tensor_type_RKNN_TENSOR_INT4: _rknn_tensor_type = 10;
+pub const _rknn_tensor_type_RKNN_TENSOR_TYPE_MAX: _rknn_tensor_type = 11;
+pub type _rknn_tensor_type = ::std::os::raw::c_uint;
+pub use self::_rknn_tensor_type as rknn_tensor_type;
+pub const _rknn_tensor_qnt_type_RKNN_TENSOR_QNT_NONE: _rknn_tensor_qnt_type = 0;
+pub const _rknn_tensor_qnt_type_RKNN_TENSOR_QNT_DFP: _rknn_tensor_qnt_type = 1;
+pub const _rknn_tensor_qnt_type_RKNN_TENSOR_QNT_AFFINE_ASYMMETRIC: _rknn_tensor_qnt_type = 2;
+pub const _rknn_tensor_qnt_type_RKNN_TENSOR_QNT_MAX: _rknn_tensor_qnt_type = 3;
+pub type _rknn_tensor_qnt_type = ::std::os::raw::c_uint;
+pub use self::_rknn_tensor_qnt_type as rknn_tensor_qnt_type;
+pub const _rknn_tensor_format_RKNN_TENSOR_NCHW: _rknn_tensor_format = 0;
+pub const _rknn_tensor_format_RKNN_TENSOR_NHWC: _rknn_tensor_format = 1;
+pub const _rknn_tensor_format_RKNN_TENSOR_NC1HWC2: _rknn_tensor_format = 2;
+pub const _rknn_tensor_format_RKNN_TENSOR_UNDEFINED: _rknn_tensor_format = 3;
+pub const _rknn_tensor_format_RKNN_TENSOR_FORMAT_MAX: _rknn_tensor_format = 4;
+pub type _rknn_tensor_format = ::std::os::raw::c_uint;
+pub use self::_rknn_tensor_format as rknn_tensor_format;
+pub const _rknn_core_mask_RKNN_NPU_CORE_AUTO: _rknn_core_mask = 0;
+pub const _rknn_core_mask_RKNN_NPU_CORE_0: _rknn_core_mask = 1;
+pub const _rknn_core_mask_RKNN_NPU_CORE_1: _rknn_core_mask = 2;
+pub const _rknn_core_mask_RKNN_NPU_CORE_2: _rknn_core_mask = 4;
+pub const _rknn_core_mask_RKNN_NPU_CORE_0_1: _rknn_core_mask = 3;
+pub const _rknn_core_mask_RKNN_NPU_CORE_0_1_2: _rknn_core_mask = 7;
+pub const _rknn_core_mask_RKNN_NPU_CORE_UNDEFINED: _rknn_core_mask = 8;
+pub type _rknn_core_mask = ::std::os::raw::c_uint;
+pub use self::_rknn_core_mask as rknn_core_mask;
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct _rknn_input_output_num {
+    pub n_input: u32,
+    pub n_output: u32,
+}
+#[test]
+fn bindgen_test_layout__rknn_input_output_num() {
+    const UNINIT: ::std::mem::MaybeUninit<_rknn_input_output_num> =
+        ::std::mem::MaybeUninit::uninit();
+    let ptr = UNINIT.as_ptr();
+    assert_eq!(
+        ::std::mem::size_of::<_rknn_input_output_num>(),
+        8usize,
+        concat!("Size of: ", stringify!(_rknn_input_output_num))
+    );
+    assert_eq!(
+        ::std::mem::align_of::<_rknn_input_output_num>(),
+        4usize,
+        concat!("Alignment of ", stringify!(_rknn_input_output_num))
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).n_input) as usize - ptr as usize },
+        0usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_input_output_num),
+            "::",
+            stringify!(n_input)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).n_output) as usize - ptr as usize },
+        4usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_input_output_num),
+            "::",
+            stringify!(n_output)
+        )
+    );
+}
+pub type rknn_input_output_num = _rknn_input_output_num;
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct _rknn_tensor_attr {
+    pub index: u32,
+    pub n_dims: u32,
+    pub dims: [u32; 16usize],
+    pub name: [::std::os::raw::c_char; 256usize],
+    pub n_elems: u32,
+    pub size: u32,
+    pub fmt: rknn_tensor_format,
+    pub type_: rknn_tensor_type,
+    pub qnt_type: rknn_tensor_qnt_type,
+    pub fl: i8,
+    pub zp: i32,
+    pub scale: f32,
+    pub w_stride: u32,
+    pub size_with_stride: u32,
+    pub pass_through: u8,
+    pub h_stride: u32,
+}
+#[test]
+fn bindgen_test_layout__rknn_tensor_attr() {
+    const UNINIT: ::std::mem::MaybeUninit<_rknn_tensor_attr> = ::std::mem::MaybeUninit::uninit();
+    let ptr = UNINIT.as_ptr();
+    assert_eq!(
+        ::std::mem::size_of::<_rknn_tensor_attr>(),
+        376usize,
+        concat!("Size of: ", stringify!(_rknn_tensor_attr))
+    );
+    assert_eq!(
+        ::std::mem::align_of::<_rknn_tensor_attr>(),
+        4usize,
+        concat!("Alignment of ", stringify!(_rknn_tensor_attr))
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).index) as usize - ptr as usize },
+        0usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_tensor_attr),
+            "::",
+            stringify!(index)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).n_dims) as usize - ptr as usize },
+        4usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_tensor_attr),
+            "::",
+            stringify!(n_dims)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).dims) as usize - ptr as usize },
+        8usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_tensor_attr),
+            "::",
+            stringify!(dims)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).name) as usize - ptr as usize },
+        72usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_tensor_attr),
+            "::",
+            stringify!(name)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).n_elems) as usize - ptr as usize },
+        328usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_tensor_attr),
+            "::",
+            stringify!(n_elems)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).size) as usize - ptr as usize },
+        332usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_tensor_attr),
+            "::",
+            stringify!(size)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).fmt) as usize - ptr as usize },
+        336usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_tensor_attr),
+            "::",
+            stringify!(fmt)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).type_) as usize - ptr as usize },
+        340usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_tensor_attr),
+            "::",
+            stringify!(type_)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).qnt_type) as usize - ptr as usize },
+        344usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_tensor_attr),
+            "::",
+            stringify!(qnt_type)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).fl) as usize - ptr as usize },
+        348usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_tensor_attr),
+            "::",
+            stringify!(fl)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).zp) as usize - ptr as usize },
+        352usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_tensor_attr),
+            "::",
+            stringify!(zp)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).scale) as usize - ptr as usize },
+        356usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_tensor_attr),
+            "::",
+            stringify!(scale)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).w_stride) as usize - ptr as usize },
+        360usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_tensor_attr),
+            "::",
+            stringify!(w_stride)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).size_with_stride) as usize - ptr as usize },
+        364usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_tensor_attr),
+            "::",
+            stringify!(size_with_stride)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).pass_through) as usize - ptr as usize },
+        368usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_tensor_attr),
+            "::",
+            stringify!(pass_through)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).h_stride) as usize - ptr as usize },
+        372usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_tensor_attr),
+            "::",
+            stringify!(h_stride)
+        )
+    );
+}
+pub type rknn_tensor_attr = _rknn_tensor_attr;
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct _rknn_input_range {
+    pub index: u32,
+    pub shape_number: u32,
+    pub fmt: rknn_tensor_format,
+    pub name: [::std::os::raw::c_char; 256usize],
+    pub dyn_range: [[u32; 16usize]; 512usize],
+    pub n_dims: u32,
+}
+#[test]
+fn bindgen_test_layout__rknn_input_range() {
+    const UNINIT: ::std::mem::MaybeUninit<_rknn_input_range> = ::std::mem::MaybeUninit::uninit();
+    let ptr = UNINIT.as_ptr();
+    assert_eq!(
+        ::std::mem::size_of::<_rknn_input_range>(),
+        33040usize,
+        concat!("Size of: ", stringify!(_rknn_input_range))
+    );
+    assert_eq!(
+        ::std::mem::align_of::<_rknn_input_range>(),
+        4usize,
+        concat!("Alignment of ", stringify!(_rknn_input_range))
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).index) as usize - ptr as usize },
+        0usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_input_range),
+            "::",
+            stringify!(index)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).shape_number) as usize - ptr as usize },
+        4usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_input_range),
+            "::",
+            stringify!(shape_number)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).fmt) as usize - ptr as usize },
+        8usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_input_range),
+            "::",
+            stringify!(fmt)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).name) as usize - ptr as usize },
+        12usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_input_range),
+            "::",
+            stringify!(name)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).dyn_range) as usize - ptr as usize },
+        268usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_input_range),
+            "::",
+            stringify!(dyn_range)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).n_dims) as usize - ptr as usize },
+        33036usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_input_range),
+            "::",
+            stringify!(n_dims)
+        )
+    );
+}
+pub type rknn_input_range = _rknn_input_range;
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct _rknn_perf_detail {
+    pub perf_data: *mut ::std::os::raw::c_char,
+    pub data_len: u64,
+}
+#[test]
+fn bindgen_test_layout__rknn_perf_detail() {
+    const UNINIT: ::std::mem::MaybeUninit<_rknn_perf_detail> = ::std::mem::MaybeUninit::uninit();
+    let ptr = UNINIT.as_ptr();
+    assert_eq!(
+        ::std::mem::size_of::<_rknn_perf_detail>(),
+        16usize,
+        concat!("Size of: ", stringify!(_rknn_perf_detail))
+    );
+    assert_eq!(
+        ::std::mem::align_of::<_rknn_perf_detail>(),
+        8usize,
+        concat!("Alignment of ", stringify!(_rknn_perf_detail))
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).perf_data) as usize - ptr as usize },
+        0usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_perf_detail),
+            "::",
+            stringify!(perf_data)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).data_len) as usize - ptr as usize },
+        8usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_perf_detail),
+            "::",
+            stringify!(data_len)
+        )
+    );
+}
+pub type rknn_perf_detail = _rknn_perf_detail;
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct _rknn_perf_run {
+    pub run_duration: i64,
+}
+#[test]
+fn bindgen_test_layout__rknn_perf_run() {
+    const UNINIT: ::std::mem::MaybeUninit<_rknn_perf_run> = ::std::mem::MaybeUninit::uninit();
+    let ptr = UNINIT.as_ptr();
+    assert_eq!(
+        ::std::mem::size_of::<_rknn_perf_run>(),
+        8usize,
+        concat!("Size of: ", stringify!(_rknn_perf_run))
+    );
+    assert_eq!(
+        ::std::mem::align_of::<_rknn_perf_run>(),
+        8usize,
+        concat!("Alignment of ", stringify!(_rknn_perf_run))
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).run_duration) as usize - ptr as usize },
+        0usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_perf_run),
+            "::",
+            stringify!(run_duration)
+        )
+    );
+}
+pub type rknn_perf_run = _rknn_perf_run;
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct _rknn_sdk_version {
+    pub api_version: [::std::os::raw::c_char; 256usize],
+    pub drv_version: [::std::os::raw::c_char; 256usize],
+}
+#[test]
+fn bindgen_test_layout__rknn_sdk_version() {
+    const UNINIT: ::std::mem::MaybeUninit<_rknn_sdk_version> = ::std::mem::MaybeUninit::uninit();
+    let ptr = UNINIT.as_ptr();
+    assert_eq!(
+        ::std::mem::size_of::<_rknn_sdk_version>(),
+        512usize,
+        concat!("Size of: ", stringify!(_rknn_sdk_version))
+    );
+    assert_eq!(
+        ::std::mem::align_of::<_rknn_sdk_version>(),
+        1usize,
+        concat!("Alignment of ", stringify!(_rknn_sdk_version))
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).api_version) as usize - ptr as usize },
+        0usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_sdk_version),
+            "::",
+            stringify!(api_version)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).drv_version) as usize - ptr as usize },
+        256usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_sdk_version),
+            "::",
+            stringify!(drv_version)
+        )
+    );
+}
+pub type rknn_sdk_version = _rknn_sdk_version;
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct _rknn_mem_size {
+    pub total_weight_size: u32,
+    pub total_internal_size: u32,
+    pub total_dma_allocated_size: u64,
+    pub total_sram_size: u32,
+    pub free_sram_size: u32,
+    pub reserved: [u32; 12usize],
+}
+#[test]
+fn bindgen_test_layout__rknn_mem_size() {
+    const UNINIT: ::std::mem::MaybeUninit<_rknn_mem_size> = ::std::mem::MaybeUninit::uninit();
+    let ptr = UNINIT.as_ptr();
+    assert_eq!(
+        ::std::mem::size_of::<_rknn_mem_size>(),
+        72usize,
+        concat!("Size of: ", stringify!(_rknn_mem_size))
+    );
+    assert_eq!(
+        ::std::mem::align_of::<_rknn_mem_size>(),
+        8usize,
+        concat!("Alignment of ", stringify!(_rknn_mem_size))
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).total_weight_size) as usize - ptr as usize },
+        0usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_mem_size),
+            "::",
+            stringify!(total_weight_size)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).total_internal_size) as usize - ptr as usize },
+        4usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_mem_size),
+            "::",
+            stringify!(total_internal_size)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).total_dma_allocated_size) as usize - ptr as usize },
+        8usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_mem_size),
+            "::",
+            stringify!(total_dma_allocated_size)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).total_sram_size) as usize - ptr as usize },
+        16usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_mem_size),
+            "::",
+            stringify!(total_sram_size)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).free_sram_size) as usize - ptr as usize },
+        20usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_mem_size),
+            "::",
+            stringify!(free_sram_size)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).reserved) as usize - ptr as usize },
+        24usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_mem_size),
+            "::",
+            stringify!(reserved)
+        )
+    );
+}
+pub type rknn_mem_size = _rknn_mem_size;
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct _rknn_custom_string {
+    pub string: [::std::os::raw::c_char; 1024usize],
+}
+#[test]
+fn bindgen_test_layout__rknn_custom_string() {
+    const UNINIT: ::std::mem::MaybeUninit<_rknn_custom_string> = ::std::mem::MaybeUninit::uninit();
+    let ptr = UNINIT.as_ptr();
+    assert_eq!(
+        ::std::mem::size_of::<_rknn_custom_string>(),
+        1024usize,
+        concat!("Size of: ", stringify!(_rknn_custom_string))
+    );
+    assert_eq!(
+        ::std::mem::align_of::<_rknn_custom_string>(),
+        1usize,
+        concat!("Alignment of ", stringify!(_rknn_custom_string))
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).string) as usize - ptr as usize },
+        0usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_custom_string),
+            "::",
+            stringify!(string)
+        )
+    );
+}
+pub type rknn_custom_string = _rknn_custom_string;
+pub const _rknn_tensor_mem_flags_RKNN_TENSOR_MEMORY_FLAGS_ALLOC_INSIDE: _rknn_tensor_mem_flags = 1;
+pub const _rknn_tensor_mem_flags_RKNN_TENSOR_MEMORY_FLAGS_FROM_FD: _rknn_tensor_mem_flags = 2;
+pub const _rknn_tensor_mem_flags_RKNN_TENSOR_MEMORY_FLAGS_FROM_PHYS: _rknn_tensor_mem_flags = 3;
+pub const _rknn_tensor_mem_flags_RKNN_TENSOR_MEMORY_FLAGS_UNKNOWN: _rknn_tensor_mem_flags = 4;
+pub type _rknn_tensor_mem_flags = ::std::os::raw::c_uint;
+pub use self::_rknn_tensor_mem_flags as rknn_tensor_mem_flags;
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct _rknn_tensor_memory {
+    pub virt_addr: *mut ::std::os::raw::c_void,
+    pub phys_addr: u64,
+    pub fd: i32,
+    pub offset: i32,
+    pub size: u32,
+    pub flags: u32,
+    pub priv_data: *mut ::std::os::raw::c_void,
+}
+#[test]
+fn bindgen_test_layout__rknn_tensor_memory() {
+    const UNINIT: ::std::mem::MaybeUninit<_rknn_tensor_memory> = ::std::mem::MaybeUninit::uninit();
+    let ptr = UNINIT.as_ptr();
+    assert_eq!(
+        ::std::mem::size_of::<_rknn_tensor_memory>(),
+        40usize,
+        concat!("Size of: ", stringify!(_rknn_tensor_memory))
+    );
+    assert_eq!(
+        ::std::mem::align_of::<_rknn_tensor_memory>(),
+        8usize,
+        concat!("Alignment of ", stringify!(_rknn_tensor_memory))
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).virt_addr) as usize - ptr as usize },
+        0usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_tensor_memory),
+            "::",
+            stringify!(virt_addr)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).phys_addr) as usize - ptr as usize },
+        8usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_tensor_memory),
+            "::",
+            stringify!(phys_addr)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).fd) as usize - ptr as usize },
+        16usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_tensor_memory),
+            "::",
+            stringify!(fd)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).offset) as usize - ptr as usize },
+        20usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_tensor_memory),
+            "::",
+            stringify!(offset)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).size) as usize - ptr as usize },
+        24usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_tensor_memory),
+            "::",
+            stringify!(size)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).flags) as usize - ptr as usize },
+        28usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_tensor_memory),
+            "::",
+            stringify!(flags)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).priv_data) as usize - ptr as usize },
+        32usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_tensor_memory),
+            "::",
+            stringify!(priv_data)
+        )
+    );
+}
+pub type rknn_tensor_mem = _rknn_tensor_memory;
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct _rknn_input {
+    pub index: u32,
+    pub buf: *mut ::std::os::raw::c_void,
+    pub size: u32,
+    pub pass_through: u8,
+    pub type_: rknn_tensor_type,
+    pub fmt: rknn_tensor_format,
+}
+#[test]
+fn bindgen_test_layout__rknn_input() {
+    const UNINIT: ::std::mem::MaybeUninit<_rknn_input> = ::std::mem::MaybeUninit::uninit();
+    let ptr = UNINIT.as_ptr();
+    assert_eq!(
+        ::std::mem::size_of::<_rknn_input>(),
+        24usize,
+        concat!("Size of: ", stringify!(_rknn_input))
+    );
+    assert_eq!(
+        ::std::mem::align_of::<_rknn_input>(),
+        4usize,
+        concat!("Alignment of ", stringify!(_rknn_input))
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).index) as usize - ptr as usize },
+        0usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_input),
+            "::",
+            stringify!(index)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).buf) as usize - ptr as usize },
+        4usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_input),
+            "::",
+            stringify!(buf)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).size) as usize - ptr as usize },
+        8usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_input),
+            "::",
+            stringify!(size)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).pass_through) as usize - ptr as usize },
+        12usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_input),
+            "::",
+            stringify!(pass_through)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).type_) as usize - ptr as usize },
+        16usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_input),
+            "::",
+            stringify!(type_)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).fmt) as usize - ptr as usize },
+        20usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_input),
+            "::",
+            stringify!(fmt)
+        )
+    );
+}
+pub type rknn_input = _rknn_input;
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct _rknn_output {
+    pub want_float: u8,
+    pub is_prealloc: u8,
+    pub index: u32,
+    pub buf: *mut ::std::os::raw::c_void,
+    pub size: u32,
+}
+#[test]
+fn bindgen_test_layout__rknn_output() {
+    const UNINIT: ::std::mem::MaybeUninit<_rknn_output> = ::std::mem::MaybeUninit::uninit();
+    let ptr = UNINIT.as_ptr();
+    assert_eq!(
+        ::std::mem::size_of::<_rknn_output>(),
+        16usize,
+        concat!("Size of: ", stringify!(_rknn_output))
+    );
+    assert_eq!(
+        ::std::mem::align_of::<_rknn_output>(),
+        4usize,
+        concat!("Alignment of ", stringify!(_rknn_output))
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).want_float) as usize - ptr as usize },
+        0usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_output),
+            "::",
+            stringify!(want_float)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).is_prealloc) as usize - ptr as usize },
+        1usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_output),
+            "::",
+            stringify!(is_prealloc)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).index) as usize - ptr as usize },
+        4usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_output),
+            "::",
+            stringify!(index)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).buf) as usize - ptr as usize },
+        8usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_output),
+            "::",
+            stringify!(buf)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).size) as usize - ptr as usize },
+        12usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_output),
+            "::",
+            stringify!(size)
+        )
+    );
+}
+pub type rknn_output = _rknn_output;
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct _rknn_init_extend {
+    pub ctx: rknn_context,
+    pub real_model_offset: i32,
+    pub real_model_size: u32,
+    pub reserved: [u8; 120usize],
+}
+#[test]
+fn bindgen_test_layout__rknn_init_extend() {
+    const UNINIT: ::std::mem::MaybeUninit<_rknn_init_extend> = ::std::mem::MaybeUninit::uninit();
+    let ptr = UNINIT.as_ptr();
+    assert_eq!(
+        ::std::mem::size_of::<_rknn_init_extend>(),
+        132usize,
+        concat!("Size of: ", stringify!(_rknn_init_extend))
+    );
+    assert_eq!(
+        ::std::mem::align_of::<_rknn_init_extend>(),
+        4usize,
+        concat!("Alignment of ", stringify!(_rknn_init_extend))
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).ctx) as usize - ptr as usize },
+        0usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_init_extend),
+            "::",
+            stringify!(ctx)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).real_model_offset) as usize - ptr as usize },
+        4usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_init_extend),
+            "::",
+            stringify!(real_model_offset)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).real_model_size) as usize - ptr as usize },
+        8usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_init_extend),
+            "::",
+            stringify!(real_model_size)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).reserved) as usize - ptr as usize },
+        12usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_init_extend),
+            "::",
+            stringify!(reserved)
+        )
+    );
+}
+pub type rknn_init_extend = _rknn_init_extend;
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct _rknn_run_extend {
+    pub frame_id: u64,
+    pub non_block: i32,
+    pub timeout_ms: i32,
+    pub fence_fd: i32,
+}
+#[test]
+fn bindgen_test_layout__rknn_run_extend() {
+    const UNINIT: ::std::mem::MaybeUninit<_rknn_run_extend> = ::std::mem::MaybeUninit::uninit();
+    let ptr = UNINIT.as_ptr();
+    assert_eq!(
+        ::std::mem::size_of::<_rknn_run_extend>(),
+        24usize,
+        concat!("Size of: ", stringify!(_rknn_run_extend))
+    );
+    assert_eq!(
+        ::std::mem::align_of::<_rknn_run_extend>(),
+        8usize,
+        concat!("Alignment of ", stringify!(_rknn_run_extend))
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).frame_id) as usize - ptr as usize },
+        0usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_run_extend),
+            "::",
+            stringify!(frame_id)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).non_block) as usize - ptr as usize },
+        8usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_run_extend),
+            "::",
+            stringify!(non_block)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).timeout_ms) as usize - ptr as usize },
+        12usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_run_extend),
+            "::",
+            stringify!(timeout_ms)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).fence_fd) as usize - ptr as usize },
+        16usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_run_extend),
+            "::",
+            stringify!(fence_fd)
+        )
+    );
+}
+pub type rknn_run_extend = _rknn_run_extend;
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct _rknn_output_extend {
+    pub frame_id: u64,
+}
+#[test]
+fn bindgen_test_layout__rknn_output_extend() {
+    const UNINIT: ::std::mem::MaybeUninit<_rknn_output_extend> = ::std::mem::MaybeUninit::uninit();
+    let ptr = UNINIT.as_ptr();
+    assert_eq!(
+        ::std::mem::size_of::<_rknn_output_extend>(),
+        8usize,
+        concat!("Size of: ", stringify!(_rknn_output_extend))
+    );
+    assert_eq!(
+        ::std::mem::align_of::<_rknn_output_extend>(),
+        8usize,
+        concat!("Alignment of ", stringify!(_rknn_output_extend))
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).frame_id) as usize - ptr as usize },
+        0usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_output_extend),
+            "::",
+            stringify!(frame_id)
+        )
+    );
+}
+pub type rknn_output_extend = _rknn_output_extend;
+extern "C" {
+    pub fn rknn_init(
+        context: *mut rknn_context,
+        model: *mut ::std::os::raw::c_void,
+        size: u32,
+        flag: u32,
+        extend: *mut rknn_init_extend,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rknn_dup_context(
+        context_in: *mut rknn_context,
+        context_out: *mut rknn_context,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rknn_destroy(context: rknn_context) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rknn_query(
+        context: rknn_context,
+        cmd: rknn_query_cmd,
+        info: *mut ::std::os::raw::c_void,
+        size: u32,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rknn_inputs_set(
+        context: rknn_context,
+        n_inputs: u32,
+        inputs: *mut rknn_input,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rknn_set_batch_core_num(
+        context: rknn_context,
+        core_num: ::std::os::raw::c_int,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rknn_set_core_mask(
+        context: rknn_context,
+        core_mask: rknn_core_mask,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rknn_run(context: rknn_context, extend: *mut rknn_run_extend) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rknn_wait(context: rknn_context, extend: *mut rknn_run_extend) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rknn_outputs_get(
+        context: rknn_context,
+        n_outputs: u32,
+        outputs: *mut rknn_output,
+        extend: *mut rknn_output_extend,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rknn_outputs_release(
+        context: rknn_context,
+        n_ouputs: u32,
+        outputs: *mut rknn_output,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rknn_create_mem_from_phys(
+        ctx: rknn_context,
+        phys_addr: u64,
+        virt_addr: *mut ::std::os::raw::c_void,
+        size: u32,
+    ) -> *mut rknn_tensor_mem;
+}
+extern "C" {
+    pub fn rknn_create_mem_from_fd(
+        ctx: rknn_context,
+        fd: i32,
+        virt_addr: *mut ::std::os::raw::c_void,
+        size: u32,
+        offset: i32,
+    ) -> *mut rknn_tensor_mem;
+}
+extern "C" {
+    pub fn rknn_create_mem_from_mb_blk(
+        ctx: rknn_context,
+        mb_blk: *mut ::std::os::raw::c_void,
+        offset: i32,
+    ) -> *mut rknn_tensor_mem;
+}
+extern "C" {
+    pub fn rknn_create_mem(ctx: rknn_context, size: u32) -> *mut rknn_tensor_mem;
+}
+extern "C" {
+    pub fn rknn_destroy_mem(ctx: rknn_context, mem: *mut rknn_tensor_mem) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rknn_set_weight_mem(
+        ctx: rknn_context,
+        mem: *mut rknn_tensor_mem,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rknn_set_internal_mem(
+        ctx: rknn_context,
+        mem: *mut rknn_tensor_mem,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rknn_set_io_mem(
+        ctx: rknn_context,
+        mem: *mut rknn_tensor_mem,
+        attr: *mut rknn_tensor_attr,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rknn_set_input_shape(
+        ctx: rknn_context,
+        attr: *mut rknn_tensor_attr,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rknn_set_input_shapes(
+        ctx: rknn_context,
+        n_inputs: u32,
+        attr: *mut rknn_tensor_attr,
+    ) -> ::std::os::raw::c_int;
+}
+pub type rknn_matmul_ctx = rknn_context;
+pub const _rknn_matmul_type_RKNN_FLOAT16_MM_FLOAT16_TO_FLOAT32: _rknn_matmul_type = 1;
+pub const _rknn_matmul_type_RKNN_INT8_MM_INT8_TO_INT32: _rknn_matmul_type = 2;
+pub const _rknn_matmul_type_RKNN_INT4_MM_INT4_TO_INT16: _rknn_matmul_type = 10;
+pub type _rknn_matmul_type = ::std::os::raw::c_uint;
+pub use self::_rknn_matmul_type as rknn_matmul_type;
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct _rknn_matmul_tensor_attr {
+    pub name: [::std::os::raw::c_char; 256usize],
+    pub n_dims: u32,
+    pub dims: [u32; 16usize],
+    pub size: u32,
+    pub type_: rknn_tensor_type,
+}
+#[test]
+fn bindgen_test_layout__rknn_matmul_tensor_attr() {
+    const UNINIT: ::std::mem::MaybeUninit<_rknn_matmul_tensor_attr> =
+        ::std::mem::MaybeUninit::uninit();
+    let ptr = UNINIT.as_ptr();
+    assert_eq!(
+        ::std::mem::size_of::<_rknn_matmul_tensor_attr>(),
+        332usize,
+        concat!("Size of: ", stringify!(_rknn_matmul_tensor_attr))
+    );
+    assert_eq!(
+        ::std::mem::align_of::<_rknn_matmul_tensor_attr>(),
+        4usize,
+        concat!("Alignment of ", stringify!(_rknn_matmul_tensor_attr))
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).name) as usize - ptr as usize },
+        0usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_matmul_tensor_attr),
+            "::",
+            stringify!(name)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).n_dims) as usize - ptr as usize },
+        256usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_matmul_tensor_attr),
+            "::",
+            stringify!(n_dims)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).dims) as usize - ptr as usize },
+        260usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_matmul_tensor_attr),
+            "::",
+            stringify!(dims)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).size) as usize - ptr as usize },
+        324usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_matmul_tensor_attr),
+            "::",
+            stringify!(size)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).type_) as usize - ptr as usize },
+        328usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_matmul_tensor_attr),
+            "::",
+            stringify!(type_)
+        )
+    );
+}
+pub type rknn_matmul_tensor_attr = _rknn_matmul_tensor_attr;
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct _rknn_matmul_io_attr {
+    pub A: rknn_matmul_tensor_attr,
+    pub B: rknn_matmul_tensor_attr,
+    pub C: rknn_matmul_tensor_attr,
+}
+#[test]
+fn bindgen_test_layout__rknn_matmul_io_attr() {
+    const UNINIT: ::std::mem::MaybeUninit<_rknn_matmul_io_attr> = ::std::mem::MaybeUninit::uninit();
+    let ptr = UNINIT.as_ptr();
+    assert_eq!(
+        ::std::mem::size_of::<_rknn_matmul_io_attr>(),
+        996usize,
+        concat!("Size of: ", stringify!(_rknn_matmul_io_attr))
+    );
+    assert_eq!(
+        ::std::mem::align_of::<_rknn_matmul_io_attr>(),
+        4usize,
+        concat!("Alignment of ", stringify!(_rknn_matmul_io_attr))
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).A) as usize - ptr as usize },
+        0usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_matmul_io_attr),
+            "::",
+            stringify!(A)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).B) as usize - ptr as usize },
+        332usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_matmul_io_attr),
+            "::",
+            stringify!(B)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).C) as usize - ptr as usize },
+        664usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(_rknn_matmul_io_attr),
+            "::",
+            stringify!(C)
+        )
+    );
+}
+pub type rknn_matmul_io_attr = _rknn_matmul_io_attr;
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct rknn_matmul_info_t {
+    pub M: i32,
+    pub K: i32,
+    pub N: i32,
+    pub type_: rknn_matmul_type,
+    pub native_layout: i32,
+    pub perf_layout: i32,
+}
+#[test]
+fn bindgen_test_layout_rknn_matmul_info_t() {
+    const UNINIT: ::std::mem::MaybeUninit<rknn_matmul_info_t> = ::std::mem::MaybeUninit::uninit();
+    let ptr = UNINIT.as_ptr();
+    assert_eq!(
+        ::std::mem::size_of::<rknn_matmul_info_t>(),
+        24usize,
+        concat!("Size of: ", stringify!(rknn_matmul_info_t))
+    );
+    assert_eq!(
+        ::std::mem::align_of::<rknn_matmul_info_t>(),
+        4usize,
+        concat!("Alignment of ", stringify!(rknn_matmul_info_t))
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).M) as usize - ptr as usize },
+        0usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(rknn_matmul_info_t),
+            "::",
+            stringify!(M)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).K) as usize - ptr as usize },
+        4usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(rknn_matmul_info_t),
+            "::",
+            stringify!(K)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).N) as usize - ptr as usize },
+        8usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(rknn_matmul_info_t),
+            "::",
+            stringify!(N)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).type_) as usize - ptr as usize },
+        12usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(rknn_matmul_info_t),
+            "::",
+            stringify!(type_)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).native_layout) as usize - ptr as usize },
+        16usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(rknn_matmul_info_t),
+            "::",
+            stringify!(native_layout)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).perf_layout) as usize - ptr as usize },
+        20usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(rknn_matmul_info_t),
+            "::",
+            stringify!(perf_layout)
+        )
+    );
+}
+pub type rknn_matmul_info = rknn_matmul_info_t;
+extern "C" {
+    pub fn rknn_matmul_create(
+        ctx: *mut rknn_matmul_ctx,
+        info: *mut rknn_matmul_info,
+        io_attr: *mut rknn_matmul_io_attr,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rknn_matmul_set_io_mem(
+        ctx: rknn_matmul_ctx,
+        mem: *mut rknn_tensor_mem,
+        attr: *mut rknn_matmul_tensor_attr,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rknn_matmul_set_core_mask(
+        context: rknn_matmul_ctx,
+        core_mask: rknn_core_mask,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rknn_matmul_run(ctx: rknn_matmul_ctx) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rknn_matmul_destroy(ctx: rknn_matmul_ctx) -> ::std::os::raw::c_int;
+}
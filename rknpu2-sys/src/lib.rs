@@ -3,4 +3,185 @@
 #![allow(non_snake_case)]
 #![allow(unused_mut)]
 #![allow(unused)]
+
+// Generated by build.rs with the `bindgen` feature, checked in otherwise.
+// build.rs sets `rknn_sdk` to the selected SDK.
+#[cfg(feature = "bindgen")]
 include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
+
+#[cfg(all(
+    not(feature = "bindgen"),
+    rknn_sdk = "v1_5_2",
+    target_pointer_width = "64"
+))]
+include!("bindings/v1_5_2_64.rs");
+
+#[cfg(all(
+    not(feature = "bindgen"),
+    rknn_sdk = "v1_5_2",
+    target_pointer_width = "32"
+))]
+include!("bindings/v1_5_2_32.rs");
+
+#[cfg(all(
+    not(feature = "bindgen"),
+    rknn_sdk = "v1_6_0",
+    target_pointer_width = "64"
+))]
+include!("bindings/v1_6_0_64.rs");
+
+#[cfg(all(
+    not(feature = "bindgen"),
+    rknn_sdk = "v1_6_0",
+    target_pointer_width = "32"
+))]
+include!("bindings/v1_6_0_32.rs");
+
+#[cfg(all(
+    not(feature = "bindgen"),
+    rknn_sdk = "v2_3_0",
+    target_pointer_width = "64"
+))]
+include!("bindings/v2_3_0_64.rs");
+
+#[cfg(all(
+    not(feature = "bindgen"),
+    rknn_sdk = "v2_3_0",
+    target_pointer_width = "32"
+))]
+include!("bindings/v2_3_0_32.rs");
+
+/// The checked in bindings must match the ones generated from the headers.
+#[cfg(all(test, feature = "bindgen"))]
+mod checked_in_tests {
+    use std::mem::{align_of, offset_of, size_of};
+
+    #[allow(clashing_extern_declarations)]
+    mod checked_in {
+        #[cfg(all(rknn_sdk = "v1_5_2", target_pointer_width = "64"))]
+        include!("bindings/v1_5_2_64.rs");
+        #[cfg(all(rknn_sdk = "v1_5_2", target_pointer_width = "32"))]
+        include!("bindings/v1_5_2_32.rs");
+        #[cfg(all(rknn_sdk = "v1_6_0", target_pointer_width = "64"))]
+        include!("bindings/v1_6_0_64.rs");
+        #[cfg(all(rknn_sdk = "v1_6_0", target_pointer_width = "32"))]
+        include!("bindings/v1_6_0_32.rs");
+        #[cfg(all(rknn_sdk = "v2_3_0", target_pointer_width = "64"))]
+        include!("bindings/v2_3_0_64.rs");
+        #[cfg(all(rknn_sdk = "v2_3_0", target_pointer_width = "32"))]
+        include!("bindings/v2_3_0_32.rs");
+    }
+
+    macro_rules! assert_same_layout {
+        ($($ty:ident { $($field:ident),* })*) => {$(
+            assert_eq!(size_of::<crate::$ty>(), size_of::<checked_in::$ty>(), stringify!($ty));
+            assert_eq!(align_of::<crate::$ty>(), align_of::<checked_in::$ty>(), stringify!($ty));
+            $(assert_eq!(
+                offset_of!(crate::$ty, $field),
+                offset_of!(checked_in::$ty, $field),
+                concat!(stringify!($ty), "::", stringify!($field))
+            );)*
+        )*};
+    }
+
+    macro_rules! assert_same_value {
+        ($($name:ident),*) => {$(
+            assert_eq!(crate::$name as i64, checked_in::$name as i64, stringify!($name));
+        )*};
+    }
+
+    #[test]
+    fn test_layout() {
+        assert_eq!(size_of::<crate::rknn_context>(), size_of::<checked_in::rknn_context>());
+        assert_same_layout! {
+            rknn_input_output_num { n_input, n_output }
+            rknn_tensor_attr {
+                index, n_dims, dims, name, n_elems, size, fmt, type_, qnt_type, fl, zp, scale,
+                w_stride, size_with_stride, pass_through, h_stride
+            }
+            rknn_input_range { index, shape_number, fmt, name, dyn_range, n_dims }
+            rknn_perf_detail { perf_data, data_len }
+            rknn_perf_run { run_duration }
+            rknn_sdk_version { api_version, drv_version }
+            rknn_mem_size {
+                total_weight_size, total_internal_size, total_dma_allocated_size,
+                total_sram_size, free_sram_size, reserved
+            }
+            rknn_custom_string { string }
+            rknn_tensor_mem { virt_addr, phys_addr, fd, offset, size, flags, priv_data }
+            rknn_input { index, buf, size, pass_through, type_, fmt }
+            rknn_output { want_float, is_prealloc, index, buf, size }
+            rknn_init_extend { ctx, real_model_offset, real_model_size, reserved }
+            rknn_run_extend { frame_id, non_block, timeout_ms, fence_fd }
+            rknn_output_extend { frame_id }
+            rknn_matmul_tensor_attr { name, n_dims, dims, size, type_ }
+            rknn_matmul_io_attr { A, B, C }
+        }
+
+        #[cfg(rknn_sdk = "v1_5_2")]
+        assert_same_layout! {
+            rknn_matmul_info { M, K, N, type_, native_layout, perf_layout }
+        }
+
+        #[cfg(not(rknn_sdk = "v1_5_2"))]
+        assert_same_layout! {
+            rknn_matmul_info {
+                M, K, N, type_, B_layout, B_quant_type, AC_layout, AC_quant_type,
+                iommu_domain_id, reserved
+            }
+            rknn_matmul_shape { M, K, N }
+            rknn_quant_params { name, scale, scale_len, zp, zp_len }
+            rknn_gpu_op_context { cl_context, cl_command_queue, cl_kernel }
+            rknn_custom_op_context { target, internal_ctx, gpu_ctx, priv_data }
+            rknn_custom_op_tensor { attr, mem }
+            rknn_custom_op_attr { name, dtype, n_elems, data }
+            rknn_custom_op {
+                version, target, op_type, cl_kernel_name, cl_kernel_source, cl_source_size,
+                cl_build_options, init, prepare, compute, compute_native, destroy
+            }
+        }
+
+        #[cfg(rknn_sdk = "v2_3_0")]
+        assert_same_layout! {
+            rknn_matmul_info { group_size }
+        }
+    }
+
+    #[test]
+    fn test_values() {
+        assert_same_value!(
+            RKNN_FLAG_COLLECT_PERF_MASK,
+            RKNN_FLAG_DISABLE_FLUSH_OUTPUT_MEM_CACHE,
+            RKNN_ERR_FAIL,
+            RKNN_ERR_TARGET_PLATFORM_UNMATCH,
+            RKNN_MAX_DIMS,
+            RKNN_MAX_NAME_LEN,
+            RKNN_MAX_DYNAMIC_SHAPE_NUM,
+            _rknn_query_cmd_RKNN_QUERY_CUSTOM_STRING,
+            _rknn_query_cmd_RKNN_QUERY_CURRENT_NATIVE_OUTPUT_ATTR,
+            _rknn_query_cmd_RKNN_QUERY_CMD_MAX,
+            _rknn_tensor_type_RKNN_TENSOR_INT4,
+            _rknn_tensor_type_RKNN_TENSOR_TYPE_MAX,
+            _rknn_tensor_qnt_type_RKNN_TENSOR_QNT_AFFINE_ASYMMETRIC,
+            _rknn_tensor_format_RKNN_TENSOR_FORMAT_MAX,
+            _rknn_core_mask_RKNN_NPU_CORE_0_1_2,
+            _rknn_core_mask_RKNN_NPU_CORE_UNDEFINED,
+            _rknn_tensor_mem_flags_RKNN_TENSOR_MEMORY_FLAGS_UNKNOWN,
+            _rknn_matmul_type_RKNN_INT4_MM_INT4_TO_INT16
+        );
+
+        #[cfg(not(rknn_sdk = "v1_5_2"))]
+        assert_same_value!(
+            _rknn_matmul_type_RKNN_FLOAT16_MM_INT8_TO_FLOAT32,
+            _rknn_matmul_type_RKNN_FLOAT16_MM_INT4_TO_FLOAT32,
+            _rknn_matmul_type_RKNN_INT8_MM_INT4_TO_INT32,
+            _rknn_matmul_quant_type_RKNN_QUANT_TYPE_PER_LAYER_SYM,
+            _rknn_matmul_quant_type_RKNN_QUANT_TYPE_PER_CHANNEL_SYM,
+            RKNN_CUSTOM_OP_VERSION,
+            _rknn_target_type_RKNN_TARGET_TYPE_CPU
+        );
+
+        #[cfg(rknn_sdk = "v2_3_0")]
+        assert_same_value!(_rknn_matmul_quant_type_RKNN_QUANT_TYPE_PER_GROUP_SYM);
+    }
+}